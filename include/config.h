@@ -25,6 +25,9 @@
 #define DEFAULT_WG_INTERFACE    "jnk0"
 #define DEFAULT_STORAGE_SIZE    "10G"
 #define DEFAULT_WG_KEEPALIVE    25
+#define DEFAULT_QUOTA_WARN_PERCENT 90    /* Soft warning band below the storage quota */
+#define DEFAULT_MAX_JSON_BODY   "64K"   /* Largest JSON request body (mesh endpoints) */
+#define DEFAULT_MAX_CHUNK_BODY  "4M"    /* Largest POST /chunks/<hash> body */
 
 /* Maximum lengths for various strings */
 #define MAX_PATH_LEN            4096
@@ -35,10 +38,17 @@
 #define MAX_DATA_DIRS           8       /* Max chunk storage directories */
 #define MAX_DATA_MOUNT_POINTS   16      /* Max mesh mount points */
 #define MAX_WG_PEERS            64      /* Max WireGuard peers */
+#define MAX_MESH_SECRET_LEN     128     /* Shared secret for chunk endpoints */
 
 #define NODE_STATE_NODE         "node"
 #define NODE_STATE_END          "end"
 
+#define READ_MODE_SOFT          "soft"  /* Give up with EAGAIN after a short retry budget */
+#define READ_MODE_HARD          "hard"  /* Keep retrying unreachable peers until interrupted */
+#define ENDPOINT_PREF_AUTO      "auto"  /* Resolver order */
+#define ENDPOINT_PREF_V4        "v4"    /* Try IPv4 addresses first */
+#define ENDPOINT_PREF_V6        "v6"    /* Try IPv6 addresses first */
+
 
 /* ============================================================================
  * SECTION 2: WireGuard Configuration Structure
@@ -76,20 +86,35 @@ typedef struct {
     /* Storage configuration */
     char storage_size[32];              /* Human-readable: "10G", "500M", etc. */
     size_t max_storage_bytes;           /* Parsed value in bytes */
+    char drive_reserve[32];             /* Free space kept back per data dir: "5G", "10%" or "" */
+    int drive_reserve_in_file;          /* Read from the config file, so saves keep it (else env default) */
+    int quota_warn_percent;             /* Warn at this % of max_storage_bytes (0 = off) */
 
     /* File paths */
     char data_dir[MAX_PATH_LEN];        /* Primary metadata + chunk dir */
     char data_dirs[MAX_DATA_DIRS][MAX_PATH_LEN]; /* Chunk store directories */
     size_t data_dir_count;              /* Number of chunk store dirs */
+    size_t data_dir_caps[MAX_DATA_DIRS];/* Max chunk bytes per data dir (0 = no cap) */
     char mount_point[MAX_PATH_LEN];     /* Where FUSE mounts the filesystem */
     char config_file_path[MAX_PATH_LEN];/* Path to this config file */
 
     /* Network configuration */
     uint16_t web_port;                  /* HTTP web interface port */
+    char mesh_secret[MAX_MESH_SECRET_LEN]; /* Signs /chunks requests; empty = refused */
+    int require_encrypted_mesh;         /* Chunks only move over WireGuard-routed wg_ip links */
+    char endpoint_preference[8];        /* "auto", "v4" or "v6" when dialing peers */
+    int endpoint_preference_in_file;    /* Read from the config file, so saves keep it */
+    size_t max_json_body_bytes;         /* Larger JSON request bodies get 413 */
+    size_t max_chunk_body_bytes;        /* Larger chunk uploads get 413 */
+    int max_json_body_in_file;          /* Read from the config file, so saves keep it */
+    int max_chunk_body_in_file;
 
     /* Node role */
     char node_state[8];                 /* "node" or "end" */
 
+    /* Chunk reads from the mesh */
+    char read_mode[8];                  /* "soft" or "hard" (like NFS mounts) */
+
     /* WireGuard mesh configuration */
     junknas_wg_config_t wg;             /* Nested WireGuard config */
 
@@ -98,12 +123,14 @@ typedef struct {
     int bootstrap_peer_count;           /* How many bootstrap peers are set */
     uint64_t bootstrap_peers_updated_at;/* Unix epoch seconds for mesh propagation */
     int bootstrap_peer_status[MAX_BOOTSTRAP_PEERS]; /* 1=reachable, 0=dead end, -1=unknown */
+    uint64_t bootstrap_peer_changed_at[MAX_BOOTSTRAP_PEERS]; /* Last status change (runtime only) */
 
     /* WireGuard peers for full mesh sync */
     junknas_wg_peer_t wg_peers[MAX_WG_PEERS];
     int wg_peer_count;
     uint64_t wg_peers_updated_at;
     int wg_peer_status[MAX_WG_PEERS];  /* 1=connected, 0=unreachable, -1=connecting */
+    uint64_t wg_peer_changed_at[MAX_WG_PEERS]; /* Last status/peer change (runtime only) */
     uint16_t wg_peer_keepalive;        /* Default persistent keepalive for WG peers */
 
     /* Mesh data mount points (for cross-node discovery) */
@@ -115,6 +142,9 @@ typedef struct {
     int verbose;                        /* Enable verbose logging? */
     int enable_fuse;                    /* Mount FUSE filesystem? */
     int daemon_mode;                    /* Run as background daemon? */
+    int config_read_only;               /* Config dir unwritable: keep changes in memory (runtime only) */
+    double attr_ttl;                    /* --attr-ttl seconds, -1 = unset (runtime only) */
+    double entry_ttl;                   /* --entry-ttl seconds, -1 = unset (runtime only) */
 
     pthread_mutex_t lock;
 } junknas_config_t;
@@ -217,6 +247,14 @@ int junknas_default_config_file(char *out, size_t out_len);
  */
 int junknas_config_set_wg_peers(junknas_config_t *config, const junknas_wg_peer_t *peers, int count);
 
+/*
+ * Set a peer's runtime status, bumping its changed_at when the value differs.
+ * Caller must hold the config lock.
+ * Returns the previous status, or -2 if index is out of range.
+ */
+int junknas_config_set_wg_peer_status(junknas_config_t *config, int index, int status);
+int junknas_config_set_bootstrap_peer_status(junknas_config_t *config, int index, int status);
+
 /*
  * Lock/unlock helpers for shared config access.
  */
@@ -231,5 +269,37 @@ void junknas_config_unlock(junknas_config_t *config);
  */
 size_t junknas_parse_storage_size(const char *size_str);
 
+/*
+ * Resolve a drive_reserve value against a filesystem's total size
+ * e.g., "2G" -> 2147483648, "10%" of 1T -> 109951162777
+ * @param spec          Size string, "<n>%" (0-99), or "" for no reserve
+ * @param total_bytes   Size of the filesystem holding the data dir
+ * @return              Reserve in bytes (0 for none or on parse error)
+ */
+uint64_t junknas_drive_reserve_bytes(const char *spec, uint64_t total_bytes);
+
+/*
+ * Checks shared by the mesh sync client and server for gossiped payloads,
+ * whose strings are stored verbatim.
+ */
+struct cJSON;
+
+/*
+ * Whether a gossiped string can be stored as-is: non-empty, shorter than
+ * max_len, and free of whitespace/control characters.
+ */
+int junknas_is_clean_string(const char *value, size_t max_len);
+
+/*
+ * Keep absolute, untruncated, non-duplicate mount points from a JSON array.
+ * Returns how many were copied to out (at most max); *rejected counts the rest.
+ */
+int junknas_sanitize_mount_points(struct cJSON *mounts, char out[][MAX_PATH_LEN], int max, int *rejected);
+
+/*
+ * A peer listed twice under one endpoint: the later entry wins.
+ * Compacts peers in place, updates *count and returns how many were dropped.
+ */
+int junknas_collapse_duplicate_peers(junknas_wg_peer_t *peers, int *count);
 
 #endif /* JUNKNAS_CONFIG_H */
@@ -0,0 +1,74 @@
+/*
+ * junkNAS - Per-directory create policy
+ *
+ * A directory may carry a policy for entries created under it: which
+ * requested permission bits to keep, which to force on, and a group for
+ * new entries. It covers the directory's own children, and deeper
+ * subdirectories too when `inherit` is set. The policy that applies is the
+ * one on the nearest ancestor that has any.
+ *
+ * Stored as "key value" lines in a hidden file in the backing directory:
+ *   mask 0775
+ *   force 0664
+ *   group 100
+ *   inherit 1
+ */
+
+#ifndef JUNKNAS_CREATE_POLICY_H
+#define JUNKNAS_CREATE_POLICY_H
+
+#include <stddef.h>
+#include <sys/types.h>
+
+#define JUNKNAS_CREATE_POLICY_FILE  ".jnkpolicy"
+#define JUNKNAS_CREATE_POLICY_XATTR "user.junknas.create_policy"
+#define JUNKNAS_CREATE_POLICY_TEXT_LEN 96
+
+typedef struct {
+    mode_t mask;        /* requested permission bits kept (default 0777) */
+    mode_t force;       /* permission bits always set (default 0) */
+    int    has_group;
+    gid_t  gid;         /* group of new entries when has_group */
+    int    inherit;     /* also applies below direct children */
+} junknas_create_policy_t;
+
+/*
+ * Policy with nothing forced: mask 0777, force 0, no group, no inherit.
+ */
+void junknas_create_policy_default(junknas_create_policy_t *p);
+
+/*
+ * Parse policy text (not necessarily NUL-terminated).
+ * Missing keys keep their defaults.
+ * Returns 0, or -1 on an unknown key or out-of-range value.
+ */
+int junknas_create_policy_parse(const char *text, size_t len, junknas_create_policy_t *out);
+
+/*
+ * Write the canonical text for a policy.
+ * Returns its length, or -1 if out is too small.
+ */
+int junknas_create_policy_format(const junknas_create_policy_t *p, char *out, size_t out_len);
+
+/*
+ * Effective permission bits for a create asking for `requested`:
+ * (requested & mask) | force. A NULL policy keeps requested & 0777.
+ */
+mode_t junknas_create_policy_mode(const junknas_create_policy_t *p, mode_t requested);
+
+/*
+ * Load the policy stored on one backing directory.
+ * Returns 1 if it has one, 0 if not, -1 if the file is unreadable or malformed.
+ */
+int junknas_create_policy_load(const char *dir, junknas_create_policy_t *out);
+
+/*
+ * Find the policy for a new entry at `path` (absolute, under `root`),
+ * walking up from its parent directory to `root`. The nearest directory
+ * with a policy decides: it applies if it is the parent, or if it
+ * inherits.
+ * Returns 1 with *out filled, 0 if no policy applies, -1 on error.
+ */
+int junknas_create_policy_resolve(const char *root, const char *path, junknas_create_policy_t *out);
+
+#endif /* JUNKNAS_CREATE_POLICY_H */
@@ -0,0 +1,40 @@
+/*
+ * junkNAS - In-process event feed for the web UI (server-sent events)
+ */
+
+#ifndef JUNKNAS_EVENTS_H
+#define JUNKNAS_EVENTS_H
+
+#include <stdint.h>
+
+#define JUNKNAS_EVENT_TYPE_LEN 32
+#define JUNKNAS_EVENT_DATA_LEN 512
+
+typedef struct {
+    uint64_t seq;                          /* Monotonic id, starts at 1 */
+    uint64_t time;                         /* Unix time the event was published */
+    char type[JUNKNAS_EVENT_TYPE_LEN];     /* e.g. "peer_status", "config_updated" */
+    char data[JUNKNAS_EVENT_DATA_LEN];     /* JSON object text */
+} junknas_event_t;
+
+/*
+ * Publish an event to every subscriber.
+ * data_json may be NULL (sent as "{}"); oversized payloads are dropped to "{}".
+ */
+void junknas_events_publish(const char *type, const char *data_json);
+
+/*
+ * Wait for the first event with seq > after_seq.
+ * If after_seq has already fallen out of the backlog, the oldest retained
+ * event is returned instead; out->seq > after_seq + 1 tells the caller
+ * events were skipped.
+ * Returns 1 with *out filled, 0 on timeout.
+ */
+int junknas_events_wait(uint64_t after_seq, junknas_event_t *out, int timeout_ms);
+
+/*
+ * Sequence number of the most recently published event (0 if none).
+ */
+uint64_t junknas_events_latest(void);
+
+#endif /* JUNKNAS_EVENTS_H */
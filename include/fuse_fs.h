@@ -20,6 +20,10 @@
 #include "config.h"
 #include "mesh.h"
 
+/* Backing dir layout shared with the web server's dedup tools. */
+#define JUNKNAS_META_SUFFIX  ".__jnkmeta"          /* manifest next to each file name */
+#define JUNKNAS_REFS_SUBDIR  ".jnk/refs"           /* <ab>/<hash>.ref refcounts */
+
 /*
  * Start the FUSE filesystem.
  *
@@ -35,4 +39,32 @@ int junknas_fuse_run(const junknas_config_t *cfg,
                      int argc,
                      char **argv);
 
+/*
+ * Run fn while the mounted filesystem can't change a manifest or a
+ * refcount. open_hashes lists the manifest lines of files that were
+ * unlinked or replaced while open: their names are gone but they keep
+ * their refs until the last close.
+ *
+ * @return          fn's result, or -EAGAIN if nothing is mounted
+ */
+int junknas_fuse_with_refs_locked(int (*fn)(void *ctx, char **open_hashes, size_t open_count),
+                                  void *ctx);
+
+/*
+ * Pick the store dir for a chunk of len bytes arriving from outside the
+ * mount (peer uploads), by the same rule the filesystem uses for its own
+ * chunks: the quota, read-only dirs, drive_reserve and data_dir_caps.
+ *
+ * @return          0 with the dir in out, -ENOSPC if no dir has room,
+ *                  -EROFS if every dir is read-only, -EAGAIN if nothing
+ *                  is mounted
+ */
+int junknas_fuse_store_dir_for_chunk(size_t len, char *out, size_t out_len);
+
+/*
+ * Count a chunk of len bytes stored into dir (as returned above) toward
+ * that dir's usage, which the filesystem keeps as a running total.
+ */
+void junknas_fuse_store_chunk_added(const char *dir, size_t len);
+
 #endif /* JUNKNAS_FUSE_FS_H */
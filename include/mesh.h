@@ -9,6 +9,7 @@
 #include <stdint.h>
 
 #include "config.h"
+#include "stats.h"
 
 typedef struct junknas_mesh junknas_mesh_t;
 
@@ -25,12 +26,16 @@ void junknas_mesh_stop(junknas_mesh_t *mesh);
 
 /*
  * Try to fetch a chunk from the mesh into dest_path.
- * Returns 0 on success, -1 on failure/not found.
+ * Returns 0 on success, -ENOENT if every peer answered without it,
+ * -EAGAIN if at least one peer could not be reached (worth retrying),
+ * -EACCES if no peer could be asked (none has a wg_ip, or require_encrypted_mesh
+ * is set and no wg_ip is routed through WireGuard), -1 on bad arguments.
  */
 int junknas_mesh_fetch_chunk(junknas_mesh_t *mesh, const char *hashhex, const char *dest_path);
 
 /*
- * Replicate a chunk to known mesh peers (best-effort).
+ * Replicate a chunk to known mesh peers (best-effort). Peers without an
+ * encrypted link are skipped when require_encrypted_mesh is set.
  * Returns 0 if dispatched, -1 on error.
  */
 int junknas_mesh_replicate_chunk(junknas_mesh_t *mesh,
@@ -38,6 +43,73 @@ int junknas_mesh_replicate_chunk(junknas_mesh_t *mesh,
                                 const uint8_t *data,
                                 size_t len);
 
+/*
+ * Build the X-Junknas-Auth header value ("<unix ts>:<nonce>:<hmac hex>") that
+ * signs "<method> <path> <ts> <nonce>" with the shared mesh secret. The nonce
+ * is up to JUNKNAS_MESH_AUTH_NONCE_LEN hex digits, fresh for every request.
+ * Returns 0 on success, -1 if the secret is empty, the nonce malformed or out
+ * is too small.
+ */
+int junknas_mesh_auth_sign(const char *secret, const char *method, const char *path,
+                           uint64_t ts, const char *nonce, char *out, size_t out_len);
+
+/*
+ * Check an X-Junknas-Auth header value against the shared mesh secret.
+ * The timestamp must be within JUNKNAS_MESH_AUTH_WINDOW_SEC of now, and a
+ * value that was already accepted inside the window is refused as a replay.
+ * Returns 0 if valid, -1 otherwise (always -1 with an empty secret).
+ */
+int junknas_mesh_auth_verify(const char *secret, const char *method, const char *path,
+                             const char *value, uint64_t now);
+
+#define JUNKNAS_MESH_AUTH_WINDOW_SEC 300
+#define JUNKNAS_MESH_AUTH_NONCE_LEN 32
+
+typedef enum {
+    JUNKNAS_MESH_LINK_PLAINTEXT = 0,    /* not over WireGuard, unsigned requests */
+    JUNKNAS_MESH_LINK_AUTHENTICATED,    /* not over WireGuard, requests signed with mesh_secret */
+    JUNKNAS_MESH_LINK_ENCRYPTED         /* the peer's wg_ip is routed through a WireGuard interface */
+} junknas_mesh_link_security_t;
+
+/*
+ * How chunk traffic to this peer is protected, given whether mesh_secret is
+ * set and whether its wg_ip is routed through WireGuard right now (see
+ * junknas_net_route_is_wireguard). A wg_ip that goes out any other way,
+ * say while the tunnel is down, is not encrypted.
+ */
+junknas_mesh_link_security_t junknas_mesh_peer_security(const junknas_wg_peer_t *peer,
+                                                        int have_secret,
+                                                        int wg_routed);
+
+/*
+ * "plaintext", "authenticated" or "encrypted".
+ */
+const char *junknas_mesh_link_security_name(junknas_mesh_link_security_t security);
+
+#define JUNKNAS_MESH_MAX_CANDIDATES 2
+
+typedef struct {
+    char     host[MAX_ENDPOINT_LEN];
+    uint16_t port;
+} junknas_mesh_candidate_t;
+
+/*
+ * Addresses to try for a peer's web port (mesh sync), best first: its wg_ip
+ * with web_port (or default_web_port), then its advertised endpoint, which
+ * still works before the tunnel is up. Duplicates are listed once.
+ * Returns how many were written to out (0 if the peer has neither).
+ */
+int junknas_mesh_peer_candidates(const junknas_wg_peer_t *peer, uint16_t default_web_port,
+                                 junknas_mesh_candidate_t out[JUNKNAS_MESH_MAX_CANDIDATES]);
+
+/*
+ * Traffic with a peer, whichever of its candidates was dialed: chunk
+ * transfers go to its wg_ip, syncs to its wg_ip or endpoint, and each is
+ * counted under the address used. Returns 0, or -1 if nothing was recorded.
+ */
+int junknas_mesh_peer_traffic(const junknas_wg_peer_t *peer, uint16_t default_web_port,
+                              junknas_peer_traffic_t *out);
+
 /*
  * Whether the mesh has at least one active peer.
  */
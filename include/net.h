@@ -0,0 +1,44 @@
+/*
+ * junkNAS - Network helpers shared by the mesh client and web server
+ */
+
+#ifndef JUNKNAS_NET_H
+#define JUNKNAS_NET_H
+
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/socket.h>
+
+/*
+ * Connect to host:port, trying addresses of the preferred family first
+ * (ENDPOINT_PREF_V4/V6, anything else means no preference) and falling
+ * back to the others. Each address gets dial_timeout_ms to connect; the
+ * connected socket's sends and receives time out after io_timeout_ms.
+ * Returns a connected fd, -1 if the name didn't resolve, -2 if no address
+ * accepted the connection.
+ */
+int junknas_net_connect(const char *host, uint16_t port, const char *preference,
+                        int dial_timeout_ms, int io_timeout_ms);
+
+/*
+ * send() until everything is out, without SIGPIPE.
+ * Returns 0, or -1 on error or send timeout.
+ */
+int junknas_net_send_all(int fd, const void *data, size_t len);
+
+/*
+ * Whether addr (AF_INET or AF_INET6) is assigned to a WireGuard interface
+ * on this host.
+ * Returns 1 if it is, 0 if it's on another interface or on none.
+ */
+int junknas_net_addr_on_wireguard(const struct sockaddr *addr);
+
+/*
+ * Whether traffic to ip (a numeric address) would leave through a
+ * WireGuard interface: the source address the kernel picks for it must
+ * sit on one. Nothing is sent.
+ * Returns 1 if so, 0 if not, -1 if ip isn't an address or has no route.
+ */
+int junknas_net_route_is_wireguard(const char *ip);
+
+#endif /* JUNKNAS_NET_H */
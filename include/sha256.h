@@ -0,0 +1,39 @@
+/*
+ * junkNAS - SHA-256 / HMAC-SHA-256 helpers (chunk addressing + peer auth)
+ */
+
+#ifndef JUNKNAS_SHA256_H
+#define JUNKNAS_SHA256_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct {
+    uint32_t h[8];
+    uint64_t len_bits;
+    uint8_t  buf[64];
+    size_t   buf_len;
+} junknas_sha256_ctx;
+
+void junknas_sha256_init(junknas_sha256_ctx *c);
+void junknas_sha256_update(junknas_sha256_ctx *c, const void *data, size_t n);
+void junknas_sha256_final(junknas_sha256_ctx *c, uint8_t out[32]);
+
+/*
+ * Lower-case hex encoding of a 32-byte digest (hex must hold 65 bytes).
+ */
+void junknas_sha256_hex(const uint8_t digest[32], char hex[65]);
+
+/*
+ * One-shot SHA-256 of a buffer, as hex.
+ */
+void junknas_sha256_buf_hex(const void *data, size_t n, char hex[65]);
+
+/*
+ * HMAC-SHA-256 of msg under key, as hex.
+ */
+void junknas_hmac_sha256_hex(const void *key, size_t key_len,
+                             const void *msg, size_t msg_len,
+                             char hex[65]);
+
+#endif /* JUNKNAS_SHA256_H */
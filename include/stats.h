@@ -0,0 +1,139 @@
+/*
+ * junkNAS - Process-wide counters for FUSE, chunk store and mesh activity
+ */
+
+#ifndef JUNKNAS_STATS_H
+#define JUNKNAS_STATS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#include "config.h"
+
+typedef enum {
+    JUNKNAS_STAT_CHUNK_READ_LOCAL = 0,
+    JUNKNAS_STAT_CHUNK_READ_MESH,
+    JUNKNAS_STAT_CHUNK_READ_FAILED,
+    JUNKNAS_STAT_CHUNK_WRITE_NEW,
+    JUNKNAS_STAT_CHUNK_WRITE_DEDUP,
+    JUNKNAS_STAT_CHUNK_WRITE_FAILED,
+    JUNKNAS_STAT_MESH_FETCH_OK,
+    JUNKNAS_STAT_MESH_FETCH_FAILED,
+    JUNKNAS_STAT_MESH_REPLICATE_OK,
+    JUNKNAS_STAT_MESH_REPLICATE_FAILED,
+    JUNKNAS_STAT_MESH_SYNC_OK,
+    JUNKNAS_STAT_MESH_SYNC_FAILED,
+    JUNKNAS_STAT_MESH_PEER_REJECTED,
+    JUNKNAS_STAT_MESH_MOUNT_REJECTED,
+    JUNKNAS_STAT_WRITE_BYTES_LOGICAL,
+    JUNKNAS_STAT_WRITE_BYTES_STORED,
+    JUNKNAS_STAT_WRITE_BYTES_REPLICATED,
+    JUNKNAS_STAT_MESH_FETCH_BLOCKED,
+    JUNKNAS_STAT_MESH_REPLICATE_BLOCKED,
+    JUNKNAS_STAT_SCRUB_CHECKED,
+    JUNKNAS_STAT_SCRUB_CORRUPT,
+    JUNKNAS_STAT_SCRUB_REPAIRED,
+    JUNKNAS_STAT_COUNTER_COUNT
+} junknas_stat_counter_t;
+
+typedef enum {
+    JUNKNAS_FUSE_OP_GETATTR = 0,
+    JUNKNAS_FUSE_OP_READDIR,
+    JUNKNAS_FUSE_OP_CREATE,
+    JUNKNAS_FUSE_OP_OPEN,
+    JUNKNAS_FUSE_OP_READ,
+    JUNKNAS_FUSE_OP_WRITE,
+    JUNKNAS_FUSE_OP_RELEASE,
+    JUNKNAS_FUSE_OP_UNLINK,
+    JUNKNAS_FUSE_OP_RENAME,
+    JUNKNAS_FUSE_OP_COUNT
+} junknas_fuse_op_t;
+
+/*
+ * Bump a counter by one / by n. Safe to call from any thread.
+ */
+void junknas_stats_inc(junknas_stat_counter_t counter);
+void junknas_stats_add(junknas_stat_counter_t counter, uint64_t n);
+
+/*
+ * Current value of a counter.
+ */
+uint64_t junknas_stats_get(junknas_stat_counter_t counter);
+
+/*
+ * Monotonic clock in microseconds, for timing operations.
+ */
+uint64_t junknas_stats_now_usec(void);
+
+/*
+ * Record the latency of one FUSE operation.
+ */
+void junknas_stats_observe_fuse_op(junknas_fuse_op_t op, uint64_t usec);
+
+/*
+ * Count one FUSE operation that ran over its latency budget.
+ */
+void junknas_stats_count_slow_fuse_op(junknas_fuse_op_t op);
+
+/*
+ * Short name of a FUSE operation ("getattr", "read", ...), as used in metric labels.
+ */
+const char *junknas_stats_fuse_op_name(junknas_fuse_op_t op);
+
+/* Per-peer HTTP traffic, keyed by the "host:port" we dialed. */
+#define JUNKNAS_STATS_MAX_PEERS (MAX_WG_PEERS + MAX_BOOTSTRAP_PEERS)
+
+typedef struct {
+    char endpoint[MAX_ENDPOINT_LEN];
+    uint64_t bytes_sent;
+    uint64_t bytes_received;
+    uint64_t requests;
+    uint64_t failures;
+} junknas_peer_traffic_t;
+
+/*
+ * Account one request to a peer. Peers beyond JUNKNAS_STATS_MAX_PEERS are not tracked.
+ */
+void junknas_stats_record_peer(const char *endpoint, uint64_t sent, uint64_t received, int ok);
+
+/*
+ * Copy the counters for one peer. Returns 0 if found, -1 otherwise.
+ */
+int junknas_stats_get_peer(const char *endpoint, junknas_peer_traffic_t *out);
+
+/*
+ * Copy up to max peer entries into out. Returns the number copied.
+ */
+int junknas_stats_peer_snapshot(junknas_peer_traffic_t *out, int max);
+
+/*
+ * Growable text buffer for building exposition output. Start from {0} (or a
+ * malloc'd buf/cap); after an allocation failure `failed` is set and further
+ * appends are ignored. The caller frees buf.
+ */
+typedef struct {
+    char *buf;
+    size_t len;
+    size_t cap;
+    int failed;
+} junknas_strbuf_t;
+
+void junknas_strbuf_printf(junknas_strbuf_t *sb, const char *fmt, ...)
+    __attribute__((format(printf, 2, 3)));
+
+/* Room for an escaped label value built from a string of up to n bytes. */
+#define JUNKNAS_STATS_LABEL_LEN(n) (2 * (n) + 1)
+
+/*
+ * Escape a Prometheus label value (backslash, double quote, newline) into out.
+ * Output that doesn't fit is cut at a character boundary.
+ */
+void junknas_stats_escape_label(const char *in, char *out, size_t out_len);
+
+/*
+ * Render every counter and histogram in Prometheus text exposition format.
+ * Returns a malloc'd string (caller frees) or NULL on allocation failure.
+ */
+char *junknas_stats_render_prometheus(void);
+
+#endif /* JUNKNAS_STATS_H */
@@ -9,12 +9,19 @@
  * Expected JSON shape (example):
  * {
  *   "storage_size": "10G",
+ *   "quota_warn_percent": 90,
  *   "data_dir": "$HOME/.local/share/junknas/data",
+ *   "data_dir_caps": ["200G", ""],
  *   "mount_point": "/mnt/junknas",
  *   "web_port": 8080,
  *   "verbose": 1,
  *   "enable_fuse": 1,
  *   "daemon_mode": 0,
+ *   "read_mode": "soft",
+ *   "endpoint_preference": "auto",
+ *   "max_json_body": "64K",
+ *   "max_chunk_body": "4M",
+ *   "drive_reserve": "5%",
  *   "wireguard": {
  *     "interface_name": "jnk0",
  *     "private_key": "BASE64...",
@@ -45,6 +52,7 @@
 #include <sys/syscall.h>
 #include <sys/stat.h>
 #include <sys/types.h>
+#include <time.h>
 #include <unistd.h>
 #include <ctype.h>
 #include <pwd.h>
@@ -486,8 +494,8 @@ static int read_entire_file(const char *path, char **out_buf, size_t *out_len) {
 }
 
 /* Write a string to file atomically-ish:
- * - Writes to "<path>.tmp"
- * - Renames to "<path>"
+ * - Writes and fsyncs "<path>.tmp"
+ * - Renames to "<path>", then fsyncs the directory
  */
 static int write_entire_file_atomic(const char *path, const char *data) {
     if (!path || !data) return -1;
@@ -509,13 +517,16 @@ static int write_entire_file_atomic(const char *path, const char *data) {
         }
     }
 
-    if (fflush(f) != 0) {
+    if (fflush(f) != 0 || fsync(fileno(f)) != 0) {
         fclose(f);
         (void)remove(tmp_path);
         return -1;
     }
 
-    fclose(f);
+    if (fclose(f) != 0) {
+        (void)remove(tmp_path);
+        return -1;
+    }
 
     /* rename() is atomic on POSIX when within same filesystem */
     if (rename(tmp_path, path) != 0) {
@@ -523,7 +534,21 @@ static int write_entire_file_atomic(const char *path, const char *data) {
         return -1;
     }
 
-    return 0;
+    char dir[MAX_PATH_LEN];
+    (void)snprintf(dir, sizeof(dir), "%s", path);
+    char *slash = strrchr(dir, '/');
+    if (slash == dir) {
+        dir[1] = '\0';
+    } else if (slash) {
+        *slash = '\0';
+    } else {
+        (void)snprintf(dir, sizeof(dir), ".");
+    }
+    int dfd = open(dir, O_RDONLY | O_DIRECTORY);
+    if (dfd < 0) return -1;
+    int rc = fsync(dfd);
+    close(dfd);
+    return (rc == 0) ? 0 : -1;
 }
 
 static int normalize_key_string(const char *input, char *out, size_t out_len) {
@@ -715,6 +740,8 @@ int junknas_config_add_bootstrap_peer(junknas_config_t *config, const char *endp
 
     /* Copy endpoint into fixed buffer */
     (void)safe_strcpy(config->bootstrap_peers[idx], MAX_ENDPOINT_LEN, endpoint);
+    config->bootstrap_peer_status[idx] = -1;
+    config->bootstrap_peer_changed_at[idx] = (uint64_t)time(NULL);
 
     config->bootstrap_peer_count++;
     return 0;
@@ -784,6 +811,7 @@ int junknas_config_upsert_wg_peer(junknas_config_t *config, const junknas_wg_pee
         if (!match) continue;
         if (wg_peer_equal(&config->wg_peers[i], peer)) return 0;
         config->wg_peers[i] = *peer;
+        config->wg_peer_changed_at[i] = (uint64_t)time(NULL);
         return 1;
     }
 
@@ -791,6 +819,8 @@ int junknas_config_upsert_wg_peer(junknas_config_t *config, const junknas_wg_pee
         return -1;
     }
 
+    config->wg_peer_status[config->wg_peer_count] = -1;
+    config->wg_peer_changed_at[config->wg_peer_count] = (uint64_t)time(NULL);
     config->wg_peers[config->wg_peer_count++] = *peer;
     return 1;
 }
@@ -798,14 +828,87 @@ int junknas_config_upsert_wg_peer(junknas_config_t *config, const junknas_wg_pee
 int junknas_config_set_wg_peers(junknas_config_t *config, const junknas_wg_peer_t *peers, int count) {
     if (!config || !peers || count < 0 || count > MAX_WG_PEERS) return -1;
 
+    uint64_t now = (uint64_t)time(NULL);
     config->wg_peer_count = 0;
     for (int i = 0; i < count; i++) {
         if (peers[i].public_key[0] == '\0') continue;
+        config->wg_peer_changed_at[config->wg_peer_count] = now;
         config->wg_peers[config->wg_peer_count++] = peers[i];
     }
     return 0;
 }
 
+int junknas_config_set_wg_peer_status(junknas_config_t *config, int index, int status) {
+    if (!config || index < 0 || index >= config->wg_peer_count) return -2;
+    int prev = config->wg_peer_status[index];
+    if (prev != status) {
+        config->wg_peer_status[index] = status;
+        config->wg_peer_changed_at[index] = (uint64_t)time(NULL);
+    }
+    return prev;
+}
+
+int junknas_config_set_bootstrap_peer_status(junknas_config_t *config, int index, int status) {
+    if (!config || index < 0 || index >= config->bootstrap_peer_count) return -2;
+    int prev = config->bootstrap_peer_status[index];
+    if (prev != status) {
+        config->bootstrap_peer_status[index] = status;
+        config->bootstrap_peer_changed_at[index] = (uint64_t)time(NULL);
+    }
+    return prev;
+}
+
+int junknas_is_clean_string(const char *value, size_t max_len) {
+    if (!value || value[0] == '\0') return 0;
+    size_t n = 0;
+    for (const unsigned char *c = (const unsigned char *)value; *c; c++, n++) {
+        if (*c <= ' ' || *c == 0x7f) return 0;
+    }
+    return n < max_len;
+}
+
+int junknas_sanitize_mount_points(struct cJSON *mounts, char out[][MAX_PATH_LEN], int max, int *rejected) {
+    int count = 0;
+    int n = cJSON_GetArraySize(mounts);
+    for (int i = 0; i < n; i++) {
+        cJSON *entry = cJSON_GetArrayItem(mounts, i);
+        const char *value = (cJSON_IsString(entry) && entry->valuestring) ? entry->valuestring : NULL;
+        if (!value || value[0] != '/' || strlen(value) >= MAX_PATH_LEN) {
+            (*rejected)++;
+            continue;
+        }
+        int dup = 0;
+        for (int k = 0; k < count; k++) {
+            if (strcmp(out[k], value) == 0) dup = 1;
+        }
+        if (dup) {
+            (*rejected)++;
+            continue;
+        }
+        if (count >= max) continue;
+        snprintf(out[count], MAX_PATH_LEN, "%s", value);
+        count++;
+    }
+    return count;
+}
+
+int junknas_collapse_duplicate_peers(junknas_wg_peer_t *peers, int *count) {
+    int dropped = 0;
+    for (int i = 0; i < *count; i++) {
+        if (peers[i].endpoint[0] == '\0') continue;
+        for (int k = i + 1; k < *count; k++) {
+            if (strcmp(peers[i].endpoint, peers[k].endpoint) == 0) {
+                memmove(&peers[i], &peers[i + 1], (size_t)(*count - i - 1) * sizeof(peers[0]));
+                (*count)--;
+                dropped++;
+                i--;
+                break;
+            }
+        }
+    }
+    return dropped;
+}
+
 static int g_startup_verbose = 0;
 
 void junknas_config_set_startup_verbose(int verbose) {
@@ -830,6 +933,68 @@ static int is_valid_node_state(const char *state) {
     return (strcmp(state, NODE_STATE_NODE) == 0 || strcmp(state, NODE_STATE_END) == 0);
 }
 
+static int is_valid_read_mode(const char *mode) {
+    if (!mode) return 0;
+    return (strcmp(mode, READ_MODE_SOFT) == 0 || strcmp(mode, READ_MODE_HARD) == 0);
+}
+
+static int is_valid_endpoint_preference(const char *pref) {
+    if (!pref) return 0;
+    return (strcmp(pref, ENDPOINT_PREF_AUTO) == 0 || strcmp(pref, ENDPOINT_PREF_V4) == 0 ||
+            strcmp(pref, ENDPOINT_PREF_V6) == 0);
+}
+
+static int parse_reserve_percent(const char *spec, unsigned long *out) {
+    size_t n = strlen(spec);
+    if (n < 2 || spec[n - 1] != '%') return -1;
+    errno = 0;
+    char *end = NULL;
+    unsigned long pct = strtoul(spec, &end, 10);
+    if (errno != 0 || end == spec || end != spec + n - 1 || pct >= 100) return -1;
+    *out = pct;
+    return 0;
+}
+
+static int is_valid_drive_reserve(const char *spec) {
+    if (!spec) return 0;
+    if (spec[0] == '\0' || strcmp(spec, "0") == 0) return 1;
+    unsigned long pct = 0;
+    if (parse_reserve_percent(spec, &pct) == 0) return 1;
+    return junknas_parse_storage_size(spec) != 0;
+}
+
+uint64_t junknas_drive_reserve_bytes(const char *spec, uint64_t total_bytes) {
+    if (!spec || spec[0] == '\0') return 0;
+    unsigned long pct = 0;
+    if (parse_reserve_percent(spec, &pct) == 0) {
+        return (total_bytes / 100ULL) * pct;
+    }
+    return (uint64_t)junknas_parse_storage_size(spec);
+}
+
+static size_t size_from_env(const char *name, const char *fallback) {
+    size_t b = junknas_parse_storage_size(getenv(name));
+    return (b != 0) ? b : junknas_parse_storage_size(fallback);
+}
+
+/* Size keys may be written as "64K" or as a plain byte count.
+ * Returns 1 if the file set the value.
+ */
+static int load_size_field(cJSON *root, const char *key, size_t *out) {
+    cJSON *item = cJSON_GetObjectItemCaseSensitive(root, key);
+    if (cJSON_IsString(item) && item->valuestring) {
+        size_t b = junknas_parse_storage_size(item->valuestring);
+        if (b == 0) return 0;
+        *out = b;
+        return 1;
+    }
+    if (cJSON_IsNumber(item) && item->valuedouble >= 1) {
+        *out = (size_t)item->valuedouble;
+        return 1;
+    }
+    return 0;
+}
+
 static void set_defaults(junknas_config_t *config) {
     /* This function sets the full config structure to known defaults. */
     memset(config, 0, sizeof(*config));
@@ -839,6 +1004,14 @@ static void set_defaults(junknas_config_t *config) {
     (void)safe_strcpy(config->storage_size, sizeof(config->storage_size), DEFAULT_STORAGE_SIZE);
     config->max_storage_bytes = junknas_parse_storage_size(DEFAULT_STORAGE_SIZE);
 
+    config->quota_warn_percent = DEFAULT_QUOTA_WARN_PERCENT;
+
+    /* Drive reserve: the environment seeds it, the config file wins */
+    const char *reserve = getenv("JUNKNAS_DRIVE_RESERVE");
+    if (reserve && is_valid_drive_reserve(reserve)) {
+        (void)safe_strcpy(config->drive_reserve, sizeof(config->drive_reserve), reserve);
+    }
+
     /* Paths */
     (void)junknas_default_data_dir(config->data_dir, sizeof(config->data_dir));
     (void)junknas_default_data_dir(config->data_dirs[0], sizeof(config->data_dirs[0]));
@@ -848,14 +1021,24 @@ static void set_defaults(junknas_config_t *config) {
 
     /* Web */
     config->web_port = (uint16_t)DEFAULT_WEB_PORT;
+    (void)safe_strcpy(config->endpoint_preference, sizeof(config->endpoint_preference), ENDPOINT_PREF_AUTO);
+    const char *pref = getenv("JUNKNAS_ENDPOINT_PREFERENCE");
+    if (pref && is_valid_endpoint_preference(pref)) {
+        (void)safe_strcpy(config->endpoint_preference, sizeof(config->endpoint_preference), pref);
+    }
+    config->max_json_body_bytes = size_from_env("JUNKNAS_MAX_JSON_BODY", DEFAULT_MAX_JSON_BODY);
+    config->max_chunk_body_bytes = size_from_env("JUNKNAS_MAX_CHUNK_BODY", DEFAULT_MAX_CHUNK_BODY);
 
     /* Node role */
     (void)safe_strcpy(config->node_state, sizeof(config->node_state), NODE_STATE_NODE);
+    (void)safe_strcpy(config->read_mode, sizeof(config->read_mode), READ_MODE_SOFT);
 
     /* Runtime flags (sane defaults) */
     config->verbose = 0;
     config->enable_fuse = 1;
     config->daemon_mode = 0;
+    config->attr_ttl = -1;
+    config->entry_ttl = -1;
 
     /* WireGuard defaults */
     (void)safe_strcpy(config->wg.interface_name, sizeof(config->wg.interface_name), DEFAULT_WG_INTERFACE);
@@ -901,6 +1084,9 @@ int junknas_config_validate(const junknas_config_t *config) {
     if (config->wg.listen_port == 0) return -1;
 
     if (!is_valid_node_state(config->node_state)) return -1;
+    if (!is_valid_read_mode(config->read_mode)) return -1;
+    if (!is_valid_endpoint_preference(config->endpoint_preference)) return -1;
+    if (config->max_json_body_bytes == 0 || config->max_chunk_body_bytes == 0) return -1;
 
     /* Basic string sanity */
     if (config->data_dir[0] == '\0') return -1;
@@ -913,6 +1099,8 @@ int junknas_config_validate(const junknas_config_t *config) {
 
     /* Storage: require parse success */
     if (config->max_storage_bytes == 0) return -1;
+    if (!is_valid_drive_reserve(config->drive_reserve)) return -1;
+    if (config->quota_warn_percent < 0 || config->quota_warn_percent > 100) return -1;
 
     /* Bootstrap peers count range */
     if (config->bootstrap_peer_count < 0 || config->bootstrap_peer_count > MAX_BOOTSTRAP_PEERS) {
@@ -1018,7 +1206,9 @@ int junknas_config_ensure_wg_keys(junknas_config_t *config) {
 
     junknas_config_unlock(config);
 
-    if (should_write_private) {
+    if (should_write_private && config->config_read_only) {
+        config_log_verbose(config, "config: config dir is read-only, keeping WireGuard key in memory");
+    } else if (should_write_private) {
         if (ensure_parent_dir(private_key_path) != 0) {
             char config_dir[MAX_PATH_LEN];
             if (junknas_default_config_dir(config_dir, sizeof(config_dir)) != 0) {
@@ -1055,13 +1245,24 @@ int junknas_config_load(junknas_config_t *config, const char *config_file) {
     }
 
     cJSON *root = cJSON_Parse(json_text);
-    free(json_text);
-    json_text = NULL;
-
     if (!root) {
-        config_log_verbose(config, "config: failed to parse JSON in %s", config_file);
+        /* Saves are synced before and after the rename, so a bad file was
+         * most likely edited by hand, but a crash on a filesystem that
+         * doesn't honour fsync can leave one too. Either way say so loudly
+         * and leave it alone: the WireGuard key is in its own file and
+         * regenerating config would lose peers.
+         */
+        const char *err = cJSON_GetErrorPtr();
+        long offset = (err && err >= json_text) ? (long)(err - json_text) : -1;
+        fprintf(stderr,
+                "config: %s is not valid JSON (near byte %ld); it was edited by hand or damaged "
+                "by a crash. Fix or remove it, it was left untouched\n",
+                config_file, offset);
+        free(json_text);
         return -1;
     }
+    free(json_text);
+    json_text = NULL;
 
     /* storage_size */
     cJSON *storage_size = cJSON_GetObjectItemCaseSensitive(root, "storage_size");
@@ -1071,6 +1272,18 @@ int junknas_config_load(junknas_config_t *config, const char *config_file) {
         if (b != 0) config->max_storage_bytes = b;
     }
 
+    cJSON *warn_pct = cJSON_GetObjectItemCaseSensitive(root, "quota_warn_percent");
+    if (cJSON_IsNumber(warn_pct) && warn_pct->valuedouble >= 0 && warn_pct->valuedouble <= 100) {
+        config->quota_warn_percent = (int)warn_pct->valuedouble;
+    }
+
+    cJSON *drive_reserve = cJSON_GetObjectItemCaseSensitive(root, "drive_reserve");
+    if (cJSON_IsString(drive_reserve) && drive_reserve->valuestring &&
+        is_valid_drive_reserve(drive_reserve->valuestring)) {
+        (void)safe_strcpy(config->drive_reserve, sizeof(config->drive_reserve), drive_reserve->valuestring);
+        config->drive_reserve_in_file = 1;
+    }
+
     /* data_dir */
     cJSON *data_dir = cJSON_GetObjectItemCaseSensitive(root, "data_dir");
     if (cJSON_IsString(data_dir) && data_dir->valuestring) {
@@ -1116,6 +1329,22 @@ int junknas_config_load(junknas_config_t *config, const char *config_file) {
         }
     }
 
+    /* data_dir_caps: index-aligned with data_dirs; "" or 0 means no cap */
+    cJSON *caps = cJSON_GetObjectItemCaseSensitive(root, "data_dir_caps");
+    if (cJSON_IsArray(caps)) {
+        int n = cJSON_GetArraySize(caps);
+        for (int i = 0; i < n && i < MAX_DATA_DIRS; i++) {
+            cJSON *cap = cJSON_GetArrayItem(caps, i);
+            config->data_dir_caps[i] = 0;
+            if (cJSON_IsString(cap) && cap->valuestring) {
+                config->data_dir_caps[i] = junknas_parse_storage_size(cap->valuestring);
+            } else if (cJSON_IsNumber(cap) && cap->valuedouble >= 1) {
+                config->data_dir_caps[i] = (size_t)cap->valuedouble;
+            }
+        }
+    }
+
+
     /* mount_point */
     cJSON *mount_point = cJSON_GetObjectItemCaseSensitive(root, "mount_point");
     if (cJSON_IsString(mount_point) && mount_point->valuestring) {
@@ -1128,6 +1357,16 @@ int junknas_config_load(junknas_config_t *config, const char *config_file) {
         config->web_port = (uint16_t)web_port->valuedouble;
     }
 
+    /* mesh_secret */
+    cJSON *mesh_secret = cJSON_GetObjectItemCaseSensitive(root, "mesh_secret");
+    if (cJSON_IsString(mesh_secret) && mesh_secret->valuestring) {
+        (void)safe_strcpy(config->mesh_secret, sizeof(config->mesh_secret), mesh_secret->valuestring);
+    }
+
+    cJSON *require_encrypted = cJSON_GetObjectItemCaseSensitive(root, "require_encrypted_mesh");
+    if (cJSON_IsBool(require_encrypted)) config->require_encrypted_mesh = cJSON_IsTrue(require_encrypted) ? 1 : 0;
+    if (cJSON_IsNumber(require_encrypted)) config->require_encrypted_mesh = (require_encrypted->valueint != 0);
+
     /* runtime flags */
     cJSON *verbose = cJSON_GetObjectItemCaseSensitive(root, "verbose");
     if (cJSON_IsBool(verbose)) config->verbose = cJSON_IsTrue(verbose) ? 1 : 0;
@@ -1147,6 +1386,23 @@ int junknas_config_load(junknas_config_t *config, const char *config_file) {
         (void)safe_strcpy(config->node_state, sizeof(config->node_state), node_state->valuestring);
     }
 
+    cJSON *read_mode = cJSON_GetObjectItemCaseSensitive(root, "read_mode");
+    if (cJSON_IsString(read_mode) && read_mode->valuestring &&
+        is_valid_read_mode(read_mode->valuestring)) {
+        (void)safe_strcpy(config->read_mode, sizeof(config->read_mode), read_mode->valuestring);
+    }
+
+    config->max_json_body_in_file = load_size_field(root, "max_json_body", &config->max_json_body_bytes);
+    config->max_chunk_body_in_file = load_size_field(root, "max_chunk_body", &config->max_chunk_body_bytes);
+
+    cJSON *endpoint_pref = cJSON_GetObjectItemCaseSensitive(root, "endpoint_preference");
+    if (cJSON_IsString(endpoint_pref) && endpoint_pref->valuestring &&
+        is_valid_endpoint_preference(endpoint_pref->valuestring)) {
+        (void)safe_strcpy(config->endpoint_preference, sizeof(config->endpoint_preference),
+                          endpoint_pref->valuestring);
+        config->endpoint_preference_in_file = 1;
+    }
+
     /* wireguard object */
     cJSON *wg = cJSON_GetObjectItemCaseSensitive(root, "wireguard");
     if (cJSON_IsObject(wg)) {
@@ -1280,11 +1536,21 @@ int junknas_config_load(junknas_config_t *config, const char *config_file) {
 int junknas_config_save(const junknas_config_t *config, const char *config_file) {
     if (!config || !config_file) return -1;
 
+    /* Runtime updates (mesh sync, web edits) stay in memory; the notice was
+     * printed once at startup. Explicit saves elsewhere still go through.
+     */
+    if (config->config_read_only && strcmp(config_file, config->config_file_path) == 0) return 0;
+
     cJSON *root = cJSON_CreateObject();
     if (!root) return -1;
 
     /* top-level fields */
     cJSON_AddStringToObject(root, "storage_size", config->storage_size);
+    /* An env-derived default isn't written back: it would pin the value. */
+    if (config->drive_reserve_in_file) {
+        cJSON_AddStringToObject(root, "drive_reserve", config->drive_reserve);
+    }
+    cJSON_AddNumberToObject(root, "quota_warn_percent", (double)config->quota_warn_percent);
     cJSON_AddStringToObject(root, "data_dir", config->data_dir);
     cJSON *data_dirs_out = cJSON_CreateArray();
     if (!data_dirs_out) {
@@ -1298,9 +1564,36 @@ int junknas_config_save(const junknas_config_t *config, const char *config_file)
         const char *dir = (config->data_dir_count > 0) ? config->data_dirs[i] : config->data_dir;
         cJSON_AddItemToArray(data_dirs_out, cJSON_CreateString(dir));
     }
+    int has_caps = 0;
+    for (size_t i = 0; i < dir_count && i < MAX_DATA_DIRS; i++) {
+        if (config->data_dir_caps[i] != 0) has_caps = 1;
+    }
+    if (has_caps) {
+        cJSON *caps_out = cJSON_CreateArray();
+        if (!caps_out) {
+            cJSON_Delete(root);
+            return -1;
+        }
+        cJSON_AddItemToObject(root, "data_dir_caps", caps_out);
+        for (size_t i = 0; i < dir_count && i < MAX_DATA_DIRS; i++) {
+            cJSON_AddItemToArray(caps_out, cJSON_CreateNumber((double)config->data_dir_caps[i]));
+        }
+    }
     cJSON_AddStringToObject(root, "mount_point", config->mount_point);
     cJSON_AddNumberToObject(root, "web_port", (double)config->web_port);
+    cJSON_AddStringToObject(root, "mesh_secret", config->mesh_secret);
+    cJSON_AddBoolToObject(root, "require_encrypted_mesh", config->require_encrypted_mesh ? 1 : 0);
     cJSON_AddStringToObject(root, "node_state", config->node_state);
+    cJSON_AddStringToObject(root, "read_mode", config->read_mode);
+    if (config->endpoint_preference_in_file) {
+        cJSON_AddStringToObject(root, "endpoint_preference", config->endpoint_preference);
+    }
+    if (config->max_json_body_in_file) {
+        cJSON_AddNumberToObject(root, "max_json_body", (double)config->max_json_body_bytes);
+    }
+    if (config->max_chunk_body_in_file) {
+        cJSON_AddNumberToObject(root, "max_chunk_body", (double)config->max_chunk_body_bytes);
+    }
 
     cJSON_AddBoolToObject(root, "verbose", config->verbose ? 1 : 0);
     cJSON_AddBoolToObject(root, "enable_fuse", config->enable_fuse ? 1 : 0);
@@ -1394,6 +1687,25 @@ int junknas_config_save(const junknas_config_t *config, const char *config_file)
     return (rc == 0) ? 0 : -1;
 }
 
+/* The config dir is writable if it exists and we may write it, or if it
+ * can be created. JUNKNAS_READONLY_CONFIG=1 forces read-only mode.
+ */
+static int config_dir_writable(const char *config_file) {
+    const char *force = getenv("JUNKNAS_READONLY_CONFIG");
+    if (force && strcmp(force, "1") == 0) return 0;
+
+    char dir[MAX_PATH_LEN];
+    if (safe_strcpy(dir, sizeof(dir), config_file) != 0) return 0;
+    char *slash = strrchr(dir, '/');
+    if (!slash) return access(".", W_OK) == 0;
+    if (slash == dir) slash[1] = '\0';
+    else *slash = '\0';
+
+    if (access(dir, W_OK) == 0) return 1;
+    if (errno != ENOENT) return 0;
+    return ensure_dir_recursive(dir) == 0;
+}
+
 int junknas_config_init(junknas_config_t *config, const char *config_file) {
     if (!config) return -1;
 
@@ -1421,6 +1733,12 @@ int junknas_config_init(junknas_config_t *config, const char *config_file) {
         }
     }
 
+    if (config->config_file_path[0] != '\0' && !config_dir_writable(config->config_file_path)) {
+        config->config_read_only = 1;
+        fprintf(stderr, "config: %s is not writable; running with in-memory config, changes won't persist\n",
+                config->config_file_path);
+    }
+
     config_log_verbose(config, "config: ensuring WireGuard keys");
     if (junknas_config_ensure_wg_keys(config) != 0) {
         config_log_verbose(config, "config: WireGuard key setup failed");
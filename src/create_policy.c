@@ -0,0 +1,129 @@
+/*
+ * junkNAS - Per-directory create policy
+ *
+ * Parsing, formatting and lookup only; the FUSE layer applies the result
+ * in create/mknod/mkdir and stores it through the policy xattr.
+ */
+
+#include "create_policy.h"
+
+#include <errno.h>
+#include <limits.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define CREATE_POLICY_MAX_TEXT 256
+
+void junknas_create_policy_default(junknas_create_policy_t *p) {
+    memset(p, 0, sizeof(*p));
+    p->mask = 0777;
+}
+
+static int parse_octal_mode(const char *value, mode_t *out) {
+    char *end = NULL;
+    errno = 0;
+    unsigned long v = strtoul(value, &end, 8);
+    if (errno != 0 || end == value || *end != '\0' || v > 0777) return -1;
+    *out = (mode_t)v;
+    return 0;
+}
+
+int junknas_create_policy_parse(const char *text, size_t len, junknas_create_policy_t *out) {
+    if (!text || !out || len >= CREATE_POLICY_MAX_TEXT) return -1;
+    char buf[CREATE_POLICY_MAX_TEXT];
+    memcpy(buf, text, len);
+    buf[len] = '\0';
+
+    junknas_create_policy_t p;
+    junknas_create_policy_default(&p);
+
+    char *save = NULL;
+    for (char *line = strtok_r(buf, "\n", &save); line; line = strtok_r(NULL, "\n", &save)) {
+        char key[16], value[32];
+        char extra;
+        int n = sscanf(line, "%15s %31s %c", key, value, &extra);
+        if (n <= 0) continue;
+        if (n != 2) return -1;
+
+        if (strcmp(key, "mask") == 0) {
+            if (parse_octal_mode(value, &p.mask) != 0) return -1;
+        } else if (strcmp(key, "force") == 0) {
+            if (parse_octal_mode(value, &p.force) != 0) return -1;
+        } else if (strcmp(key, "group") == 0) {
+            char *end = NULL;
+            errno = 0;
+            unsigned long gid = strtoul(value, &end, 10);
+            if (errno != 0 || end == value || *end != '\0' || gid >= UINT_MAX) return -1;
+            p.has_group = 1;
+            p.gid = (gid_t)gid;
+        } else if (strcmp(key, "inherit") == 0) {
+            if (strcmp(value, "0") != 0 && strcmp(value, "1") != 0) return -1;
+            p.inherit = (value[0] == '1');
+        } else {
+            return -1;
+        }
+    }
+
+    *out = p;
+    return 0;
+}
+
+int junknas_create_policy_format(const junknas_create_policy_t *p, char *out, size_t out_len) {
+    if (!p || !out) return -1;
+    int n;
+    if (p->has_group) {
+        n = snprintf(out, out_len, "mask %04o\nforce %04o\ngroup %u\ninherit %d\n",
+                     (unsigned int)p->mask, (unsigned int)p->force, (unsigned int)p->gid,
+                     p->inherit ? 1 : 0);
+    } else {
+        n = snprintf(out, out_len, "mask %04o\nforce %04o\ninherit %d\n",
+                     (unsigned int)p->mask, (unsigned int)p->force, p->inherit ? 1 : 0);
+    }
+    if (n < 0 || (size_t)n >= out_len) return -1;
+    return n;
+}
+
+mode_t junknas_create_policy_mode(const junknas_create_policy_t *p, mode_t requested) {
+    if (!p) return requested & 0777;
+    return ((requested & p->mask) | p->force) & 0777;
+}
+
+int junknas_create_policy_load(const char *dir, junknas_create_policy_t *out) {
+    char path[PATH_MAX];
+    if (snprintf(path, sizeof(path), "%s/%s", dir, JUNKNAS_CREATE_POLICY_FILE) >= (int)sizeof(path)) return -1;
+
+    FILE *f = fopen(path, "rb");
+    if (!f) return (errno == ENOENT) ? 0 : -1;
+    char text[CREATE_POLICY_MAX_TEXT];
+    size_t n = fread(text, 1, sizeof(text), f);
+    int err = ferror(f);
+    fclose(f);
+    if (err || n >= sizeof(text)) return -1;
+    return (junknas_create_policy_parse(text, n, out) == 0) ? 1 : -1;
+}
+
+int junknas_create_policy_resolve(const char *root, const char *path, junknas_create_policy_t *out) {
+    if (!root || !path || !out) return -1;
+    size_t root_len = strlen(root);
+    while (root_len > 1 && root[root_len - 1] == '/') root_len--;
+    if (strncmp(path, root, root_len) != 0 || path[root_len] != '/') return -1;
+
+    char dir[PATH_MAX];
+    if (snprintf(dir, sizeof(dir), "%s", path) >= (int)sizeof(dir)) return -1;
+
+    for (int depth = 0;; depth++) {
+        char *slash = strrchr(dir, '/');
+        if (!slash || (size_t)(slash - dir) < root_len) return 0;
+        *slash = '\0';
+
+        junknas_create_policy_t p;
+        int rc = junknas_create_policy_load(dir, &p);
+        if (rc < 0) return -1;
+        if (rc == 1) {
+            if (depth > 0 && !p.inherit) return 0;
+            *out = p;
+            return 1;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+/*
+ * junkNAS - In-process event feed for the web UI
+ *
+ * A small ring buffer of recent events guarded by a mutex + condvar.
+ * Publishers (mesh thread, web handlers) never block on subscribers;
+ * slow subscribers simply skip ahead to the oldest retained event.
+ */
+
+#include "events.h"
+
+#include <errno.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/time.h>
+#include <time.h>
+
+#define EVENTS_BACKLOG 64
+
+static pthread_mutex_t g_events_lock = PTHREAD_MUTEX_INITIALIZER;
+static pthread_cond_t g_events_cond = PTHREAD_COND_INITIALIZER;
+static junknas_event_t g_events[EVENTS_BACKLOG];
+static uint64_t g_events_next_seq = 1;
+
+void junknas_events_publish(const char *type, const char *data_json) {
+    if (!type || type[0] == '\0') return;
+    if (!data_json || strlen(data_json) >= JUNKNAS_EVENT_DATA_LEN) data_json = "{}";
+
+    pthread_mutex_lock(&g_events_lock);
+    uint64_t seq = g_events_next_seq++;
+    junknas_event_t *ev = &g_events[seq % EVENTS_BACKLOG];
+    ev->seq = seq;
+    ev->time = (uint64_t)time(NULL);
+    snprintf(ev->type, sizeof(ev->type), "%s", type);
+    snprintf(ev->data, sizeof(ev->data), "%s", data_json);
+    pthread_cond_broadcast(&g_events_cond);
+    pthread_mutex_unlock(&g_events_lock);
+}
+
+int junknas_events_wait(uint64_t after_seq, junknas_event_t *out, int timeout_ms) {
+    if (!out) return 0;
+
+    struct timeval now;
+    gettimeofday(&now, NULL);
+    struct timespec deadline;
+    long long nsec = (long long)now.tv_usec * 1000LL + (long long)(timeout_ms % 1000) * 1000000LL;
+    deadline.tv_sec = now.tv_sec + timeout_ms / 1000 + (time_t)(nsec / 1000000000LL);
+    deadline.tv_nsec = (long)(nsec % 1000000000LL);
+
+    pthread_mutex_lock(&g_events_lock);
+    while (g_events_next_seq - 1 <= after_seq) {
+        int rc = pthread_cond_timedwait(&g_events_cond, &g_events_lock, &deadline);
+        if (rc == ETIMEDOUT) {
+            pthread_mutex_unlock(&g_events_lock);
+            return 0;
+        }
+    }
+
+    uint64_t latest = g_events_next_seq - 1;
+    uint64_t oldest = (latest > EVENTS_BACKLOG) ? latest - EVENTS_BACKLOG + 1 : 1;
+    uint64_t want = after_seq + 1;
+    if (want < oldest) want = oldest;
+    *out = g_events[want % EVENTS_BACKLOG];
+    pthread_mutex_unlock(&g_events_lock);
+    return 1;
+}
+
+uint64_t junknas_events_latest(void) {
+    pthread_mutex_lock(&g_events_lock);
+    uint64_t latest = g_events_next_seq - 1;
+    pthread_mutex_unlock(&g_events_lock);
+    return latest;
+}
@@ -10,6 +10,7 @@
  * ON DISK (backing dir = cfg->data_dir):
  *   /data/foo.txt.__jnkmeta                 (manifest: size + chunk hashes)
  *   /data/.jnk/chunks/sha256/ab/<hash>      (content-addressed chunks)
+ *   /data/photos/.jnkpolicy                 (create policy, see create_policy.h)
  *
  * Key properties:
  *   - Fixed chunk size (1 MiB) except final chunk may be shorter.
@@ -19,12 +20,17 @@
  *   - Quota enforced as total bytes stored in chunk store directory.
  *
  * Security / attack surface:
- *   - No symlinks, no xattrs, no chmod/chown, no device nodes, no ioctls.
+ *   - No symlinks, no device nodes, no ioctls; chmod/chown are kept in the
+ *     manifest (permission bits only) rather than applied to backing files.
  *   - Reject any FUSE path component that ends with internal suffixes or ".jnk".
  *   - Hide internal artifacts in directory listings.
  */
 
 #include "fuse_fs.h"
+#include "create_policy.h"
+#include "events.h"
+#include "sha256.h"
+#include "stats.h"
 #include <fuse3/fuse.h>
 #include <sys/file.h>
 #include <ctype.h>
@@ -42,6 +48,7 @@
 #include <sys/stat.h>
 #include <sys/statvfs.h>
 #include <sys/types.h>
+#include <sys/xattr.h>
 #include <time.h>
 #include <unistd.h>
 
@@ -49,7 +56,6 @@
 #define JNK_CHUNK_SIZE (1024 * 1024)
 
 /* Internal naming */
-#define META_SUFFIX   ".__jnkmeta"
 #define INTERNAL_DIR  ".jnk"
 
 /* Chunk store: <data_dir>/.jnk/chunks/sha256/ab/<hashhex> */
@@ -64,152 +70,69 @@ static void fuse_log_verbose(const junknas_config_t *cfg, const char *fmt, ...)
     va_end(args);
 }
 
-/* ----------------------------- SHA-256 ----------------------------------
- * Minimal SHA-256 implementation (public-domain style).
- * Good enough for integrity & content addressing.
- */
-
-typedef struct {
-    uint32_t h[8];
-    uint64_t len_bits;
-    uint8_t  buf[64];
-    size_t   buf_len;
-} sha256_ctx;
-
-static uint32_t rotr32(uint32_t x, uint32_t n) { return (x >> n) | (x << (32 - n)); }
-
-static void sha256_init(sha256_ctx *c) {
-    c->h[0] = 0x6a09e667u; c->h[1] = 0xbb67ae85u; c->h[2] = 0x3c6ef372u; c->h[3] = 0xa54ff53au;
-    c->h[4] = 0x510e527fu; c->h[5] = 0x9b05688cu; c->h[6] = 0x1f83d9abu; c->h[7] = 0x5be0cd19u;
-    c->len_bits = 0;
-    c->buf_len = 0;
-}
-
-static void sha256_compress(sha256_ctx *c, const uint8_t block[64]) {
-    static const uint32_t K[64] = {
-        0x428a2f98u,0x71374491u,0xb5c0fbcfu,0xe9b5dba5u,0x3956c25bu,0x59f111f1u,0x923f82a4u,0xab1c5ed5u,
-        0xd807aa98u,0x12835b01u,0x243185beu,0x550c7dc3u,0x72be5d74u,0x80deb1feu,0x9bdc06a7u,0xc19bf174u,
-        0xe49b69c1u,0xefbe4786u,0x0fc19dc6u,0x240ca1ccu,0x2de92c6fu,0x4a7484aau,0x5cb0a9dcu,0x76f988dau,
-        0x983e5152u,0xa831c66du,0xb00327c8u,0xbf597fc7u,0xc6e00bf3u,0xd5a79147u,0x06ca6351u,0x14292967u,
-        0x27b70a85u,0x2e1b2138u,0x4d2c6dfcu,0x53380d13u,0x650a7354u,0x766a0abbu,0x81c2c92eu,0x92722c85u,
-        0xa2bfe8a1u,0xa81a664bu,0xc24b8b70u,0xc76c51a3u,0xd192e819u,0xd6990624u,0xf40e3585u,0x106aa070u,
-        0x19a4c116u,0x1e376c08u,0x2748774cu,0x34b0bcb5u,0x391c0cb3u,0x4ed8aa4au,0x5b9cca4fu,0x682e6ff3u,
-        0x748f82eeu,0x78a5636fu,0x84c87814u,0x8cc70208u,0x90befffau,0xa4506cebu,0xbef9a3f7u,0xc67178f2u
-    };
-
-    uint32_t w[64];
-    for (int i = 0; i < 16; i++) {
-        w[i] = ((uint32_t)block[i*4+0] << 24) | ((uint32_t)block[i*4+1] << 16) |
-               ((uint32_t)block[i*4+2] << 8)  | ((uint32_t)block[i*4+3]);
-    }
-    for (int i = 16; i < 64; i++) {
-        uint32_t s0 = rotr32(w[i-15], 7) ^ rotr32(w[i-15], 18) ^ (w[i-15] >> 3);
-        uint32_t s1 = rotr32(w[i-2], 17) ^ rotr32(w[i-2], 19) ^ (w[i-2] >> 10);
-        w[i] = w[i-16] + s0 + w[i-7] + s1;
-    }
-
-    uint32_t a=c->h[0], b=c->h[1], d=c->h[3], e=c->h[4], f=c->h[5], g=c->h[6], h=c->h[7], cc=c->h[2];
-
-    for (int i = 0; i < 64; i++) {
-        uint32_t S1 = rotr32(e,6) ^ rotr32(e,11) ^ rotr32(e,25);
-        uint32_t ch = (e & f) ^ ((~e) & g);
-        uint32_t temp1 = h + S1 + ch + K[i] + w[i];
-        uint32_t S0 = rotr32(a,2) ^ rotr32(a,13) ^ rotr32(a,22);
-        uint32_t maj = (a & b) ^ (a & cc) ^ (b & cc);
-        uint32_t temp2 = S0 + maj;
-
-        h = g;
-        g = f;
-        f = e;
-        e = d + temp1;
-        d = cc;
-        cc = b;
-        b = a;
-        a = temp1 + temp2;
-    }
-
-    c->h[0]+=a; c->h[1]+=b; c->h[2]+=cc; c->h[3]+=d; c->h[4]+=e; c->h[5]+=f; c->h[6]+=g; c->h[7]+=h;
-}
-
-static void sha256_update(sha256_ctx *c, const void *data, size_t n) {
-    const uint8_t *p = (const uint8_t *)data;
-    c->len_bits += (uint64_t)n * 8u;
-
-    while (n > 0) {
-        size_t room = 64 - c->buf_len;
-        size_t take = (n < room) ? n : room;
-        memcpy(c->buf + c->buf_len, p, take);
-        c->buf_len += take;
-        p += take;
-        n -= take;
-
-        if (c->buf_len == 64) {
-            sha256_compress(c, c->buf);
-            c->buf_len = 0;
-        }
-    }
-}
-
-static void sha256_final(sha256_ctx *c, uint8_t out[32]) {
-    /* pad */
-    c->buf[c->buf_len++] = 0x80;
-    if (c->buf_len > 56) {
-        while (c->buf_len < 64) c->buf[c->buf_len++] = 0x00;
-        sha256_compress(c, c->buf);
-        c->buf_len = 0;
-    }
-    while (c->buf_len < 56) c->buf[c->buf_len++] = 0x00;
-
-    /* length big-endian */
-    uint64_t L = c->len_bits;
-    for (int i = 7; i >= 0; i--) {
-        c->buf[c->buf_len++] = (uint8_t)((L >> (i*8)) & 0xffu);
-    }
-    sha256_compress(c, c->buf);
-
-    for (int i = 0; i < 8; i++) {
-        out[i*4+0] = (uint8_t)((c->h[i] >> 24) & 0xffu);
-        out[i*4+1] = (uint8_t)((c->h[i] >> 16) & 0xffu);
-        out[i*4+2] = (uint8_t)((c->h[i] >> 8) & 0xffu);
-        out[i*4+3] = (uint8_t)((c->h[i]) & 0xffu);
-    }
-}
-
-static void sha256_hex(const uint8_t digest[32], char hex[65]) {
-    static const char *H = "0123456789abcdef";
-    for (int i = 0; i < 32; i++) {
-        hex[i*2+0] = H[(digest[i] >> 4) & 0xF];
-        hex[i*2+1] = H[digest[i] & 0xF];
-    }
-    hex[64] = '\0';
-}
-
-static void sha256_buf_hex(const void *data, size_t n, char hex[65]) {
-    sha256_ctx c;
-    uint8_t d[32];
-    sha256_init(&c);
-    sha256_update(&c, data, n);
-    sha256_final(&c, d);
-    sha256_hex(d, hex);
-}
-
 /* --------------------------- Internal State ---------------------------- */
 
+/* Open-file table entry: one per manifest with live handles, so unlink can
+ * defer dropping chunk refs until the last close (POSIX unlink-while-open).
+ */
+typedef struct jnk_open_file {
+    char   meta_path[MAX_PATH_LEN];
+    int    open_count;
+    int    unlinked;            /* name is gone; drop refs on last close */
+    char **disk_hashes;         /* manifest content when it was unlinked */
+    size_t disk_count;
+    int    times_set;           /* utimens while open, no write since: reapply once release saves */
+    struct timespec times[2];   /* atime, mtime as set */
+    struct jnk_file_handle *handles; /* open handles, told about truncate by path */
+    struct jnk_open_file *next;
+} jnk_open_file_t;
+
 typedef struct {
     char   backing_dir[MAX_PATH_LEN];
     char   store_dirs[MAX_DATA_DIRS][MAX_PATH_LEN]; /* <backing>/.jnk/chunks/sha256 */
     size_t store_dir_count;
     size_t store_rr_next;
     char   refs_dir[MAX_PATH_LEN]; /* <bakcing>/.jnk/refs */
-    int    verbose;
+    const junknas_config_t *config; /* live settings (verbose can change at runtime) */
     size_t quota_bytes;             /* 0 = unlimited */
+    char   drive_reserve[32];       /* free space never used on each store dir */
+    size_t store_caps[MAX_DATA_DIRS]; /* per store dir chunk byte cap (0 = none) */
+    int64_t store_used[MAX_DATA_DIRS]; /* chunk bytes per store dir: scanned at mount, then counted */
+    int    quota_warn_percent;      /* soft band below quota_bytes (0 = off) */
+    int    quota_warning;           /* currently inside the band (see quota_update_warning) */
+    int    hard_reads;              /* read_mode "hard": retry unreachable peers until interrupted */
+    size_t fsync_batch;             /* chunks per fsync batch in release (<= 1: sync each chunk) */
+    uint64_t fsync_batch_usec;      /* flush a batch once its oldest chunk waited this long */
+    uint64_t slow_budget_usec[JUNKNAS_FUSE_OP_COUNT]; /* slow-op log threshold (0 = off; read: per MiB) */
     junknas_mesh_t *mesh;
+    pthread_mutex_t open_lock;      /* guards open_files */
+    jnk_open_file_t *open_files;
+    uint64_t scrub_interval_sec;    /* seconds between chunk scrub passes (0 = off) */
+    pthread_t scrub_thread;
+    pthread_mutex_t scrub_lock;     /* with scrub_cond, wakes the scrubber to stop */
+    pthread_cond_t scrub_cond;
+    int scrub_running;
+    volatile int scrub_quit;
 } jnk_fuse_state_t;
 
+/* Where the current FUSE operation spent its time, for slow-op logs.
+ * Reset by JNK_TIMED; each FUSE request runs on one worker thread.
+ */
+typedef struct {
+    uint64_t disk_usec;
+    uint64_t mesh_usec;
+    uint64_t hash_usec;
+} jnk_op_timing_t;
+
+static __thread jnk_op_timing_t t_op_timing;
+
+#define JNK_TIMING_ADD(field, t0) (t_op_timing.field += junknas_stats_now_usec() - (t0))
+
 /* Per-open handle */
 typedef struct dirty_chunk dirty_chunk_t;
-typedef struct {
+typedef struct jnk_file_handle {
     char meta_path[MAX_PATH_LEN];
+    jnk_open_file_t *of;        /* shared open-file entry */
     size_t size;
     size_t chunk_count;
     char **hashes;      /* array of chunk hash strings (64-hex) */
@@ -225,6 +148,16 @@ typedef struct {
    * Instead, we stage per-chunk buffers here and commit once on release().
    */
   dirty_chunk_t *dirty_chunks;
+
+  /* truncate(2) by path while this handle was open, guarded by open_lock
+   * and applied by handle_apply_truncate before the handle's next use.
+   */
+  struct jnk_file_handle *next_in_file;
+  int    trunc_pending;
+  size_t trunc_low;             /* smallest size truncated to since the last apply */
+  size_t trunc_size;            /* the manifest as the last one left it */
+  char **trunc_hashes;
+  size_t trunc_count;
 } jnk_file_handle_t;
 
 /* Dirty chunk node: full 1 MiB chunk buffer for a given index */
@@ -264,10 +197,11 @@ static int path_is_safe_user_path(const char *path) {
 
         if (strcmp(comp, ".") == 0 || strcmp(comp, "..") == 0) return 0;
         if (strcmp(comp, INTERNAL_DIR) == 0) return 0;
-        if (str_endswith(comp, META_SUFFIX)) return 0;
+        if (strcmp(comp, JUNKNAS_CREATE_POLICY_FILE) == 0) return 0;
+        if (str_endswith(comp, JUNKNAS_META_SUFFIX)) return 0;
 
         /* Prevent sneaky internal artifacts */
-        if (strstr(comp, META_SUFFIX) != NULL) return 0;
+        if (strstr(comp, JUNKNAS_META_SUFFIX) != NULL) return 0;
 
         p = slash ? slash + 1 : (p + len);
     }
@@ -288,7 +222,7 @@ static int make_real_and_meta(const char *backing_dir, const char *path,
     }
 
     if (snprintf(real_path, MAX_PATH_LEN, "%s%s", backing_dir, path) >= MAX_PATH_LEN) return -1;
-    if (snprintf(meta_path, MAX_PATH_LEN, "%s%s%s", backing_dir, path, META_SUFFIX) >= MAX_PATH_LEN) return -1;
+    if (snprintf(meta_path, MAX_PATH_LEN, "%s%s%s", backing_dir, path, JUNKNAS_META_SUFFIX) >= MAX_PATH_LEN) return -1;
     return 0;
 }
 
@@ -380,7 +314,7 @@ static int ensure_store_layout(jnk_fuse_state_t *s) {
   char p1[MAX_PATH_LEN], p4[MAX_PATH_LEN];
 
   if (snprintf(p1, sizeof(p1), "%s/%s", s->backing_dir, INTERNAL_DIR) >= (int)sizeof(p1)) return -1;
-  if (snprintf(p4, sizeof(p4), "%s/%s/refs", s->backing_dir, INTERNAL_DIR) >= (int)sizeof(p4)) return -1;
+  if (snprintf(p4, sizeof(p4), "%s/%s", s->backing_dir, JUNKNAS_REFS_SUBDIR) >= (int)sizeof(p4)) return -1;
 
   if (ensure_dir(p1) != 0) return -1;
   if (ensure_dir(p4) != 0) return -1;
@@ -452,6 +386,24 @@ static int write_refcount_fd(int fd, long long v) {
     return 0;
 }
 
+/* Running usage, updated wherever a chunk file appears or goes away, so
+ * placing a chunk doesn't walk the whole store dir.
+ */
+static void store_used_add(jnk_fuse_state_t *s, size_t dir, int64_t delta) {
+    if (dir < s->store_dir_count) (void)__sync_add_and_fetch(&s->store_used[dir], delta);
+}
+
+static int64_t store_dir_used(jnk_fuse_state_t *s, size_t dir) {
+    int64_t used = __sync_add_and_fetch(&s->store_used[dir], 0);
+    return (used > 0) ? used : 0;
+}
+
+/* Bytes of a chunk file about to be added or removed; 0 if it isn't there. */
+static int64_t chunk_file_bytes(const char *p) {
+    struct stat st;
+    return (stat(p, &st) == 0 && S_ISREG(st.st_mode)) ? (int64_t)st.st_size : 0;
+}
+
 /* delta > 0 increments; delta < 0 decrements. Safe rule:
  * - If decrement and ref file missing => do nothing (avoid accidental deletion).
  * - Only delete chunk when ref file exists and reaches 0.
@@ -501,7 +453,8 @@ static int apply_ref_delta(jnk_fuse_state_t *s, const char hashhex[65], long lon
         char chunkp[MAX_PATH_LEN];
         for (size_t i = 0; i < s->store_dir_count; i++) {
             if (store_path_for_hash(chunkp, s->store_dirs[i], hashhex, 0) == 0) {
-                (void)unlink(chunkp);
+                int64_t bytes = chunk_file_bytes(chunkp);
+                if (unlink(chunkp) == 0) store_used_add(s, i, -bytes);
             }
         }
         return 0;
@@ -582,6 +535,21 @@ static int apply_ref_deltas_from_manifests(jnk_fuse_state_t *s,
     return 0;
 }
 
+/* Same chunk list, treating missing trailing entries as NULL. */
+static int hashes_equal(char **a, size_t an, char **b, size_t bn) {
+    size_t n = (an > bn) ? an : bn;
+    for (size_t i = 0; i < n; i++) {
+        const char *x = (i < an) ? a[i] : NULL;
+        const char *y = (i < bn) ? b[i] : NULL;
+        if (!x || !y) {
+            if (x != y) return 0;
+            continue;
+        }
+        if (strcmp(x, y) != 0) return 0;
+    }
+    return 1;
+}
+
 /* Deep copy hashes array */
 static int clone_hashes(char ***out, size_t *out_count, char **in, size_t in_count) {
     *out = NULL;
@@ -607,78 +575,281 @@ static int clone_hashes(char ***out, size_t *out_count, char **in, size_t in_cou
     return 0;
 }
 
-/* Compute current store usage by walking all store dirs. (Simple & correct; can optimize later.) */
-static int64_t store_usage_bytes(const jnk_fuse_state_t *s) {
+/* Bytes of chunks held in one store dir, by walking its shards. */
+static int64_t store_dir_usage_bytes(const char *dir) {
     int64_t total = 0;
-    for (size_t i = 0; i < s->store_dir_count; i++) {
-        char store_root[MAX_PATH_LEN];
-        if (snprintf(store_root, sizeof(store_root), "%s/%s/chunks/sha256",
-                     s->store_dirs[i], INTERNAL_DIR) >= (int)sizeof(store_root)) {
-            continue;
-        }
+    char store_root[MAX_PATH_LEN];
+    if (snprintf(store_root, sizeof(store_root), "%s/%s/chunks/sha256",
+                 dir, INTERNAL_DIR) >= (int)sizeof(store_root)) {
+        return 0;
+    }
 
-        DIR *d = opendir(store_root);
-        if (!d) continue;
+    DIR *d = opendir(store_root);
+    if (!d) return 0;
 
-        struct dirent *de;
-        while ((de = readdir(d)) != NULL) {
-            if (strcmp(de->d_name, ".") == 0 || strcmp(de->d_name, "..") == 0) continue;
+    struct dirent *de;
+    while ((de = readdir(d)) != NULL) {
+        if (strcmp(de->d_name, ".") == 0 || strcmp(de->d_name, "..") == 0) continue;
 
-            char shard[MAX_PATH_LEN];
-            if (snprintf(shard, sizeof(shard), "%s/%s", store_root, de->d_name) >= (int)sizeof(shard)) continue;
+        char shard[MAX_PATH_LEN];
+        if (snprintf(shard, sizeof(shard), "%s/%s", store_root, de->d_name) >= (int)sizeof(shard)) continue;
 
-            DIR *sd = opendir(shard);
-            if (!sd) continue;
+        DIR *sd = opendir(shard);
+        if (!sd) continue;
 
-            struct dirent *fe;
-            while ((fe = readdir(sd)) != NULL) {
-                if (strcmp(fe->d_name, ".") == 0 || strcmp(fe->d_name, "..") == 0) continue;
+        struct dirent *fe;
+        while ((fe = readdir(sd)) != NULL) {
+            if (strcmp(fe->d_name, ".") == 0 || strcmp(fe->d_name, "..") == 0) continue;
 
-                char fp[MAX_PATH_LEN];
-                if (snprintf(fp, sizeof(fp), "%s/%s", shard, fe->d_name) >= (int)sizeof(fp)) continue;
+            char fp[MAX_PATH_LEN];
+            if (snprintf(fp, sizeof(fp), "%s/%s", shard, fe->d_name) >= (int)sizeof(fp)) continue;
 
-                struct stat st;
-                if (lstat(fp, &st) == 0 && S_ISREG(st.st_mode)) total += (int64_t)st.st_size;
-            }
-            closedir(sd);
+            struct stat st;
+            if (lstat(fp, &st) == 0 && S_ISREG(st.st_mode)) total += (int64_t)st.st_size;
         }
-
-        closedir(d);
+        closedir(sd);
     }
 
+    closedir(d);
     return total;
 }
 
-/* Store chunk by hash, if missing. Returns 0 on success, -ENOSPC if quota exceeded. */
-static int store_put_chunk_if_missing(jnk_fuse_state_t *s, const char hashhex[65], const uint8_t *data, size_t len) {
-    char p[MAX_PATH_LEN];
+static int64_t store_usage_bytes(jnk_fuse_state_t *s) {
+    int64_t total = 0;
     for (size_t i = 0; i < s->store_dir_count; i++) {
-        if (store_path_for_hash(p, s->store_dirs[i], hashhex, 0) != 0) continue;
-        if (file_exists(p)) {
-            return 0; /* already present */
+        total += store_dir_used(s, i);
+    }
+    return total;
+}
+
+/* Points below quota_warn_percent usage must drop before the warning clears. */
+#define JNK_QUOTA_WARN_HYSTERESIS 5
+#define JNK_QUOTA_WARN_XATTR "user.junknas.quota_warning"
+
+/* Enter/leave the soft-quota band, publishing one event per crossing.
+ * Evaluated wherever usage is already computed (chunk stores, statfs).
+ */
+static void quota_update_warning(jnk_fuse_state_t *s, uint64_t used) {
+    if (s->quota_bytes == 0 || s->quota_warn_percent <= 0) return;
+
+    uint64_t quota = (uint64_t)s->quota_bytes;
+    uint64_t on_at = quota / 100ULL * (uint64_t)s->quota_warn_percent;
+    int off_pct = s->quota_warn_percent - JNK_QUOTA_WARN_HYSTERESIS;
+    uint64_t off_at = (off_pct > 0) ? quota / 100ULL * (uint64_t)off_pct : 0;
+
+    const char *state = NULL;
+    if (used >= on_at && __sync_bool_compare_and_swap(&s->quota_warning, 0, 1)) {
+        state = "warning";
+    } else if (used < off_at && __sync_bool_compare_and_swap(&s->quota_warning, 1, 0)) {
+        state = "ok";
+    }
+    if (!state) return;
+
+    char data[160];
+    snprintf(data, sizeof(data),
+             "{\"state\":\"%s\",\"used_bytes\":%llu,\"quota_bytes\":%llu,\"warn_percent\":%d}",
+             state, (unsigned long long)used, (unsigned long long)quota, s->quota_warn_percent);
+    junknas_events_publish("quota_warning", data);
+    fuse_log_verbose(s->config, "fuse: quota %s (%llu of %llu bytes used)", state,
+                     (unsigned long long)used, (unsigned long long)quota);
+}
+
+/* Free bytes on a store dir's filesystem once drive_reserve is held back. */
+static int store_dir_available(const jnk_fuse_state_t *s, const char *dir, uint64_t *out) {
+    struct statvfs vfs;
+    if (statvfs(dir, &vfs) != 0) return -1;
+    uint64_t total = (uint64_t)vfs.f_blocks * (uint64_t)vfs.f_frsize;
+    uint64_t avail = (uint64_t)vfs.f_bavail * (uint64_t)vfs.f_frsize;
+    uint64_t reserve = junknas_drive_reserve_bytes(s->drive_reserve, total);
+    *out = (avail > reserve) ? avail - reserve : 0;
+    return 0;
+}
+
+/* A drive that hit errors is often remounted read-only; stop placing chunks there. */
+static int store_dir_read_only(const char *dir) {
+    struct statvfs vfs;
+    if (statvfs(dir, &vfs) != 0) return 0;
+    return (vfs.f_flag & ST_RDONLY) != 0;
+}
+
+/* Whether store dir i can take len more bytes: writable, under its reserve and cap. */
+static int store_dir_accepts(jnk_fuse_state_t *s, size_t i, size_t len) {
+    if (store_dir_read_only(s->store_dirs[i])) return 0;
+    if (s->drive_reserve[0] != '\0') {
+        uint64_t avail = 0;
+        if (store_dir_available(s, s->store_dirs[i], &avail) != 0 || avail < (uint64_t)len) return 0;
+    }
+    if (s->store_caps[i] != 0) {
+        int64_t used = store_dir_used(s, i);
+        if ((uint64_t)used + (uint64_t)len > (uint64_t)s->store_caps[i]) return 0;
+    }
+    return 1;
+}
+
+/* Chunks written but not yet synced, still under a ".tmp.<tid>" name so a
+ * crash can't leave an unsynced file that dedup would trust by its hash.
+ * The manifest naming them is only saved after the batch is flushed.
+ * A batch belongs to one release: whatever it holds is flushed before the
+ * close returns, so fsync_batch_usec only splits a long one.
+ */
+#define JNK_FSYNC_BATCH_MAX 64
+#define JNK_FSYNC_BATCH_DEFAULT_MS 1000
+
+typedef struct {
+    int      fd[JNK_FSYNC_BATCH_MAX];
+    size_t   dir[JNK_FSYNC_BATCH_MAX];
+    char     hash[JNK_FSYNC_BATCH_MAX][65];
+    char     tmp[JNK_FSYNC_BATCH_MAX][MAX_PATH_LEN];
+    size_t   len[JNK_FSYNC_BATCH_MAX];
+    size_t   n;
+    uint64_t first_usec;
+} jnk_sync_batch_t;
+
+static int sync_batch_has(const jnk_sync_batch_t *b, const char hashhex[65]) {
+    for (size_t i = 0; i < b->n; i++) {
+        if (memcmp(b->hash[i], hashhex, 64) == 0) return 1;
+    }
+    return 0;
+}
+
+/* Move a synced temp chunk to its final name. Another writer may have
+ * stored the same hash meanwhile; identical content, so keep theirs.
+ * Returns 1 if ours was dropped as a duplicate, 0 if renamed, -1 on error.
+ */
+static int chunk_tmp_commit(const char *tmp, const char *p) {
+    if (file_exists(p)) {
+        (void)unlink(tmp);
+        return 1;
+    }
+    if (rename(tmp, p) != 0) {
+        (void)unlink(tmp);
+        return -1;
+    }
+    return 0;
+}
+
+/* Shard dir b->hash[i] was renamed into, unless an earlier entry of the
+ * batch already names the same one.
+ */
+static int sync_batch_shard_dir(const jnk_fuse_state_t *s, const jnk_sync_batch_t *b, size_t i,
+                                char out[MAX_PATH_LEN]) {
+    for (size_t j = 0; j < i; j++) {
+        if (b->dir[j] == b->dir[i] && memcmp(b->hash[j], b->hash[i], 2) == 0) return 1;
+    }
+    if (store_path_for_hash(out, s->store_dirs[b->dir[i]], b->hash[i], 0) != 0) return -1;
+    *strrchr(out, '/') = '\0';
+    return 0;
+}
+
+/* fsync each chunk, rename everything into place, then fsync each shard
+ * dir touched once so the names are durable before any manifest refers
+ * to them.
+ * On failure the remaining temp files are discarded and -EIO is returned.
+ */
+static int sync_batch_flush(jnk_fuse_state_t *s, jnk_sync_batch_t *b) {
+    int rc = 0;
+    for (size_t i = 0; i < b->n; i++) {
+        if (fsync(b->fd[i]) != 0) rc = -EIO;
+        close(b->fd[i]);
+    }
+
+    for (size_t i = 0; i < b->n; i++) {
+        char p[MAX_PATH_LEN];
+        if (rc != 0 || store_path_for_hash(p, s->store_dirs[b->dir[i]], b->hash[i], 0) != 0) {
+            (void)unlink(b->tmp[i]);
+            rc = -EIO;
+            continue;
         }
+        int c = chunk_tmp_commit(b->tmp[i], p);
+        if (c < 0) rc = -EIO;
+        else if (c == 0) store_used_add(s, b->dir[i], (int64_t)b->len[i]);
     }
 
-    /* quota check: if storing new unique chunk */
+    for (size_t i = 0; i < b->n && rc == 0; i++) {
+        char shard[MAX_PATH_LEN];
+        int d = sync_batch_shard_dir(s, b, i, shard);
+        if (d == 1) continue;
+        int fd = (d == 0) ? open(shard, O_RDONLY | O_DIRECTORY) : -1;
+        if (fd < 0 || fsync(fd) != 0) rc = -EIO;
+        if (fd >= 0) close(fd);
+    }
+    b->n = 0;
+    return rc;
+}
+
+/* Store dir for a new chunk of len bytes: within the quota, then
+ * round-robin, skipping dirs that are read-only, would dip into the drive
+ * reserve or pass their cap. Returns 0, -ENOSPC, -EROFS (every dir is
+ * read-only) or -EIO.
+ */
+static int store_pick_dir(jnk_fuse_state_t *s, size_t len, size_t *out) {
     if (s->quota_bytes != 0) {
         int64_t used = store_usage_bytes(s);
         if (used < 0) return -EIO;
         if ((uint64_t)used + (uint64_t)len > (uint64_t)s->quota_bytes) {
             return -ENOSPC;
         }
+        quota_update_warning(s, (uint64_t)used + (uint64_t)len);
     }
 
     if (s->store_dir_count == 0) return -EIO;
-    size_t target = s->store_rr_next % s->store_dir_count;
+    size_t start = s->store_rr_next % s->store_dir_count;
     s->store_rr_next = (s->store_rr_next + 1) % s->store_dir_count;
 
+    for (size_t n = 0; n < s->store_dir_count; n++) {
+        size_t i = (start + n) % s->store_dir_count;
+        if (store_dir_accepts(s, i, len)) {
+            *out = i;
+            return 0;
+        }
+    }
+    size_t ro = 0;
+    for (size_t i = 0; i < s->store_dir_count; i++) ro += (size_t)store_dir_read_only(s->store_dirs[i]);
+    return (ro == s->store_dir_count) ? -EROFS : -ENOSPC;
+}
+
+/* Store chunk by hash, if missing. Returns 0 on success, -ENOSPC if quota exceeded
+ * or no dir has room, -EROFS if every dir is read-only, else -EIO.
+ */
+static int store_put_chunk_if_missing(jnk_fuse_state_t *s, const char hashhex[65], const uint8_t *data, size_t len,
+                                      jnk_sync_batch_t *batch) {
+    char p[MAX_PATH_LEN];
+    for (size_t i = 0; i < s->store_dir_count; i++) {
+        if (store_path_for_hash(p, s->store_dirs[i], hashhex, 0) != 0) continue;
+        if (file_exists(p)) {
+            junknas_stats_inc(JUNKNAS_STAT_CHUNK_WRITE_DEDUP);
+            return 0; /* already present */
+        }
+    }
+    if (batch && sync_batch_has(batch, hashhex)) {
+        junknas_stats_inc(JUNKNAS_STAT_CHUNK_WRITE_DEDUP);
+        return 0; /* written earlier in this batch */
+    }
+
+    size_t target = 0;
+    int rc = store_pick_dir(s, len, &target);
+    if (rc != 0) return rc;
+
     if (store_path_for_hash(p, s->store_dirs[target], hashhex, 1) != 0) return -EIO;
 
-    /* write atomically-ish */
+    /* write atomically-ish; the temp name is per thread so concurrent
+     * writers of the same chunk don't collide */
     char tmp[MAX_PATH_LEN];
-    if (snprintf(tmp, sizeof(tmp), "%s.tmp", p) >= (int)sizeof(tmp)) return -EIO;
+    if (snprintf(tmp, sizeof(tmp), "%s.tmp.%lu", p, (unsigned long)pthread_self()) >= (int)sizeof(tmp)) {
+        return -EIO;
+    }
 
+    uint64_t t_disk = junknas_stats_now_usec();
     int fd = open(tmp, O_WRONLY | O_CREAT | O_EXCL, 0644);
+    if (fd < 0 && errno == EEXIST && file_exists(p)) {
+        junknas_stats_inc(JUNKNAS_STAT_CHUNK_WRITE_DEDUP);
+        return 0; /* stored by someone else since the check above */
+    }
+    if (fd < 0 && errno == EEXIST) {
+        /* left behind by a crash on this thread id; nothing else owns it */
+        (void)unlink(tmp);
+        fd = open(tmp, O_WRONLY | O_CREAT | O_EXCL, 0644);
+    }
     if (fd < 0) return -EIO;
 
     ssize_t w = write(fd, data, len);
@@ -687,6 +858,32 @@ static int store_put_chunk_if_missing(jnk_fuse_state_t *s, const char hashhex[65
         (void)unlink(tmp);
         return -EIO;
     }
+    if (batch) {
+        /* Synced and renamed by sync_batch_flush. */
+        if (batch->n == 0) batch->first_usec = junknas_stats_now_usec();
+        batch->fd[batch->n] = fd;
+        batch->dir[batch->n] = target;
+        memcpy(batch->hash[batch->n], hashhex, 65);
+        memcpy(batch->tmp[batch->n], tmp, sizeof(tmp));
+        batch->len[batch->n] = len;
+        batch->n++;
+        JNK_TIMING_ADD(disk_usec, t_disk);
+        junknas_stats_inc(JUNKNAS_STAT_CHUNK_WRITE_NEW);
+        junknas_stats_add(JUNKNAS_STAT_WRITE_BYTES_STORED, (uint64_t)len);
+        if (s->mesh) {
+            uint64_t t0 = junknas_stats_now_usec();
+            (void)junknas_mesh_replicate_chunk(s->mesh, hashhex, data, len);
+            JNK_TIMING_ADD(mesh_usec, t0);
+        }
+        if (batch->n >= s->fsync_batch ||
+            junknas_stats_now_usec() - batch->first_usec >= s->fsync_batch_usec) {
+            t_disk = junknas_stats_now_usec();
+            int rc = sync_batch_flush(s, batch);
+            JNK_TIMING_ADD(disk_usec, t_disk);
+            return rc;
+        }
+        return 0;
+    }
     if (fsync(fd) != 0) {
         close(fd);
         (void)unlink(tmp);
@@ -694,20 +891,71 @@ static int store_put_chunk_if_missing(jnk_fuse_state_t *s, const char hashhex[65
     }
     close(fd);
 
-    if (rename(tmp, p) != 0) {
-        (void)unlink(tmp);
-        return -EIO;
+    int c = chunk_tmp_commit(tmp, p);
+    if (c < 0) return -EIO;
+    JNK_TIMING_ADD(disk_usec, t_disk);
+    if (c == 1) {
+        junknas_stats_inc(JUNKNAS_STAT_CHUNK_WRITE_DEDUP);
+        return 0;
     }
+    store_used_add(s, target, (int64_t)len);
+    junknas_stats_inc(JUNKNAS_STAT_CHUNK_WRITE_NEW);
+    junknas_stats_add(JUNKNAS_STAT_WRITE_BYTES_STORED, (uint64_t)len);
 
     if (s->mesh) {
+        uint64_t t0 = junknas_stats_now_usec();
         (void)junknas_mesh_replicate_chunk(s->mesh, hashhex, data, len);
+        JNK_TIMING_ADD(mesh_usec, t0);
     }
 
     return 0;
 }
 
-/* Read chunk from store and verify hash. Returns number of bytes read or -EIO/-ENOENT. */
-static int read_chunk_verified(const jnk_fuse_state_t *s, const char hashhex[65], uint8_t *out, size_t max_len, size_t *out_len) {
+/* Mesh fetch retry budget for peers that didn't answer (see read_mode). */
+#define JNK_FETCH_SOFT_ATTEMPTS 3
+#define JNK_FETCH_BACKOFF_MS    500
+#define JNK_FETCH_BACKOFF_MAX_MS 2000
+
+/* Fetch a chunk from the mesh into the first writable store dir.
+ * Soft mode retries -EAGAIN a few times; hard mode until the chunk arrives.
+ * Returns 0, -ENOENT (no peer has it), -EAGAIN (peers still unreachable),
+ * -EACCES (only peers without an encrypted link might have it) or -EINTR.
+ */
+static int fetch_chunk_from_mesh(jnk_fuse_state_t *s, const char hashhex[65], char *p) {
+    size_t i = 0;
+    for (; i < s->store_dir_count; i++) {
+        if (store_dir_read_only(s->store_dirs[i])) continue;
+        if (store_path_for_hash(p, s->store_dirs[i], hashhex, 1) == 0) break;
+    }
+    if (i == s->store_dir_count) return -EIO;
+
+    int backoff_ms = JNK_FETCH_BACKOFF_MS;
+    for (int attempt = 1; ; attempt++) {
+        int rc = junknas_mesh_fetch_chunk(s->mesh, hashhex, p);
+        if (rc == 0) {
+            store_used_add(s, i, chunk_file_bytes(p));
+            return 0;
+        }
+        if (rc == -EACCES) return rc;
+        if (rc != -EAGAIN) return -ENOENT;
+        if (!s->hard_reads && attempt >= JNK_FETCH_SOFT_ATTEMPTS) return -EAGAIN;
+
+        /* Sleep in small steps so an interrupted syscall doesn't hang here. */
+        for (int waited = 0; waited < backoff_ms; waited += 100) {
+            if (fuse_interrupted()) return -EINTR;
+            struct timespec ts = { 0, 100 * 1000000L };
+            (void)nanosleep(&ts, NULL);
+        }
+        if (backoff_ms < JNK_FETCH_BACKOFF_MAX_MS) backoff_ms *= 2;
+        if (backoff_ms > JNK_FETCH_BACKOFF_MAX_MS) backoff_ms = JNK_FETCH_BACKOFF_MAX_MS;
+    }
+}
+
+/* Read chunk from store and verify hash.
+ * Returns 0, -ENOENT (missing everywhere), -EAGAIN/-EINTR (mesh retry gave up)
+ * or -EIO (short read or hash mismatch).
+ */
+static int read_chunk_verified(jnk_fuse_state_t *s, const char hashhex[65], uint8_t *out, size_t max_len, size_t *out_len) {
     char p[MAX_PATH_LEN];
     int fd = -1;
     for (size_t i = 0; i < s->store_dir_count; i++) {
@@ -715,16 +963,19 @@ static int read_chunk_verified(const jnk_fuse_state_t *s, const char hashhex[65]
         fd = open(p, O_RDONLY);
         if (fd >= 0) break;
     }
+    junknas_stat_counter_t source = JUNKNAS_STAT_CHUNK_READ_LOCAL;
+    int fetch_rc = -ENOENT;
     if (fd < 0 && s->mesh) {
-        for (size_t i = 0; i < s->store_dir_count; i++) {
-            if (store_path_for_hash(p, s->store_dirs[i], hashhex, 1) != 0) continue;
-            if (junknas_mesh_fetch_chunk(s->mesh, hashhex, p) == 0) {
-                fd = open(p, O_RDONLY);
-                if (fd >= 0) break;
-            }
-        }
+        uint64_t t0 = junknas_stats_now_usec();
+        fetch_rc = fetch_chunk_from_mesh(s, hashhex, p);
+        JNK_TIMING_ADD(mesh_usec, t0);
+        if (fetch_rc == 0) fd = open(p, O_RDONLY);
+        source = JUNKNAS_STAT_CHUNK_READ_MESH;
+    }
+    if (fd < 0) {
+        junknas_stats_inc(JUNKNAS_STAT_CHUNK_READ_FAILED);
+        return (fetch_rc == -EAGAIN || fetch_rc == -EINTR || fetch_rc == -EACCES) ? fetch_rc : -ENOENT;
     }
-    if (fd < 0) return -ENOENT;
 
     /* read whole chunk file */
     struct stat st;
@@ -734,19 +985,182 @@ static int read_chunk_verified(const jnk_fuse_state_t *s, const char hashhex[65]
     size_t len = (size_t)st.st_size;
     if (len > max_len) { close(fd); return -EIO; }
 
+    uint64_t t0 = junknas_stats_now_usec();
     ssize_t r = read(fd, out, len);
     close(fd);
+    JNK_TIMING_ADD(disk_usec, t0);
     if (r < 0 || (size_t)r != len) return -EIO;
 
     /* integrity check */
     char calc[65];
-    sha256_buf_hex(out, len, calc);
-    if (memcmp(calc, hashhex, 64) != 0) return -EIO;
+    t0 = junknas_stats_now_usec();
+    junknas_sha256_buf_hex(out, len, calc);
+    JNK_TIMING_ADD(hash_usec, t0);
+    if (memcmp(calc, hashhex, 64) != 0) {
+        junknas_stats_inc(JUNKNAS_STAT_CHUNK_READ_FAILED);
+        return -EIO;
+    }
 
+    junknas_stats_inc(source);
     *out_len = len;
     return 0;
 }
 
+/* ------------------------------ Scrubber -------------------------------- */
+
+/* Background pass over every store dir that rehashes each chunk file and
+ * compares it with its name. A mismatch is moved aside as <hash>.corrupt
+ * (so neither local reads nor peers get the bad bytes) and, if the mesh
+ * has a good copy, replaced by it. Chunks written in the last minute are
+ * skipped: mesh fetches fill the final path in place.
+ */
+#define JNK_SCRUB_DEFAULT_INTERVAL_SEC 3600
+#define JNK_SCRUB_SETTLE_SEC 60
+
+/* 1 if the file hashes to hashhex, 0 if not, -1 if it couldn't be read. */
+static int scrub_file_matches(const char *path, const char hashhex[65]) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) return -1;
+    junknas_sha256_ctx ctx;
+    junknas_sha256_init(&ctx);
+    uint8_t buf[65536];
+    ssize_t n;
+    while ((n = read(fd, buf, sizeof(buf))) > 0) junknas_sha256_update(&ctx, buf, (size_t)n);
+    close(fd);
+    if (n < 0) return -1;
+    uint8_t digest[32];
+    char calc[65];
+    junknas_sha256_final(&ctx, digest);
+    junknas_sha256_hex(digest, calc);
+    return strncasecmp(calc, hashhex, 64) == 0;
+}
+
+/* Quarantine one bad chunk and try to restore it from the mesh. Returns 1 if repaired. */
+static int scrub_repair_chunk(jnk_fuse_state_t *s, size_t dir, const char *path, const char hashhex[65]) {
+    char bad[MAX_PATH_LEN], tmp[MAX_PATH_LEN];
+    if (snprintf(bad, sizeof(bad), "%s.corrupt", path) >= (int)sizeof(bad) ||
+        snprintf(tmp, sizeof(tmp), "%s.scrub", path) >= (int)sizeof(tmp)) {
+        return 0;
+    }
+    if (rename(path, bad) != 0) return 0;
+    if (!s->mesh) return 0;
+
+    if (junknas_mesh_fetch_chunk(s->mesh, hashhex, tmp) != 0 ||
+        scrub_file_matches(tmp, hashhex) != 1 ||
+        rename(tmp, path) != 0) {
+        (void)unlink(tmp);
+        return 0;
+    }
+    store_used_add(s, dir, chunk_file_bytes(path)); /* the .corrupt copy still counts */
+    return 1;
+}
+
+static void scrub_pass(jnk_fuse_state_t *s) {
+    uint64_t checked = 0, corrupt = 0, repaired = 0;
+    time_t settle = time(NULL) - JNK_SCRUB_SETTLE_SEC;
+
+    for (size_t i = 0; i < s->store_dir_count && !s->scrub_quit; i++) {
+        char root[MAX_PATH_LEN];
+        if (snprintf(root, sizeof(root), "%s/%s/chunks/sha256", s->store_dirs[i], INTERNAL_DIR) >= (int)sizeof(root)) continue;
+        DIR *top = opendir(root);
+        if (!top) continue;
+        struct dirent *shard;
+        while (!s->scrub_quit && (shard = readdir(top)) != NULL) {
+            if (strlen(shard->d_name) != 2 || shard->d_name[0] == '.') continue;
+            char shard_dir[MAX_PATH_LEN];
+            if (snprintf(shard_dir, sizeof(shard_dir), "%s/%s", root, shard->d_name) >= (int)sizeof(shard_dir)) continue;
+            DIR *d = opendir(shard_dir);
+            if (!d) continue;
+            struct dirent *ent;
+            while (!s->scrub_quit && (ent = readdir(d)) != NULL) {
+                if (strlen(ent->d_name) != 64) continue; /* skips .tmp, .scrub, .corrupt */
+                int hex = 1;
+                for (size_t k = 0; k < 64 && hex; k++) hex = isxdigit((unsigned char)ent->d_name[k]);
+                if (!hex) continue;
+
+                char p[MAX_PATH_LEN];
+                if (snprintf(p, sizeof(p), "%s/%s", shard_dir, ent->d_name) >= (int)sizeof(p)) continue;
+                struct stat st;
+                if (stat(p, &st) != 0 || !S_ISREG(st.st_mode) || st.st_mtime > settle) continue;
+
+                int ok = scrub_file_matches(p, ent->d_name);
+                if (ok < 0) continue; /* vanished (refs dropped) or unreadable */
+                checked++;
+                junknas_stats_inc(JUNKNAS_STAT_SCRUB_CHECKED);
+                if (ok) continue;
+
+                corrupt++;
+                junknas_stats_inc(JUNKNAS_STAT_SCRUB_CORRUPT);
+                int fixed = scrub_repair_chunk(s, i, p, ent->d_name);
+                if (fixed) {
+                    repaired++;
+                    junknas_stats_inc(JUNKNAS_STAT_SCRUB_REPAIRED);
+                }
+                fprintf(stderr, "fuse: scrub: chunk %s in %s is corrupt; %s\n", ent->d_name, s->store_dirs[i],
+                        fixed ? "restored from a peer" : "moved aside as .corrupt, no peer copy yet");
+                char data[JUNKNAS_EVENT_DATA_LEN];
+                snprintf(data, sizeof(data), "{\"hash\":\"%s\",\"repaired\":%s}", ent->d_name,
+                         fixed ? "true" : "false");
+                junknas_events_publish("chunk_corrupt", data);
+            }
+            closedir(d);
+        }
+        closedir(top);
+    }
+
+    fuse_log_verbose(s->config, "fuse: scrub checked %" PRIu64 " chunks, %" PRIu64 " corrupt, %" PRIu64 " repaired",
+                     checked, corrupt, repaired);
+}
+
+static void *scrub_thread_main(void *arg) {
+    jnk_fuse_state_t *s = (jnk_fuse_state_t *)arg;
+    pthread_mutex_lock(&s->scrub_lock);
+    while (!s->scrub_quit) {
+        struct timespec deadline;
+        clock_gettime(CLOCK_REALTIME, &deadline);
+        deadline.tv_sec += (time_t)s->scrub_interval_sec;
+        int rc = 0;
+        while (!s->scrub_quit && rc != ETIMEDOUT) {
+            rc = pthread_cond_timedwait(&s->scrub_cond, &s->scrub_lock, &deadline);
+        }
+        if (s->scrub_quit) break;
+        pthread_mutex_unlock(&s->scrub_lock);
+        scrub_pass(s);
+        pthread_mutex_lock(&s->scrub_lock);
+    }
+    pthread_mutex_unlock(&s->scrub_lock);
+    return NULL;
+}
+
+static void scrub_start(jnk_fuse_state_t *s) {
+    s->scrub_interval_sec = JNK_SCRUB_DEFAULT_INTERVAL_SEC;
+    const char *env = getenv("JUNKNAS_SCRUB_INTERVAL");
+    if (env) {
+        long secs = strtol(env, NULL, 10);
+        s->scrub_interval_sec = (secs > 0) ? (uint64_t)secs : 0;
+    }
+    if (s->scrub_interval_sec == 0) return;
+
+    pthread_mutex_init(&s->scrub_lock, NULL);
+    pthread_cond_init(&s->scrub_cond, NULL);
+    if (pthread_create(&s->scrub_thread, NULL, scrub_thread_main, s) == 0) {
+        s->scrub_running = 1;
+        fuse_log_verbose(s->config, "fuse: chunk scrub every %" PRIu64 "s", s->scrub_interval_sec);
+    }
+}
+
+static void scrub_stop(jnk_fuse_state_t *s) {
+    if (!s->scrub_running) return;
+    pthread_mutex_lock(&s->scrub_lock);
+    s->scrub_quit = 1;
+    pthread_cond_broadcast(&s->scrub_cond);
+    pthread_mutex_unlock(&s->scrub_lock);
+    pthread_join(s->scrub_thread, NULL);
+    pthread_cond_destroy(&s->scrub_cond);
+    pthread_mutex_destroy(&s->scrub_lock);
+    s->scrub_running = 0;
+}
+
 /* ---------------------------- Meta (manifest) --------------------------- */
 
 static int load_manifest(const char *meta_path, size_t *out_size, char ***out_hashes, size_t *out_count) {
@@ -801,7 +1215,42 @@ static int load_manifest(const char *meta_path, size_t *out_size, char ***out_ha
     return 0;
 }
 
-static int save_manifest_atomic(const char *meta_path, size_t size, char **hashes, size_t count) {
+/* Optional attribute lines written right after "size": "mode <octal>" once
+ * the file was created or chmod'ed, "owner <uid> <gid>" once chown'ed.
+ * load_manifest skips them; files without them are 0644 and owned by us.
+ */
+#define JNK_FILE_DEFAULT_MODE 0644
+#define JNK_MANIFEST_ATTR_LINES 3
+
+typedef struct {
+    int    has_mode;
+    mode_t mode;                /* permission bits only */
+    int    has_owner;
+    uid_t  uid;
+    gid_t  gid;
+} jnk_file_attrs_t;
+
+static void load_file_attrs(const char *meta_path, jnk_file_attrs_t *a) {
+    memset(a, 0, sizeof(*a));
+    FILE *f = fopen(meta_path, "rb");
+    if (!f) return;
+    char line[256];
+    for (int i = 0; i < JNK_MANIFEST_ATTR_LINES && fgets(line, sizeof(line), f); i++) {
+        unsigned int mode = 0, uid = 0, gid = 0;
+        if (sscanf(line, "mode %o", &mode) == 1) {
+            a->has_mode = 1;
+            a->mode = (mode_t)(mode & 0777);
+        } else if (sscanf(line, "owner %u %u", &uid, &gid) == 2) {
+            a->has_owner = 1;
+            a->uid = (uid_t)uid;
+            a->gid = (gid_t)gid;
+        }
+    }
+    fclose(f);
+}
+
+static int save_manifest_with_attrs(const char *meta_path, size_t size, char **hashes, size_t count,
+                                    const jnk_file_attrs_t *attrs) {
     if (ensure_parent_dirs(meta_path) != 0) return -1;
 
     char tmp[MAX_PATH_LEN];
@@ -811,6 +1260,10 @@ static int save_manifest_atomic(const char *meta_path, size_t size, char **hashe
     if (!f) return -1;
 
     fprintf(f, "size %zu\n", size);
+    if (attrs && attrs->has_mode) fprintf(f, "mode %o\n", (unsigned int)attrs->mode);
+    if (attrs && attrs->has_owner) {
+        fprintf(f, "owner %u %u\n", (unsigned int)attrs->uid, (unsigned int)attrs->gid);
+    }
 
     for (size_t i = 0; i < count; i++) {
         if (hashes[i]) {
@@ -825,6 +1278,73 @@ static int save_manifest_atomic(const char *meta_path, size_t size, char **hashe
     return 0;
 }
 
+/* Rewrite the manifest, keeping the mode/owner lines it already has. */
+static int save_manifest_atomic(const char *meta_path, size_t size, char **hashes, size_t count) {
+    jnk_file_attrs_t attrs;
+    load_file_attrs(meta_path, &attrs);
+    return save_manifest_with_attrs(meta_path, size, hashes, count, &attrs);
+}
+
+/* Create an empty manifest only if none exists yet (link() won't replace).
+ * Returns 0, -EEXIST if another create got there first, or -EIO.
+ */
+static int create_manifest_exclusive(const char *meta_path, const jnk_file_attrs_t *attrs) {
+    static unsigned int seq;
+    if (ensure_parent_dirs(meta_path) != 0) return -EIO;
+
+    char tmp[MAX_PATH_LEN];
+    if (snprintf(tmp, sizeof(tmp), "%s.tmp.%d.%u", meta_path, (int)getpid(),
+                 __sync_fetch_and_add(&seq, 1)) >= (int)sizeof(tmp)) {
+        return -EIO;
+    }
+
+    FILE *f = fopen(tmp, "wbx");
+    if (!f) return -EIO;
+    fprintf(f, "size 0\nmode %o\n", (unsigned int)(attrs->mode & 0777));
+    if (attrs->has_owner) {
+        fprintf(f, "owner %u %u\n", (unsigned int)attrs->uid, (unsigned int)attrs->gid);
+    }
+    if (fflush(f) != 0) { fclose(f); (void)unlink(tmp); return -EIO; }
+    fclose(f);
+
+    int rc = 0;
+    if (link(tmp, meta_path) != 0) rc = (errno == EEXIST) ? -EEXIST : -EIO;
+    (void)unlink(tmp);
+    return rc;
+}
+
+/* Mode and group for a new entry at realp from the create policy covering
+ * it. Without one the requested mode is kept as is. A policy group is
+ * stored with the calling user as owner.
+ * Returns 1 if a policy applied, 0 if none did, -EIO if one is unreadable.
+ */
+static int create_policy_attrs(const jnk_fuse_state_t *s, const char *realp, mode_t mode,
+                               jnk_file_attrs_t *attrs) {
+    memset(attrs, 0, sizeof(*attrs));
+    junknas_create_policy_t policy;
+    int found = junknas_create_policy_resolve(s->backing_dir, realp, &policy);
+    if (found < 0) return -EIO;
+    attrs->has_mode = 1;
+    attrs->mode = junknas_create_policy_mode(found ? &policy : NULL, mode);
+    if (found && policy.has_group) {
+        attrs->has_owner = 1;
+        attrs->uid = fuse_get_context()->uid;
+        attrs->gid = policy.gid;
+    }
+    return found;
+}
+
+/* New files belong to whoever created them (a Samba or other user on an
+ * allow_other mount), in their primary group unless a policy set one.
+ */
+static void new_file_owner(jnk_file_attrs_t *attrs) {
+    if (attrs->has_owner) return;
+    const struct fuse_context *ctx = fuse_get_context();
+    attrs->has_owner = 1;
+    attrs->uid = ctx->uid;
+    attrs->gid = ctx->gid;
+}
+
 static void free_hashes(char **hashes, size_t count) {
     if (!hashes) return;
     for (size_t i = 0; i < count; i++) free(hashes[i]);
@@ -844,6 +1364,103 @@ static int ensure_hash_capacity(jnk_file_handle_t *h, size_t new_count) {
     return 0;
 }
 
+/* --------------------------- Open-file table --------------------------- */
+
+/* Caller holds open_lock. Unlinked entries are never matched: a new file
+ * created under the same name gets its own entry.
+ */
+static jnk_open_file_t *open_file_find_locked(jnk_fuse_state_t *s, const char *meta_path) {
+    for (jnk_open_file_t *of = s->open_files; of; of = of->next) {
+        if (!of->unlinked && strcmp(of->meta_path, meta_path) == 0) return of;
+    }
+    return NULL;
+}
+
+static jnk_open_file_t *open_file_acquire(jnk_fuse_state_t *s, const char *meta_path, jnk_file_handle_t *h) {
+    pthread_mutex_lock(&s->open_lock);
+    jnk_open_file_t *of = open_file_find_locked(s, meta_path);
+    if (!of) {
+        of = (jnk_open_file_t *)calloc(1, sizeof(*of));
+        if (!of) {
+            pthread_mutex_unlock(&s->open_lock);
+            return NULL;
+        }
+        strncpy(of->meta_path, meta_path, sizeof(of->meta_path) - 1);
+        of->next = s->open_files;
+        s->open_files = of;
+    }
+    of->open_count++;
+    h->next_in_file = of->handles;
+    of->handles = h;
+    pthread_mutex_unlock(&s->open_lock);
+    return of;
+}
+
+/* Caller holds open_lock. Drops one reference; if it was the last one the
+ * entry is freed and, for an unlinked file, its manifest hashes are handed
+ * back so the caller can release their refs before dropping the lock.
+ */
+static void open_file_put_locked(jnk_fuse_state_t *s, jnk_open_file_t *of, jnk_file_handle_t *h,
+                                 char ***out_hashes, size_t *out_count) {
+    *out_hashes = NULL;
+    *out_count = 0;
+    for (jnk_file_handle_t **pp = &of->handles; *pp; pp = &(*pp)->next_in_file) {
+        if (*pp == h) {
+            *pp = h->next_in_file;
+            break;
+        }
+    }
+    if (--of->open_count > 0) return;
+
+    for (jnk_open_file_t **pp = &s->open_files; *pp; pp = &(*pp)->next) {
+        if (*pp == of) {
+            *pp = of->next;
+            break;
+        }
+    }
+    if (of->unlinked) {
+        *out_hashes = of->disk_hashes;
+        *out_count = of->disk_count;
+    } else {
+        free_hashes(of->disk_hashes, of->disk_count);
+    }
+    free(of);
+}
+
+static void open_file_release(jnk_fuse_state_t *s, jnk_open_file_t *of, jnk_file_handle_t *h) {
+    char **hashes = NULL;
+    size_t count = 0;
+    pthread_mutex_lock(&s->open_lock);
+    open_file_put_locked(s, of, h, &hashes, &count);
+    if (hashes) {
+        (void)apply_ref_deltas_from_manifests(s, hashes, count, NULL, 0);
+        free_hashes(hashes, count);
+    }
+    pthread_mutex_unlock(&s->open_lock);
+}
+
+/* Caller holds open_lock. Keep open entries pointing at their manifest
+ * across rename(2).
+ */
+static void open_files_renamed_locked(jnk_fuse_state_t *s, const char *from, const char *to, int is_dir) {
+    size_t from_len = strlen(from);
+    for (jnk_open_file_t *of = s->open_files; of; of = of->next) {
+        if (of->unlinked) continue;
+        if (!is_dir) {
+            if (strcmp(of->meta_path, from) == 0) {
+                snprintf(of->meta_path, sizeof(of->meta_path), "%s", to);
+            }
+            continue;
+        }
+        if (strncmp(of->meta_path, from, from_len) == 0 && of->meta_path[from_len] == '/') {
+            char moved[MAX_PATH_LEN];
+            if (snprintf(moved, sizeof(moved), "%s%s", to, of->meta_path + from_len) < (int)sizeof(moved)) {
+                memcpy(of->meta_path, moved, sizeof(of->meta_path));
+            }
+        }
+    }
+}
+
 /* --------------------------- FUSE Callbacks ---------------------------- */
 
 static int jnk_getattr(const char *path, struct stat *st, struct fuse_file_info *fi) {
@@ -865,22 +1482,29 @@ static int jnk_getattr(const char *path, struct stat *st, struct fuse_file_info
         return 0;
     }
 
-    if (file_exists(metap)) {
+    struct stat meta_st;
+    if (lstat(metap, &meta_st) == 0 && S_ISREG(meta_st.st_mode)) {
         size_t size = 0;
         char **hashes = NULL;
         size_t count = 0;
         if (load_manifest(metap, &size, &hashes, &count) != 0) return -EIO;
         free_hashes(hashes, count);
 
-        st->st_mode = S_IFREG | 0644;
+        jnk_file_attrs_t attrs;
+        load_file_attrs(metap, &attrs);
+
+        /* Times are the manifest's: it's rewritten only when content changes
+         * and utimens sets them directly, so they stay stable for rsync.
+         */
+        st->st_mode = S_IFREG | (attrs.has_mode ? attrs.mode : JNK_FILE_DEFAULT_MODE);
         st->st_nlink = 1;
         st->st_size = (off_t)size;
-        st->st_uid = getuid();
-        st->st_gid = getgid();
+        st->st_uid = attrs.has_owner ? attrs.uid : getuid();
+        st->st_gid = attrs.has_owner ? attrs.gid : getgid();
         st->st_blksize = JNK_CHUNK_SIZE;
-        st->st_atime = time(NULL);
-        st->st_mtime = st->st_atime;
-        st->st_ctime = st->st_atime;
+        st->st_atim = meta_st.st_atim;
+        st->st_mtim = meta_st.st_mtim;
+        st->st_ctim = meta_st.st_ctim;
         return 0;
     }
 
@@ -905,11 +1529,12 @@ static int jnk_readdir(const char *path, void *buf, fuse_fill_dir_t filler,
     while ((de = readdir(d)) != NULL) {
         const char *name = de->d_name;
         if (strcmp(name, INTERNAL_DIR) == 0) continue; /* hide .jnk */
+        if (strcmp(name, JUNKNAS_CREATE_POLICY_FILE) == 0) continue;
 
-        if (str_endswith(name, META_SUFFIX)) {
+        if (str_endswith(name, JUNKNAS_META_SUFFIX)) {
             /* Show logical file name (strip suffix) */
             size_t ln = strlen(name);
-            size_t ms = strlen(META_SUFFIX);
+            size_t ms = strlen(JUNKNAS_META_SUFFIX);
             size_t base = ln - ms;
             char logical[NAME_MAX + 1];
             if (base > NAME_MAX) base = NAME_MAX;
@@ -935,13 +1560,27 @@ static int jnk_mkdir(const char *path, mode_t mode) {
 
     (void)metap;
 
+    jnk_file_attrs_t attrs;
+    int policy = create_policy_attrs(s, realp, mode, &attrs);
+    if (policy < 0) return policy;
+
     if (ensure_parent_dirs(realp) != 0) return -EIO;
     if (mkdir(realp, mode) != 0) return -errno;
+    if (policy) {
+        /* mkdir(2) applied our own umask; the policy's bits must stick. */
+        if (chmod(realp, attrs.mode) != 0 ||
+            (attrs.has_owner && lchown(realp, (uid_t)-1, attrs.gid) != 0)) {
+            int err = errno;
+            (void)rmdir(realp);
+            return -err;
+        }
+    }
     return 0;
 }
 
+static int jnk_open(const char *path, struct fuse_file_info *fi);
+
 static int jnk_create(const char *path, mode_t mode, struct fuse_file_info *fi) {
-    (void)mode;
     jnk_fuse_state_t *s = get_state();
 
     char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
@@ -953,14 +1592,32 @@ static int jnk_create(const char *path, mode_t mode, struct fuse_file_info *fi)
     /* Create an empty manifest:
      *   size 0
      *   (no chunks)
+     * Never over an existing one: that would drop its chunks without
+     * releasing their refs. Two creators racing on one path both end up
+     * with the same file; O_EXCL callers get EEXIST.
+     * Mode and group come from the directory's create policy, if any.
      */
-    if (save_manifest_atomic(metap, 0, NULL, 0) != 0) return -EIO;
+    jnk_file_attrs_t attrs;
+    int rc = create_policy_attrs(s, realp, mode, &attrs);
+    if (rc < 0) return rc;
+    new_file_owner(&attrs);
+    rc = create_manifest_exclusive(metap, &attrs);
+    if (rc == -EEXIST) {
+        if (fi->flags & O_EXCL) return -EEXIST;
+        return jnk_open(path, fi);
+    }
+    if (rc != 0) return rc;
 
     /* Allocate per-open handle */
     jnk_file_handle_t *h = (jnk_file_handle_t *)calloc(1, sizeof(*h));
     if (!h) return -ENOMEM;
 
     strncpy(h->meta_path, metap, sizeof(h->meta_path) - 1);
+    h->of = open_file_acquire(s, metap, h);
+    if (!h->of) {
+        free(h);
+        return -ENOMEM;
+    }
 
     /* Current in-memory view (modifiable during this open) */
     h->size = 0;
@@ -981,6 +1638,305 @@ static int jnk_create(const char *path, mode_t mode, struct fuse_file_info *fi)
     return 0;
 }
 
+/* Only regular files: there are no device nodes, fifos or sockets here. */
+static int jnk_mknod(const char *path, mode_t mode, dev_t rdev) {
+    (void)rdev;
+    jnk_fuse_state_t *s = get_state();
+
+    if (!S_ISREG(mode)) return -EPERM;
+
+    char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+
+    if (dir_exists(realp)) return -EEXIST;
+    jnk_file_attrs_t attrs;
+    int rc = create_policy_attrs(s, realp, mode, &attrs);
+    if (rc < 0) return rc;
+    new_file_owner(&attrs);
+    return create_manifest_exclusive(metap, &attrs);
+}
+
+/* Rewrite a closed or open file's manifest with new attrs, under open_lock
+ * so it can't interleave with release() saving the same manifest. The
+ * manifest's atime/mtime are put back: chmod/chown aren't content changes.
+ */
+static int update_file_attrs(jnk_fuse_state_t *s, const char *metap,
+                             void (*apply)(jnk_file_attrs_t *, const void *), const void *arg) {
+    pthread_mutex_lock(&s->open_lock);
+    size_t size = 0, count = 0;
+    char **hashes = NULL;
+    if (load_manifest(metap, &size, &hashes, &count) != 0) {
+        pthread_mutex_unlock(&s->open_lock);
+        return file_exists(metap) ? -EIO : -ENOENT;
+    }
+    struct stat before;
+    int have_times = (lstat(metap, &before) == 0);
+    jnk_file_attrs_t attrs;
+    load_file_attrs(metap, &attrs);
+    apply(&attrs, arg);
+    int rc = (save_manifest_with_attrs(metap, size, hashes, count, &attrs) == 0) ? 0 : -EIO;
+    if (rc == 0 && have_times) {
+        struct timespec times[2] = { before.st_atim, before.st_mtim };
+        (void)utimensat(AT_FDCWD, metap, times, AT_SYMLINK_NOFOLLOW);
+    }
+    pthread_mutex_unlock(&s->open_lock);
+    free_hashes(hashes, count);
+    return rc;
+}
+
+static void apply_mode(jnk_file_attrs_t *a, const void *arg) {
+    a->has_mode = 1;
+    a->mode = *(const mode_t *)arg & 0777;
+}
+
+typedef struct {
+    uid_t uid;
+    gid_t gid;
+} jnk_owner_change_t;
+
+static void apply_owner(jnk_file_attrs_t *a, const void *arg) {
+    const jnk_owner_change_t *c = (const jnk_owner_change_t *)arg;
+    uid_t cur_uid = a->has_owner ? a->uid : getuid();
+    gid_t cur_gid = a->has_owner ? a->gid : getgid();
+    a->has_owner = 1;
+    a->uid = (c->uid == (uid_t)-1) ? cur_uid : c->uid;
+    a->gid = (c->gid == (gid_t)-1) ? cur_gid : c->gid;
+}
+
+/* Owner of a file as stored in its manifest; files without one belong to
+ * the daemon's user.
+ */
+static uid_t file_owner(const char *metap) {
+    jnk_file_attrs_t attrs;
+    load_file_attrs(metap, &attrs);
+    return attrs.has_owner ? attrs.uid : getuid();
+}
+
+/* Permission bits only: setuid/setgid/sticky are dropped for files. As
+ * with chown, only root and the owner may change them.
+ */
+static int jnk_chmod(const char *path, mode_t mode, struct fuse_file_info *fi) {
+    (void)fi;
+    jnk_fuse_state_t *s = get_state();
+
+    char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+
+    if (strcmp(path, "/") == 0 || dir_exists(realp)) {
+        if (chmod(realp, mode & 07777) != 0) return -errno;
+        return 0;
+    }
+
+    const struct fuse_context *ctx = fuse_get_context();
+    if (ctx->uid != 0 && ctx->uid != file_owner(metap)) return -EPERM;
+    return update_file_attrs(s, metap, apply_mode, &mode);
+}
+
+/* Whether the calling process is in gid, primary or supplementary. */
+static int caller_in_group(gid_t gid) {
+    const struct fuse_context *ctx = fuse_get_context();
+    if (gid == ctx->gid) return 1;
+    gid_t groups[64];
+    int n = fuse_getgroups(64, groups);
+    gid_t *list = groups;
+    if (n > 64) {
+        list = (gid_t *)malloc((size_t)n * sizeof(gid_t));
+        if (!list) return 0;
+        n = fuse_getgroups(n, list);
+    }
+    int found = 0;
+    for (int i = 0; i < n && !found; i++) found = (list[i] == gid);
+    if (list != groups) free(list);
+    return found;
+}
+
+/* Files keep their owner in the manifest. As on any filesystem, only root
+ * may give a file away; others may only set the group to one they are in.
+ */
+static int jnk_chown(const char *path, uid_t uid, gid_t gid, struct fuse_file_info *fi) {
+    (void)fi;
+    jnk_fuse_state_t *s = get_state();
+
+    char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+
+    if (strcmp(path, "/") == 0 || dir_exists(realp)) {
+        if (lchown(realp, uid, gid) != 0) return -errno;
+        return 0;
+    }
+
+    const struct fuse_context *ctx = fuse_get_context();
+    if (ctx->uid != 0) {
+        uid_t owner = file_owner(metap);
+        if (ctx->uid != owner) return -EPERM;
+        if (uid != (uid_t)-1 && uid != owner) return -EPERM;
+        if (gid != (gid_t)-1 && !caller_in_group(gid)) return -EPERM;
+    }
+
+    jnk_owner_change_t change = { uid, gid };
+    return update_file_attrs(s, metap, apply_owner, &change);
+}
+
+/* touch(1) sets times right after create; apply them to the backing entry. */
+static int jnk_utimens(const char *path, const struct timespec tv[2], struct fuse_file_info *fi) {
+    (void)fi;
+    jnk_fuse_state_t *s = get_state();
+
+    char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+
+    const char *target = NULL;
+    if (strcmp(path, "/") == 0 || dir_exists(realp)) target = realp;
+    else if (file_exists(metap)) target = metap;
+    else return -ENOENT;
+
+    if (utimensat(AT_FDCWD, target, tv, AT_SYMLINK_NOFOLLOW) != 0) return -errno;
+
+    /* Open with unsaved writes (cp -p, rsync --inplace -t): the manifest is
+     * rewritten on release, so remember the times to put back afterwards.
+     */
+    struct stat st;
+    if (target == metap && stat(metap, &st) == 0) {
+        pthread_mutex_lock(&s->open_lock);
+        jnk_open_file_t *of = open_file_find_locked(s, metap);
+        if (of) {
+            of->times[0] = st.st_atim;
+            of->times[1] = st.st_mtim;
+            of->times_set = 1;
+        }
+        pthread_mutex_unlock(&s->open_lock);
+    }
+    return 0;
+}
+
+/* Two xattrs: the quota warning, set on every entry while the soft quota
+ * warning is active so scripts on the mount can check for it, and a
+ * directory's create policy.
+ */
+static int policy_file_path(const char *realp, char out[MAX_PATH_LEN]) {
+    return (snprintf(out, MAX_PATH_LEN, "%s/%s", realp, JUNKNAS_CREATE_POLICY_FILE) >= MAX_PATH_LEN) ? -1 : 0;
+}
+
+static int jnk_getxattr(const char *path, const char *name, char *value, size_t size) {
+    jnk_fuse_state_t *s = get_state();
+
+    char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+    int is_dir = (strcmp(path, "/") == 0 || dir_exists(realp));
+    if (!is_dir && !file_exists(metap)) return -ENOENT;
+
+    if (strcmp(name, JUNKNAS_CREATE_POLICY_XATTR) == 0) {
+        junknas_create_policy_t policy;
+        int rc = is_dir ? junknas_create_policy_load(realp, &policy) : 0;
+        if (rc < 0) return -EIO;
+        if (rc == 0) return -ENODATA;
+        char text[JUNKNAS_CREATE_POLICY_TEXT_LEN];
+        int n = junknas_create_policy_format(&policy, text, sizeof(text));
+        if (n < 0) return -EIO;
+        if (size == 0) return n;
+        if (size < (size_t)n) return -ERANGE;
+        memcpy(value, text, (size_t)n);
+        return n;
+    }
+
+    if (strcmp(name, JNK_QUOTA_WARN_XATTR) != 0 || !s->quota_warning) return -ENODATA;
+    if (size == 0) return 1;
+    value[0] = '1';
+    return 1;
+}
+
+static int jnk_listxattr(const char *path, char *list, size_t size) {
+    jnk_fuse_state_t *s = get_state();
+
+    char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+    int is_dir = (strcmp(path, "/") == 0 || dir_exists(realp));
+    if (!is_dir && !file_exists(metap)) return -ENOENT;
+
+    char policy_path[MAX_PATH_LEN];
+    int has_policy = is_dir && policy_file_path(realp, policy_path) == 0 && file_exists(policy_path);
+
+    size_t need = 0;
+    if (s->quota_warning) need += sizeof(JNK_QUOTA_WARN_XATTR);
+    if (has_policy) need += sizeof(JUNKNAS_CREATE_POLICY_XATTR);
+    if (size == 0) return (int)need;
+    if (size < need) return -ERANGE;
+
+    size_t used = 0;
+    if (s->quota_warning) {
+        memcpy(list + used, JNK_QUOTA_WARN_XATTR, sizeof(JNK_QUOTA_WARN_XATTR));
+        used += sizeof(JNK_QUOTA_WARN_XATTR);
+    }
+    if (has_policy) {
+        memcpy(list + used, JUNKNAS_CREATE_POLICY_XATTR, sizeof(JUNKNAS_CREATE_POLICY_XATTR));
+        used += sizeof(JUNKNAS_CREATE_POLICY_XATTR);
+    }
+    return (int)used;
+}
+
+/* Only the create policy is settable, on directories, by root or the
+ * directory's owner. The value is checked and stored in canonical form.
+ */
+static int policy_xattr_target(const char *path, const char *name, char realp[MAX_PATH_LEN],
+                               char policy_path[MAX_PATH_LEN]) {
+    jnk_fuse_state_t *s = get_state();
+
+    char metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+    if (strcmp(name, JUNKNAS_CREATE_POLICY_XATTR) != 0) return -ENOTSUP;
+
+    struct stat st;
+    if (lstat(realp, &st) != 0 || !S_ISDIR(st.st_mode)) {
+        return file_exists(metap) ? -ENOTSUP : -ENOENT;
+    }
+    const struct fuse_context *ctx = fuse_get_context();
+    if (ctx->uid != 0 && ctx->uid != st.st_uid) return -EPERM;
+    if (policy_file_path(realp, policy_path) != 0) return -EINVAL;
+    return 0;
+}
+
+static int jnk_setxattr(const char *path, const char *name, const char *value, size_t size, int flags) {
+    char realp[MAX_PATH_LEN], policy_path[MAX_PATH_LEN];
+    int rc = policy_xattr_target(path, name, realp, policy_path);
+    if (rc != 0) return rc;
+
+    junknas_create_policy_t policy;
+    if (junknas_create_policy_parse(value, size, &policy) != 0) return -EINVAL;
+    int exists = file_exists(policy_path);
+    if ((flags & XATTR_CREATE) && exists) return -EEXIST;
+    if ((flags & XATTR_REPLACE) && !exists) return -ENODATA;
+
+    char text[JUNKNAS_CREATE_POLICY_TEXT_LEN];
+    int n = junknas_create_policy_format(&policy, text, sizeof(text));
+    if (n < 0) return -EIO;
+
+    /* Staged under .jnk so a leftover temp file never shows in listings. */
+    jnk_fuse_state_t *s = get_state();
+    char tmp[MAX_PATH_LEN];
+    if (snprintf(tmp, sizeof(tmp), "%s/%s/policy.tmp.%lu", s->backing_dir, INTERNAL_DIR,
+                 (unsigned long)pthread_self()) >= (int)sizeof(tmp)) {
+        return -EINVAL;
+    }
+    FILE *f = fopen(tmp, "wb");
+    if (!f) return -errno;
+    int ok = (fwrite(text, 1, (size_t)n, f) == (size_t)n);
+    if (fflush(f) != 0) ok = 0;
+    fclose(f);
+    if (!ok || rename(tmp, policy_path) != 0) {
+        (void)unlink(tmp);
+        return -EIO;
+    }
+    return 0;
+}
+
+static int jnk_removexattr(const char *path, const char *name) {
+    char realp[MAX_PATH_LEN], policy_path[MAX_PATH_LEN];
+    int rc = policy_xattr_target(path, name, realp, policy_path);
+    if (rc != 0) return rc;
+    if (unlink(policy_path) != 0) return (errno == ENOENT) ? -ENODATA : -errno;
+    return 0;
+}
+
 static int jnk_open(const char *path, struct fuse_file_info *fi) {
     jnk_fuse_state_t *s = get_state();
 
@@ -1003,6 +1959,12 @@ static int jnk_open(const char *path, struct fuse_file_info *fi) {
         free(h);
         return -EIO;
     }
+    h->of = open_file_acquire(s, metap, h);
+    if (!h->of) {
+        free_hashes(h->hashes, h->chunk_count);
+        free(h);
+        return -ENOMEM;
+    }
     h->dirty = 0;
     h->dirty_chunks = NULL;
 
@@ -1014,6 +1976,7 @@ static int jnk_open(const char *path, struct fuse_file_info *fi) {
      */
     h->orig_size = h->size;
     if (clone_hashes(&h->orig_hashes, &h->orig_chunk_count, h->hashes, h->chunk_count) != 0) {
+        open_file_release(s, h->of, h);
         free_hashes(h->hashes, h->chunk_count);
         free(h);
         return -ENOMEM;
@@ -1040,6 +2003,7 @@ static int load_chunk_into_buf(jnk_fuse_state_t *s, jnk_file_handle_t *h, size_t
     if (idx < h->chunk_count && h->hashes[idx]) {
         size_t got_len = 0;
         int rc = read_chunk_verified(s, h->hashes[idx], out, JNK_CHUNK_SIZE, &got_len);
+        if (rc == -EAGAIN || rc == -EINTR || rc == -EACCES) return rc;
         if (rc != 0) return -EIO;
         if (got_len < JNK_CHUNK_SIZE) memset(out + got_len, 0, JNK_CHUNK_SIZE - got_len);
         return 0;
@@ -1098,12 +2062,125 @@ static void dirty_drop_from(jnk_file_handle_t *h, size_t keep_before) {
 }
 
 
+/* Shrink a handle's own view to ns. The tail of the last kept chunk is
+ * zeroed through a dirty chunk, which release stores like any write; with
+ * stored_tail_zeroed only an unsaved copy of it needs that.
+ */
+static int handle_shrink(jnk_fuse_state_t *s, jnk_file_handle_t *h, size_t ns, int stored_tail_zeroed) {
+    size_t needed = (ns == 0) ? 0 : ((ns - 1) / JNK_CHUNK_SIZE) + 1;
+    size_t tail_off = ns % JNK_CHUNK_SIZE;
+    if (tail_off != 0) {
+        size_t idx = needed - 1;
+        if (dirty_find(h, idx) || (!stored_tail_zeroed && idx < h->chunk_count && h->hashes[idx])) {
+            dirty_chunk_t *d = NULL;
+            int rc = dirty_get_or_create(s, h, idx, &d);
+            if (rc != 0) return rc;
+            memset(d->data + tail_off, 0, JNK_CHUNK_SIZE - tail_off);
+        }
+    }
+    for (size_t i = needed; i < h->chunk_count; i++) {
+        free(h->hashes[i]);
+        h->hashes[i] = NULL;
+    }
+    /* keep chunk_count as-is; manifest will omit NULLs beyond needed */
+    dirty_drop_from(h, needed);
+    h->size = ns;
+    h->dirty = 1;
+    return 0;
+}
+
+/* Catch up with truncates by path made while this handle was open: its
+ * refcount baseline becomes the manifest they wrote, and its own view is
+ * cut to the smallest size they asked for, then set to the last one.
+ */
+static int handle_apply_truncate(jnk_fuse_state_t *s, jnk_file_handle_t *h) {
+    pthread_mutex_lock(&s->open_lock);
+    if (!h->trunc_pending) {
+        pthread_mutex_unlock(&s->open_lock);
+        return 0;
+    }
+    size_t low = h->trunc_low, size = h->trunc_size;
+    char **base = h->trunc_hashes;
+    size_t base_count = h->trunc_count;
+    h->trunc_pending = 0;
+    h->trunc_hashes = NULL;
+    h->trunc_count = 0;
+    pthread_mutex_unlock(&s->open_lock);
+
+    free_hashes(h->orig_hashes, h->orig_chunk_count);
+    h->orig_hashes = base;
+    h->orig_chunk_count = base_count;
+    h->orig_size = size;
+
+    if (low < h->size) {
+        /* The truncate already stored the zeroed tail: take it unless
+         * this handle has its own unsaved copy of that chunk.
+         */
+        size_t idx = (low == 0) ? 0 : (low - 1) / JNK_CHUNK_SIZE;
+        if (low % JNK_CHUNK_SIZE != 0 && !dirty_find(h, idx) && idx < h->chunk_count) {
+            free(h->hashes[idx]);
+            h->hashes[idx] = NULL;
+            if (idx < base_count && base[idx]) {
+                h->hashes[idx] = (char *)malloc(65);
+                if (!h->hashes[idx]) return -ENOMEM;
+                memcpy(h->hashes[idx], base[idx], 65);
+            }
+        }
+        int rc = handle_shrink(s, h, low, 1);
+        if (rc != 0) return rc;
+    }
+    h->size = size;
+    h->dirty = 1;
+    return 0;
+}
+
+/* A read at or past EOF on a clean handle re-reads the manifest, so a
+ * reader following a file (tail -f) sees appends released by other handles.
+ * Handles with pending writes keep their own view.
+ */
+static void refresh_handle_if_grown(jnk_fuse_state_t *s, jnk_file_handle_t *h) {
+    if (h->dirty || h->dirty_chunks) return;
+
+    char meta_path[MAX_PATH_LEN];
+    pthread_mutex_lock(&s->open_lock);
+    int unlinked = h->of->unlinked;
+    memcpy(meta_path, h->of->meta_path, sizeof(meta_path));
+    pthread_mutex_unlock(&s->open_lock);
+    if (unlinked) return;
+
+    size_t sz = 0, cnt = 0;
+    char **hashes = NULL;
+    if (load_manifest(meta_path, &sz, &hashes, &cnt) != 0) return;
+    if (sz <= h->size) {
+        free_hashes(hashes, cnt);
+        return;
+    }
+
+    char **orig = NULL;
+    size_t orig_cnt = 0;
+    if (clone_hashes(&orig, &orig_cnt, hashes, cnt) != 0) {
+        free_hashes(hashes, cnt);
+        return;
+    }
+    free_hashes(h->hashes, h->chunk_count);
+    free_hashes(h->orig_hashes, h->orig_chunk_count);
+    h->hashes = hashes;
+    h->chunk_count = cnt;
+    h->size = sz;
+    h->orig_hashes = orig;
+    h->orig_chunk_count = orig_cnt;
+    h->orig_size = sz;
+}
+
 static int jnk_read(const char *path, char *buf, size_t size, off_t off, struct fuse_file_info *fi) {
     (void)path;
     jnk_fuse_state_t *s = get_state();
     jnk_file_handle_t *h = (jnk_file_handle_t *)(uintptr_t)fi->fh;
     if (!h) return -EIO;
 
+    int trc = handle_apply_truncate(s, h);
+    if (trc != 0) return trc;
+    if ((size_t)off + size > h->size) refresh_handle_if_grown(s, h);
     if ((size_t)off >= h->size) return 0;
     size_t max_can = h->size - (size_t)off;
     if (size > max_can) size = max_can;
@@ -1136,6 +2213,7 @@ static int jnk_read(const char *path, char *buf, size_t size, off_t off, struct
         size_t got_len = 0;
 
         int rc = read_chunk_verified(s, h->hashes[idx], chunk, sizeof(chunk), &got_len);
+        if (rc == -EAGAIN || rc == -EINTR || rc == -EACCES) return done > 0 ? (int)done : rc;
         if (rc != 0) return -EIO;
 
         /* chunk may be shorter than full size; treat beyond as zeros */
@@ -1154,12 +2232,24 @@ static int jnk_read(const char *path, char *buf, size_t size, off_t off, struct
     return (int)done;
 }
 
+/* New content after a utimens: the save that stores it must get its own
+ * mtime, not the one set before.
+ */
+static void forget_times_set(jnk_fuse_state_t *s, jnk_file_handle_t *h) {
+    pthread_mutex_lock(&s->open_lock);
+    h->of->times_set = 0;
+    pthread_mutex_unlock(&s->open_lock);
+}
+
 static int jnk_write(const char *path, const char *buf, size_t size, off_t off, struct fuse_file_info *fi) {
     (void)path;
     jnk_fuse_state_t *s = get_state();
     jnk_file_handle_t *h = (jnk_file_handle_t *)(uintptr_t)fi->fh;
     if (!h) return -EIO;
 
+    int trc = handle_apply_truncate(s, h);
+    if (trc != 0) return trc;
+
     size_t done = 0;
     while (done < size) {
         size_t abs_off = (size_t)off + done;
@@ -1185,37 +2275,140 @@ static int jnk_write(const char *path, const char *buf, size_t size, off_t off,
         h->size = end_pos;
         h->dirty = 1;
     }
+    forget_times_set(s, h);
 
+    junknas_stats_add(JUNKNAS_STAT_WRITE_BYTES_LOGICAL, (uint64_t)size);
     return (int)size;
 }
 
+/* A copy of chunk `hash` with everything from `keep` on zeroed, stored
+ * durably. A shrink that doesn't land on a chunk boundary needs it: chunks
+ * are always stored whole, and growing the file again must show zeros,
+ * not the old bytes.
+ */
+static int store_zeroed_tail(jnk_fuse_state_t *s, const char *hash, size_t keep, char out_hash[65]) {
+    uint8_t *buf = (uint8_t *)malloc(JNK_CHUNK_SIZE);
+    if (!buf) return -ENOMEM;
+    size_t got = 0;
+    int rc = read_chunk_verified(s, hash, buf, JNK_CHUNK_SIZE, &got);
+    if (rc != 0) {
+        free(buf);
+        return (rc == -EAGAIN || rc == -EINTR || rc == -EACCES) ? rc : -EIO;
+    }
+    if (got > keep) memset(buf + keep, 0, got - keep);
+    if (got < JNK_CHUNK_SIZE) memset(buf + got, 0, JNK_CHUNK_SIZE - got);
+    junknas_sha256_buf_hex(buf, JNK_CHUNK_SIZE, out_hash);
+    rc = store_put_chunk_if_missing(s, out_hash, buf, JNK_CHUNK_SIZE, NULL);
+    free(buf);
+    return rc;
+}
+
+/* truncate(2) by path: rewrite the manifest directly, zeroing the tail of
+ * the last kept chunk and dropping the refs of chunks past the new end.
+ * Handles open on the file are told to take the new size before their
+ * next use, so a later release doesn't write the old size back.
+ */
+static int truncate_by_path(jnk_fuse_state_t *s, const char *path, size_t ns) {
+    char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
+    if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
+    if (strcmp(path, "/") == 0 || dir_exists(realp)) return -EISDIR;
+
+    /* The tail chunk is read and stored without the lock (it may come from
+     * a peer); if the manifest changed meanwhile, start over. A zeroed tail
+     * that ends up unused stays behind unreferenced, like any unsaved chunk:
+     * dropping it here could delete it under another handle that stored the
+     * same bytes and hasn't saved yet.
+     */
+    for (int attempt = 0; attempt < 3; attempt++) {
+        size_t size = 0, count = 0;
+        char **hashes = NULL;
+        pthread_mutex_lock(&s->open_lock);
+        int lrc = load_manifest(metap, &size, &hashes, &count);
+        pthread_mutex_unlock(&s->open_lock);
+        if (lrc != 0) return file_exists(metap) ? -EIO : -ENOENT;
+
+        size_t keep = count;
+        if (ns < size) {
+            size_t needed = (ns == 0) ? 0 : ((ns - 1) / JNK_CHUNK_SIZE) + 1;
+            if (needed < keep) keep = needed;
+        }
+        char **next = NULL;
+        size_t next_count = 0;
+        if (clone_hashes(&next, &next_count, hashes, keep) != 0) {
+            free_hashes(hashes, count);
+            return -ENOMEM;
+        }
+
+        size_t tail_off = ns % JNK_CHUNK_SIZE;
+        if (ns < size && tail_off != 0 && keep == (ns - 1) / JNK_CHUNK_SIZE + 1 && next[keep - 1]) {
+            char zeroed[65];
+            int zrc = store_zeroed_tail(s, next[keep - 1], tail_off, zeroed);
+            if (zrc != 0) {
+                free_hashes(hashes, count);
+                free_hashes(next, next_count);
+                return zrc;
+            }
+            memcpy(next[keep - 1], zeroed, 65);
+        }
+
+        size_t cur_size = 0, cur_count = 0;
+        char **cur = NULL;
+        pthread_mutex_lock(&s->open_lock);
+        if (load_manifest(metap, &cur_size, &cur, &cur_count) != 0 || cur_size != size ||
+            !hashes_equal(cur, cur_count, hashes, count)) {
+            pthread_mutex_unlock(&s->open_lock);
+            free_hashes(cur, cur_count);
+            free_hashes(hashes, count);
+            free_hashes(next, next_count);
+            continue;
+        }
+        free_hashes(cur, cur_count);
+
+        int rc = 0;
+        if (save_manifest_atomic(metap, ns, next, next_count) != 0) {
+            rc = -EIO;
+        } else {
+            (void)apply_ref_deltas_from_manifests(s, hashes, count, next, next_count);
+        }
+        jnk_open_file_t *of = (rc == 0) ? open_file_find_locked(s, metap) : NULL;
+        if (of) of->times_set = 0;
+        for (jnk_file_handle_t *h = of ? of->handles : NULL; h && rc == 0; h = h->next_in_file) {
+            char **base = NULL;
+            size_t base_count = 0;
+            if (clone_hashes(&base, &base_count, next, next_count) != 0) {
+                rc = -ENOMEM;
+                break;
+            }
+            if (!h->trunc_pending || ns < h->trunc_low) h->trunc_low = ns;
+            h->trunc_pending = 1;
+            free_hashes(h->trunc_hashes, h->trunc_count);
+            h->trunc_hashes = base;
+            h->trunc_count = base_count;
+            h->trunc_size = ns;
+        }
+        pthread_mutex_unlock(&s->open_lock);
+        free_hashes(hashes, count);
+        free_hashes(next, next_count);
+        return rc;
+    }
+    return -EAGAIN;
+}
+
 static int jnk_truncate(const char *path, off_t newsize, struct fuse_file_info *fi) {
-    (void)path;
     if (newsize < 0) return -EINVAL;
 
+    jnk_fuse_state_t *s = get_state();
     jnk_file_handle_t *h = NULL;
     if (fi && fi->fh) h = (jnk_file_handle_t *)(uintptr_t)fi->fh;
 
-    /* If not open, we load & rewrite manifest (simpler to require open for now) */
-    if (!h) return -EACCES;
+    if (!h) return truncate_by_path(s, path, (size_t)newsize);
 
-    size_t ns = (size_t)newsize;
+    int rc = handle_apply_truncate(s, h);
+    if (rc != 0) return rc;
 
-    /* Shrink: drop hashes beyond last needed chunk; keep store data (GC later) */
-    if (ns < h->size) {
-        size_t needed = (ns == 0) ? 0 : ((ns - 1) / JNK_CHUNK_SIZE) + 1;
-        if (needed < h->chunk_count) {
-            for (size_t i = needed; i < h->chunk_count; i++) {
-                free(h->hashes[i]);
-                h->hashes[i] = NULL;
-            }
-            /* keep chunk_count as-is; manifest will omit NULLs beyond needed */
-        }
-        dirty_drop_from(h, needed);
-        h->size = ns;
-        h->dirty = 1;
-        return 0;
-    }
+    size_t ns = (size_t)newsize;
+    if (ns != h->size) forget_times_set(s, h);
+    if (ns < h->size) return handle_shrink(s, h, ns, 0);
 
     /* Expand: just update size (sparse) */
     if (ns > h->size) {
@@ -1225,29 +2418,43 @@ static int jnk_truncate(const char *path, off_t newsize, struct fuse_file_info *
     return 0;
 }
 
+/* Size and chunk list are what the handle started from. */
+static int handle_matches_orig(const jnk_file_handle_t *h) {
+    if (h->size != h->orig_size) return 0;
+    return hashes_equal(h->hashes, h->chunk_count, h->orig_hashes, h->orig_chunk_count);
+}
+
 static int jnk_release(const char *path, struct fuse_file_info *fi) {
   (void)path;
   jnk_fuse_state_t *s = get_state();
   jnk_file_handle_t *h = (jnk_file_handle_t *)(uintptr_t)fi->fh;
   if (!h) return 0;
 
-  for (dirty_chunk_t *d = h->dirty_chunks; d; d = d->next) {
+  /* An unlinked file's data dies with its last close: don't store it. */
+  pthread_mutex_lock(&s->open_lock);
+  int unlinked = h->of->unlinked;
+  pthread_mutex_unlock(&s->open_lock);
+
+  /* Batched durability: sync chunks in groups, always before the manifest. */
+  jnk_sync_batch_t *batch = NULL;
+  if (s->fsync_batch > 1 && h->dirty_chunks && !unlinked) {
+    batch = (jnk_sync_batch_t *)calloc(1, sizeof(*batch));
+  }
+
+  int rc = handle_apply_truncate(s, h);
+  for (dirty_chunk_t *d = h->dirty_chunks; d && !unlinked && rc == 0; d = d->next) {
     char hashhex[65];
-    sha256_buf_hex(d->data, JNK_CHUNK_SIZE, hashhex);
-    int rc = store_put_chunk_if_missing(s, hashhex, d->data, JNK_CHUNK_SIZE);
+    uint64_t t0 = junknas_stats_now_usec();
+    junknas_sha256_buf_hex(d->data, JNK_CHUNK_SIZE, hashhex);
+    JNK_TIMING_ADD(hash_usec, t0);
+    rc = store_put_chunk_if_missing(s, hashhex, d->data, JNK_CHUNK_SIZE, batch);
     if (rc != 0) {
-      dirty_free_all(h);
-      free_hashes(h->orig_hashes, h->orig_chunk_count);
-      free_hashes(h->hashes, h->chunk_count);
-      free(h);
-      return rc;
+      junknas_stats_inc(JUNKNAS_STAT_CHUNK_WRITE_FAILED);
+      break;
     }
     if (ensure_hash_capacity(h, d->idx + 1) != 0) {
-      dirty_free_all(h);
-      free_hashes(h->orig_hashes, h->orig_chunk_count);
-      free_hashes(h->hashes, h->chunk_count);
-      free(h);
-      return -ENOMEM;
+      rc = -ENOMEM;
+      break;
     }
     if (h->hashes[d->idx]) {
       free(h->hashes[d->idx]);
@@ -1255,34 +2462,62 @@ static int jnk_release(const char *path, struct fuse_file_info *fi) {
     }
     h->hashes[d->idx] = (char *)malloc(65);
     if (!h->hashes[d->idx]) {
-      dirty_free_all(h);
-      free_hashes(h->orig_hashes, h->orig_chunk_count);
-      free_hashes(h->hashes, h->chunk_count);
-      free(h);
-      return -ENOMEM;
+      rc = -ENOMEM;
+      break;
     }
     memcpy(h->hashes[d->idx], hashhex, 65);
     h->dirty = 1;
   }
   dirty_free_all(h);
+  if (batch) {
+    uint64_t t0 = junknas_stats_now_usec();
+    int frc = sync_batch_flush(s, batch);
+    JNK_TIMING_ADD(disk_usec, t0);
+    if (rc == 0) rc = frc;
+    free(batch);
+  }
 
-  /* If the manifest changed, write it, then update refs based on diff */
-  if (h->dirty) {
-    if (save_manifest_atomic(h->meta_path, h->size, h->hashes, h->chunk_count) != 0) {
+  /* If the manifest changed, write it, then update refs based on diff.
+   * Rewriting identical content would only bump the mtime (and make rsync
+   * copy the file again), so that is skipped.
+   * Held under open_lock so a concurrent unlink can't slip in between,
+   * and so a dedup repair never sees a manifest without its refs.
+   * Chunks stored for a manifest that isn't saved are left unreferenced:
+   * another handle may have stored the same bytes and not saved yet.
+   */
+  char **dropped = NULL;
+  size_t dropped_count = 0;
+  pthread_mutex_lock(&s->open_lock);
+  int changed = h->dirty && !handle_matches_orig(h);
+  if (rc == 0 && changed && !h->of->unlinked) {
+    if (save_manifest_atomic(h->of->meta_path, h->size, h->hashes, h->chunk_count) != 0) {
       /* fail safe: don’t touch refs if we couldn’t persist manifest */
     } else {
       (void)apply_ref_deltas_from_manifests(s,
                                             h->orig_hashes, h->orig_chunk_count,
                                             h->hashes,      h->chunk_count);
+      if (h->of->times_set) {
+        (void)utimensat(AT_FDCWD, h->of->meta_path, h->of->times, AT_SYMLINK_NOFOLLOW);
+        h->of->times_set = 0;
+      }
     }
-    h->dirty = 0;
   }
+  h->dirty = 0;
+
+  /* Last close of an unlinked file: release what its manifest referenced. */
+  open_file_put_locked(s, h->of, h, &dropped, &dropped_count);
+  if (dropped) {
+    (void)apply_ref_deltas_from_manifests(s, dropped, dropped_count, NULL, 0);
+    free_hashes(dropped, dropped_count);
+  }
+  pthread_mutex_unlock(&s->open_lock);
 
   /* cleanup */
+  free_hashes(h->trunc_hashes, h->trunc_count);
   free_hashes(h->orig_hashes, h->orig_chunk_count);
   free_hashes(h->hashes, h->chunk_count);
   free(h);
-  return 0;
+  return rc;
 }
 
 static int jnk_unlink(const char *path) {
@@ -1293,18 +2528,45 @@ static int jnk_unlink(const char *path) {
 
   if (!file_exists(metap)) return -ENOENT;
 
-  /* Load manifest and decrement refs for all hashes */
+  /* Still open somewhere: remove the name now, keep the chunks until the
+   * last release() so open descriptors keep reading what they had.
+   */
+  pthread_mutex_lock(&s->open_lock);
+  jnk_open_file_t *of = open_file_find_locked(s, metap);
+  if (of) {
+    size_t sz = 0;
+    if (load_manifest(metap, &sz, &of->disk_hashes, &of->disk_count) != 0) {
+      of->disk_hashes = NULL;
+      of->disk_count = 0;
+    }
+    if (unlink(metap) != 0) {
+      int err = errno;
+      free_hashes(of->disk_hashes, of->disk_count);
+      of->disk_hashes = NULL;
+      of->disk_count = 0;
+      pthread_mutex_unlock(&s->open_lock);
+      return -err;
+    }
+    of->unlinked = 1;
+    pthread_mutex_unlock(&s->open_lock);
+    return 0;
+  }
+
+  /* Not open: remove the manifest, then drop the refs it held. Still under
+   * open_lock, so no open, save or dedup repair runs in between.
+   */
   size_t sz = 0, cnt = 0;
   char **hashes = NULL;
-  if (load_manifest(metap, &sz, &hashes, &cnt) == 0) {
-    /* Apply delta: old=hashes -> new=empty */
-    (void)apply_ref_deltas_from_manifests(s, hashes, cnt, NULL, 0);
+  (void)load_manifest(metap, &sz, &hashes, &cnt); /* unreadable: nothing to drop */
+  if (unlink(metap) != 0) {
+    int err = errno;
+    pthread_mutex_unlock(&s->open_lock);
     free_hashes(hashes, cnt);
+    return -err;
   }
-
-  /* Remove meta file */
-  if (unlink(metap) != 0) return -errno;
-
+  (void)apply_ref_deltas_from_manifests(s, hashes, cnt, NULL, 0);
+  pthread_mutex_unlock(&s->open_lock);
+  free_hashes(hashes, cnt);
   return 0;
 }
 
@@ -1314,29 +2576,101 @@ static int jnk_rmdir(const char *path) {
     char realp[MAX_PATH_LEN], metap[MAX_PATH_LEN];
     if (make_real_and_meta(s->backing_dir, path, realp, metap) != 0) return -EINVAL;
     (void)metap;
+    if (rmdir(realp) == 0) return 0;
+    int err = errno;
+    if (err != ENOTEMPTY && err != EEXIST) return -err;
+
+    /* A create policy alone doesn't keep a directory from being removed. */
+    DIR *d = opendir(realp);
+    if (!d) return -err;
+    int only_policy = 1;
+    struct dirent *de;
+    while ((de = readdir(d)) != NULL) {
+        if (strcmp(de->d_name, ".") == 0 || strcmp(de->d_name, "..") == 0) continue;
+        if (strcmp(de->d_name, JUNKNAS_CREATE_POLICY_FILE) != 0) only_policy = 0;
+    }
+    closedir(d);
+    char policy_path[MAX_PATH_LEN];
+    if (!only_policy || policy_file_path(realp, policy_path) != 0) return -err;
+    if (unlink(policy_path) != 0 && errno != ENOENT) return -errno;
     if (rmdir(realp) != 0) return -errno;
     return 0;
 }
 
+#ifndef RENAME_NOREPLACE
+#define RENAME_NOREPLACE (1 << 0)
+#endif
+#ifndef RENAME_EXCHANGE
+#define RENAME_EXCHANGE  (1 << 1)
+#endif
+
 static int jnk_rename(const char *from, const char *to, unsigned int flags) {
-    (void)flags;
     jnk_fuse_state_t *s = get_state();
     char fr[MAX_PATH_LEN], fm[MAX_PATH_LEN];
     char tr[MAX_PATH_LEN], tm[MAX_PATH_LEN];
 
+    if (flags & RENAME_EXCHANGE) return -EINVAL;
     if (make_real_and_meta(s->backing_dir, from, fr, fm) != 0) return -EINVAL;
     if (make_real_and_meta(s->backing_dir, to,   tr, tm) != 0) return -EINVAL;
 
     if (dir_exists(fr)) {
+        if (file_exists(tm)) return -ENOTDIR;
+        if ((flags & RENAME_NOREPLACE) && dir_exists(tr)) return -EEXIST;
         if (ensure_parent_dirs(tr) != 0) return -EIO;
-        if (rename(fr, tr) != 0) return -errno;
-        return 0;
+        /* Under open_lock so a dedup scan can't miss or double count the tree. */
+        pthread_mutex_lock(&s->open_lock);
+        int rc = (rename(fr, tr) == 0) ? 0 : -errno;
+        if (rc == 0) open_files_renamed_locked(s, fr, tr, 1);
+        pthread_mutex_unlock(&s->open_lock);
+        return rc;
     }
 
     if (!file_exists(fm)) return -ENOENT;
+    if (dir_exists(tr)) return -EISDIR;
+    if (strcmp(fm, tm) == 0) return 0;
+    if ((flags & RENAME_NOREPLACE) && file_exists(tm)) return -EEXIST;
 
     if (ensure_parent_dirs(tm) != 0) return -EIO;
-    if (rename(fm, tm) != 0) return -errno;
+
+    /* Replacing a file (editors save by writing a temp file and renaming it
+     * over the original): the old manifest's chunks lose their reference,
+     * right away or, if it's still open, at its last release like unlink.
+     */
+    char **replaced = NULL;
+    size_t replaced_count = 0;
+    pthread_mutex_lock(&s->open_lock);
+    jnk_open_file_t *dest = open_file_find_locked(s, tm);
+    if (file_exists(tm)) {
+        size_t sz = 0;
+        if (load_manifest(tm, &sz, &replaced, &replaced_count) != 0) {
+            replaced = NULL;
+            replaced_count = 0;
+            /* Its last release would have nothing to drop: keep the file. */
+            if (dest) {
+                pthread_mutex_unlock(&s->open_lock);
+                return -EIO;
+            }
+        }
+    }
+    if (rename(fm, tm) != 0) {
+        int err = errno;
+        pthread_mutex_unlock(&s->open_lock);
+        free_hashes(replaced, replaced_count);
+        return -err;
+    }
+    if (dest) {
+        dest->disk_hashes = replaced;
+        dest->disk_count = replaced_count;
+        dest->unlinked = 1;
+        replaced = NULL;
+        replaced_count = 0;
+    }
+    open_files_renamed_locked(s, fm, tm, 0);
+    if (replaced) {
+        (void)apply_ref_deltas_from_manifests(s, replaced, replaced_count, NULL, 0);
+        free_hashes(replaced, replaced_count);
+    }
+    pthread_mutex_unlock(&s->open_lock);
     return 0;
 }
 
@@ -1348,13 +2682,26 @@ static int jnk_statfs(const char *path, struct statvfs *st) {
 
     if (statvfs(realp, st) != 0) return -errno;
 
+    /* Hide the drive reserve from free space */
+    uint64_t reserve = junknas_drive_reserve_bytes(s->drive_reserve,
+                                                   (uint64_t)st->f_blocks * (uint64_t)st->f_frsize);
+    if (reserve != 0 && st->f_frsize != 0) {
+        fsblkcnt_t rb = (fsblkcnt_t)((reserve + st->f_frsize - 1) / st->f_frsize);
+        st->f_bavail = (st->f_bavail > rb) ? st->f_bavail - rb : 0;
+        st->f_bfree  = (st->f_bfree > rb) ? st->f_bfree - rb : 0;
+    }
+    uint64_t disk_free = (uint64_t)st->f_bavail * (uint64_t)st->f_frsize;
+
     /* Enforce quota view if set */
     if (s->quota_bytes != 0) {
         int64_t used = store_usage_bytes(s);
         if (used < 0) return 0;
 
+        quota_update_warning(s, (uint64_t)used);
+
         uint64_t quota = (uint64_t)s->quota_bytes;
         uint64_t freeb = (quota > (uint64_t)used) ? (quota - (uint64_t)used) : 0;
+        if (freeb > disk_free) freeb = disk_free;
 
         /* Present quota-limited filesystem stats */
         st->f_bsize  = 4096;
@@ -1367,25 +2714,219 @@ static int jnk_statfs(const char *path, struct statvfs *st) {
     return 0;
 }
 
+/* ---------------------------- Timed wrappers ---------------------------
+ * Record per-op latency for /metrics without touching the op bodies, and log
+ * operations that blow their budget with where the time went.
+ */
+
+/* Default slow-op budgets in ms (read: per MiB requested); JUNKNAS_SLOW_<OP>_MS overrides. */
+static const unsigned int jnk_default_budget_ms[JUNKNAS_FUSE_OP_COUNT] = {
+    [JUNKNAS_FUSE_OP_GETATTR] = 50,
+    [JUNKNAS_FUSE_OP_READDIR] = 200,
+    [JUNKNAS_FUSE_OP_CREATE]  = 200,
+    [JUNKNAS_FUSE_OP_OPEN]    = 200,
+    [JUNKNAS_FUSE_OP_READ]    = 500,
+    [JUNKNAS_FUSE_OP_WRITE]   = 200,
+    [JUNKNAS_FUSE_OP_RELEASE] = 1000,
+    [JUNKNAS_FUSE_OP_UNLINK]  = 200,
+    [JUNKNAS_FUSE_OP_RENAME]  = 200,
+};
+
+static void load_slow_op_budgets(jnk_fuse_state_t *s) {
+    for (int op = 0; op < JUNKNAS_FUSE_OP_COUNT; op++) {
+        uint64_t ms = jnk_default_budget_ms[op];
+        char name[48];
+        snprintf(name, sizeof(name), "JUNKNAS_SLOW_%s_MS", junknas_stats_fuse_op_name((junknas_fuse_op_t)op));
+        for (char *c = name; *c; c++) *c = (char)toupper((unsigned char)*c);
+        const char *env = getenv(name);
+        if (env && env[0] != '\0') {
+            char *end = NULL;
+            unsigned long long v = strtoull(env, &end, 10);
+            if (end != env && *end == '\0') ms = v;
+        }
+        s->slow_budget_usec[op] = ms * 1000ULL;
+    }
+}
+
+static void observe_timed_op(junknas_fuse_op_t op, const char *path, size_t bytes, uint64_t usec) {
+    junknas_stats_observe_fuse_op(op, usec);
+
+    const jnk_fuse_state_t *s = get_state();
+    uint64_t budget = s->slow_budget_usec[op];
+    if (budget == 0) return;
+    if (op == JUNKNAS_FUSE_OP_READ) {
+        uint64_t mib = ((uint64_t)bytes + (1u << 20) - 1) >> 20;
+        budget *= (mib > 0) ? mib : 1;
+    }
+    if (usec <= budget) return;
+
+    junknas_stats_count_slow_fuse_op(op);
+    fprintf(stderr, "fuse: slow %s %s: %llu ms (budget %llu ms; disk %llu ms, mesh %llu ms, hash %llu ms)\n",
+            junknas_stats_fuse_op_name(op), path ? path : "?",
+            (unsigned long long)(usec / 1000), (unsigned long long)(budget / 1000),
+            (unsigned long long)(t_op_timing.disk_usec / 1000),
+            (unsigned long long)(t_op_timing.mesh_usec / 1000),
+            (unsigned long long)(t_op_timing.hash_usec / 1000));
+}
+
+#define JNK_TIMED(op, path, bytes, call)                                 \
+    do {                                                                 \
+        memset(&t_op_timing, 0, sizeof(t_op_timing));                    \
+        uint64_t t0_ = junknas_stats_now_usec();                         \
+        int rc_ = (call);                                                \
+        observe_timed_op((op), (path), (bytes), junknas_stats_now_usec() - t0_); \
+        return rc_;                                                      \
+    } while (0)
+
+static int jnk_timed_getattr(const char *path, struct stat *st, struct fuse_file_info *fi) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_GETATTR, path, 0, jnk_getattr(path, st, fi));
+}
+
+static int jnk_timed_readdir(const char *path, void *buf, fuse_fill_dir_t filler,
+                             off_t off, struct fuse_file_info *fi, enum fuse_readdir_flags flags) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_READDIR, path, 0, jnk_readdir(path, buf, filler, off, fi, flags));
+}
+
+static int jnk_timed_create(const char *path, mode_t mode, struct fuse_file_info *fi) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_CREATE, path, 0, jnk_create(path, mode, fi));
+}
+
+static int jnk_timed_open(const char *path, struct fuse_file_info *fi) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_OPEN, path, 0, jnk_open(path, fi));
+}
+
+static int jnk_timed_read(const char *path, char *buf, size_t size, off_t off, struct fuse_file_info *fi) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_READ, path, size, jnk_read(path, buf, size, off, fi));
+}
+
+static int jnk_timed_write(const char *path, const char *buf, size_t size, off_t off, struct fuse_file_info *fi) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_WRITE, path, size, jnk_write(path, buf, size, off, fi));
+}
+
+static int jnk_timed_release(const char *path, struct fuse_file_info *fi) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_RELEASE, path, 0, jnk_release(path, fi));
+}
+
+static int jnk_timed_unlink(const char *path) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_UNLINK, path, 0, jnk_unlink(path));
+}
+
+static int jnk_timed_rename(const char *from, const char *to, unsigned int flags) {
+    JNK_TIMED(JUNKNAS_FUSE_OP_RENAME, from, 0, jnk_rename(from, to, flags));
+}
+
 /* Minimal ops table (avoid extra surfaces) */
 static const struct fuse_operations jnk_ops = {
-    .getattr  = jnk_getattr,
-    .readdir  = jnk_readdir,
+    .getattr  = jnk_timed_getattr,
+    .readdir  = jnk_timed_readdir,
     .mkdir    = jnk_mkdir,
-    .create   = jnk_create,
-    .open     = jnk_open,
-    .read     = jnk_read,
-    .write    = jnk_write,
+    .mknod    = jnk_mknod,
+    .create   = jnk_timed_create,
+    .open     = jnk_timed_open,
+    .read     = jnk_timed_read,
+    .write    = jnk_timed_write,
     .truncate = jnk_truncate,
-    .release  = jnk_release,
-    .unlink   = jnk_unlink,
+    .release  = jnk_timed_release,
+    .unlink   = jnk_timed_unlink,
     .rmdir    = jnk_rmdir,
-    .rename   = jnk_rename,
+    .rename   = jnk_timed_rename,
     .statfs   = jnk_statfs,
+    .utimens  = jnk_utimens,
+    .chmod    = jnk_chmod,
+    .chown    = jnk_chown,
+    .getxattr = jnk_getxattr,
+    .setxattr = jnk_setxattr,
+    .listxattr = jnk_listxattr,
+    .removexattr = jnk_removexattr,
 };
 
 /* ---------------------------- Entry Point ------------------------------ */
 
+/* Pass a kernel cache TTL (seconds, 0 = no caching) to libfuse as
+ * "-o<opt>=<secs>": the command-line value if given (flag_secs >= 0),
+ * else the env var. Unset or malformed values keep libfuse's 1s default.
+ */
+static int add_ttl_option(const junknas_config_t *cfg, struct fuse_args *args,
+                          double flag_secs, const char *env_name, const char *opt) {
+    double secs = flag_secs;
+    if (secs < 0) {
+        const char *value = getenv(env_name);
+        if (!value || value[0] == '\0') return 0;
+        char *end = NULL;
+        secs = strtod(value, &end);
+        if (end == value || *end != '\0' || secs < 0) {
+            fprintf(stderr, "fuse: ignoring %s=%s (want seconds >= 0)\n", env_name, value);
+            return 0;
+        }
+    }
+    char arg[64];
+    snprintf(arg, sizeof(arg), "-o%s=%g", opt, secs);
+    fuse_log_verbose(cfg, "fuse: %s %gs", opt, secs);
+    return fuse_opt_add_arg(args, arg);
+}
+
+/* The mounted filesystem, for callers outside FUSE requests (web dedup
+ * repair). mounted_lock keeps it alive while in use.
+ */
+static pthread_mutex_t mounted_lock = PTHREAD_MUTEX_INITIALIZER;
+static jnk_fuse_state_t *mounted_state;
+
+int junknas_fuse_with_refs_locked(int (*fn)(void *ctx, char **open_hashes, size_t open_count),
+                                  void *ctx) {
+    if (!fn) return -EINVAL;
+    pthread_mutex_lock(&mounted_lock);
+    jnk_fuse_state_t *s = mounted_state;
+    if (!s) {
+        pthread_mutex_unlock(&mounted_lock);
+        return -EAGAIN;
+    }
+
+    pthread_mutex_lock(&s->open_lock);
+    size_t n = 0;
+    for (jnk_open_file_t *of = s->open_files; of; of = of->next) {
+        if (of->unlinked) n += of->disk_count;
+    }
+    char **open_hashes = (char **)calloc(n ? n : 1, sizeof(char *));
+    int rc = -ENOMEM;
+    if (open_hashes) {
+        size_t k = 0;
+        for (jnk_open_file_t *of = s->open_files; of; of = of->next) {
+            if (!of->unlinked) continue;
+            for (size_t i = 0; i < of->disk_count; i++) open_hashes[k++] = of->disk_hashes[i];
+        }
+        rc = fn(ctx, open_hashes, n);
+        free(open_hashes);
+    }
+    pthread_mutex_unlock(&s->open_lock);
+    pthread_mutex_unlock(&mounted_lock);
+    return rc;
+}
+
+int junknas_fuse_store_dir_for_chunk(size_t len, char *out, size_t out_len) {
+    if (!out || out_len == 0) return -EINVAL;
+    pthread_mutex_lock(&mounted_lock);
+    jnk_fuse_state_t *s = mounted_state;
+    int rc = -EAGAIN;
+    size_t i = 0;
+    if (s) rc = store_pick_dir(s, len, &i);
+    if (rc == 0 && snprintf(out, out_len, "%s", s->store_dirs[i]) >= (int)out_len) rc = -EIO;
+    pthread_mutex_unlock(&mounted_lock);
+    return rc;
+}
+
+void junknas_fuse_store_chunk_added(const char *dir, size_t len) {
+    if (!dir) return;
+    pthread_mutex_lock(&mounted_lock);
+    jnk_fuse_state_t *s = mounted_state;
+    for (size_t i = 0; s && i < s->store_dir_count; i++) {
+        if (strcmp(s->store_dirs[i], dir) == 0) {
+            store_used_add(s, i, (int64_t)len);
+            break;
+        }
+    }
+    pthread_mutex_unlock(&mounted_lock);
+}
+
 int junknas_fuse_run(const junknas_config_t *cfg,
                      junknas_mesh_t *mesh,
                      int argc,
@@ -1404,11 +2945,30 @@ int junknas_fuse_run(const junknas_config_t *cfg,
     for (size_t i = 0; i < state->store_dir_count; i++) {
         const char *dir = (cfg->data_dir_count > 0) ? cfg->data_dirs[i] : cfg->data_dir;
         strncpy(state->store_dirs[i], dir, sizeof(state->store_dirs[i]) - 1);
+        state->store_caps[i] = cfg->data_dir_caps[i];
     }
     state->store_rr_next = 0;
-    state->verbose = cfg->verbose;
+    state->config = cfg;
     state->quota_bytes = cfg->max_storage_bytes; /* 0 = unlimited */
+    strncpy(state->drive_reserve, cfg->drive_reserve, sizeof(state->drive_reserve) - 1);
+    state->quota_warn_percent = cfg->quota_warn_percent;
+    state->hard_reads = (strcmp(cfg->read_mode, READ_MODE_HARD) == 0);
+    state->fsync_batch = 1;
+    state->fsync_batch_usec = JNK_FSYNC_BATCH_DEFAULT_MS * 1000ULL;
+    const char *batch_env = getenv("JUNKNAS_FSYNC_BATCH");
+    if (batch_env) {
+        long n = strtol(batch_env, NULL, 10);
+        if (n > JNK_FSYNC_BATCH_MAX) n = JNK_FSYNC_BATCH_MAX;
+        if (n > 1) state->fsync_batch = (size_t)n;
+    }
+    const char *batch_ms_env = getenv("JUNKNAS_FSYNC_BATCH_MS");
+    if (batch_ms_env) {
+        long ms = strtol(batch_ms_env, NULL, 10);
+        if (ms >= 0) state->fsync_batch_usec = (uint64_t)ms * 1000ULL;
+    }
+    load_slow_op_budgets(state);
     state->mesh = mesh;
+    pthread_mutex_init(&state->open_lock, NULL);
 
     if (ensure_dir_recursive(state->backing_dir) != 0) {
         fuse_log_verbose(cfg, "fuse: failed to create backing dir %s: %s",
@@ -1435,6 +2995,10 @@ int junknas_fuse_run(const junknas_config_t *cfg,
     }
     fuse_log_verbose(cfg, "fuse: store layout ensured");
 
+    for (size_t i = 0; i < state->store_dir_count; i++) {
+        state->store_used[i] = store_dir_usage_bytes(state->store_dirs[i]);
+    }
+
     /* Correct FUSE3 args: build from scratch */
     struct fuse_args args = FUSE_ARGS_INIT(0, NULL);
     (void)argc;
@@ -1444,13 +3008,41 @@ int junknas_fuse_run(const junknas_config_t *cfg,
     /* Foreground for dev (you can remove later) */
     fuse_opt_add_arg(&args, "-f");
 
+    /* Let the kernel check the stored mode and owner on every access. */
+    if (fuse_opt_add_arg(&args, "-odefault_permissions") != 0) { fuse_opt_free_args(&args); free(state); return -1; }
+
+    /* Other users (smbd serving the mount as each client's user). Needs
+     * root or user_allow_other in /etc/fuse.conf.
+     */
+    const char *allow_other = getenv("JUNKNAS_ALLOW_OTHER");
+    if (allow_other && strcmp(allow_other, "1") == 0) {
+        fuse_log_verbose(cfg, "fuse: allow_other");
+        if (fuse_opt_add_arg(&args, "-oallow_other") != 0) { fuse_opt_free_args(&args); free(state); return -1; }
+    }
+
+    if (add_ttl_option(cfg, &args, cfg->attr_ttl, "JUNKNAS_ATTR_TTL", "attr_timeout") != 0 ||
+        add_ttl_option(cfg, &args, cfg->entry_ttl, "JUNKNAS_ENTRY_TTL", "entry_timeout") != 0) {
+        fuse_opt_free_args(&args); free(state); return -1;
+    }
+
     /* Mountpoint from config */
     if (fuse_opt_add_arg(&args, cfg->mount_point) != 0) { fuse_opt_free_args(&args); free(state); return -1; }
 
+    scrub_start(state);
+
+    pthread_mutex_lock(&mounted_lock);
+    mounted_state = state;
+    pthread_mutex_unlock(&mounted_lock);
+
     fuse_log_verbose(cfg, "fuse: entering fuse_main");
     int rc = fuse_main(args.argc, args.argv, &jnk_ops, state);
 
+    pthread_mutex_lock(&mounted_lock);
+    mounted_state = NULL;
+    pthread_mutex_unlock(&mounted_lock);
+    scrub_stop(state);
     fuse_opt_free_args(&args);
+    pthread_mutex_destroy(&state->open_lock);
     free(state);
     return (rc == 0) ? 0 : -1;
 }
@@ -5,7 +5,7 @@
  * It does not start WireGuard, web UI, or mesh.
  *
  * Usage:
- *   ./junknas_fuse <config.json>
+ *   ./junknas_fuse [--attr-ttl <secs>] [--entry-ttl <secs>] <config.json>
  *   ./junknas_fuse <config.json> bootstrap-peers list
  *   ./junknas_fuse <config.json> bootstrap-peers add <ip:port>
  *   ./junknas_fuse <config.json> bootstrap-peers delete <index>
@@ -15,12 +15,18 @@
  *   ./junknas_fuse config.test.json
  */
 
+#include <errno.h>
+#include <fcntl.h>
 #include <limits.h>
 #include <stdarg.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <dirent.h>
+#include <sys/file.h>
+#include <sys/stat.h>
 #include <time.h>
+#include <unistd.h>
 #include "config.h"
 #include "fuse_fs.h"
 #include "mesh.h"
@@ -29,7 +35,7 @@
 static void print_usage(const char *argv0) {
     fprintf(stderr,
             "Usage:\n"
-            "  %s [-v|--verbose] <config.json>\n"
+            "  %s [-v|--verbose] [--attr-ttl <secs>] [--entry-ttl <secs>] <config.json>\n"
             "  %s [-v|--verbose] <config.json> bootstrap-peers list\n"
             "  %s [-v|--verbose] <config.json> bootstrap-peers add <host:port>\n"
             "  %s [-v|--verbose] <config.json> bootstrap-peers delete <index>\n"
@@ -56,6 +62,28 @@ static int parse_uint_index(const char *text, int *out_index) {
     return 0;
 }
 
+/* Kernel cache TTL in seconds, fractions allowed. */
+static int parse_ttl(const char *text, double *out_secs) {
+    if (!text || !out_secs) return -1;
+    char *end = NULL;
+    double secs = strtod(text, &end);
+    if (end == text || *end != '\0' || secs < 0) return -1;
+    *out_secs = secs;
+    return 0;
+}
+
+/* "--name <value>" or "--name=<value>" at argv[*i]; advances *i past a
+ * separate value. Returns the value, or NULL if argv[*i] is another option.
+ */
+static const char *option_value(int argc, char **argv, int *i, const char *name) {
+    size_t len = strlen(name);
+    if (strncmp(argv[*i], name, len) != 0) return NULL;
+    if (argv[*i][len] == '=') return argv[*i] + len + 1;
+    if (argv[*i][len] != '\0' || *i + 1 >= argc) return NULL;
+    (*i)++;
+    return argv[*i];
+}
+
 static int validate_host(const char *host) {
     if (!host || host[0] == '\0') return -1;
     int has_alpha = 0;
@@ -225,11 +253,127 @@ static int handle_bootstrap_peers_command(junknas_config_t *cfg,
     return 0;
 }
 
+/* Catch the usual first-run mount failures before libfuse reports them
+ * as a bare errno: a missing mount point (created only when
+ * JUNKNAS_CREATE_MOUNT_POINT=1), something that isn't a directory, a stale
+ * FUSE mount left by a crashed daemon, or one we can't write to. A
+ * non-empty directory is only a warning. Returns 0 if it's safe to mount.
+ */
+static int check_mount_point(const junknas_config_t *cfg) {
+    const char *mp = cfg->mount_point;
+    struct stat st;
+    if (stat(mp, &st) != 0) {
+        if (errno == ENOTCONN) {
+            fprintf(stderr, "Mount point %s is a stale FUSE mount (\"Transport endpoint is not connected\"); "
+                            "run: fusermount3 -u %s\n", mp, mp);
+            return -1;
+        }
+        if (errno != ENOENT) {
+            fprintf(stderr, "Cannot access mount point %s: %s\n", mp, strerror(errno));
+            return -1;
+        }
+        const char *create = getenv("JUNKNAS_CREATE_MOUNT_POINT");
+        if (!create || strcmp(create, "1") != 0) {
+            fprintf(stderr, "Mount point %s does not exist; create it (mkdir -p %s) "
+                            "or set JUNKNAS_CREATE_MOUNT_POINT=1.\n", mp, mp);
+            return -1;
+        }
+        if (mkdir(mp, 0755) != 0) {
+            fprintf(stderr, "Failed to create mount point %s: %s\n", mp, strerror(errno));
+            return -1;
+        }
+        fprintf(stderr, "Created mount point %s\n", mp);
+        return 0;
+    }
+    if (!S_ISDIR(st.st_mode)) {
+        fprintf(stderr, "Mount point %s is not a directory.\n", mp);
+        return -1;
+    }
+    if (access(mp, W_OK | X_OK) != 0) {
+        fprintf(stderr, "Mount point %s is not writable by this user: %s\n", mp, strerror(errno));
+        return -1;
+    }
+
+    DIR *d = opendir(mp);
+    if (d) {
+        struct dirent *ent;
+        while ((ent = readdir(d)) != NULL) {
+            if (strcmp(ent->d_name, ".") == 0 || strcmp(ent->d_name, "..") == 0) continue;
+            fprintf(stderr, "Warning: mount point %s is not empty; its contents are hidden while junkNAS is mounted.\n",
+                    mp);
+            break;
+        }
+        closedir(d);
+    }
+    return 0;
+}
+
+/* One daemon per data dir: two would both write the same chunk store and
+ * refcounts. The lock is an flock on <data_dir>/.jnk/mount.lock, so it goes
+ * away with its process; the file records the PID and mount point of the
+ * holder for the error message. Returns the fd to keep open, or -1.
+ */
+static int acquire_instance_lock(const junknas_config_t *cfg) {
+    char dir[PATH_MAX];
+    char path[PATH_MAX];
+    if (snprintf(dir, sizeof(dir), "%s/.jnk", cfg->data_dir) >= (int)sizeof(dir) ||
+        snprintf(path, sizeof(path), "%s/mount.lock", dir) >= (int)sizeof(path)) {
+        fprintf(stderr, "Data dir path too long: %s\n", cfg->data_dir);
+        return -1;
+    }
+    (void)mkdir(cfg->data_dir, 0755);
+    (void)mkdir(dir, 0755);
+
+    int fd = open(path, O_RDWR | O_CREAT, 0644);
+    if (fd < 0) {
+        fprintf(stderr, "Failed to open %s: %s\n", path, strerror(errno));
+        return -1;
+    }
+    if (flock(fd, LOCK_EX | LOCK_NB) != 0) {
+        char holder[PATH_MAX + 64] = "";
+        ssize_t n = pread(fd, holder, sizeof(holder) - 1, 0);
+        if (n > 0) holder[n] = '\0';
+        long pid = 0;
+        char mount[PATH_MAX] = "?";
+        (void)sscanf(holder, "pid %ld mount %4095s", &pid, mount);
+        fprintf(stderr, "junkNAS is already running on %s (pid %ld, mounted at %s); stop it first.\n",
+                cfg->data_dir, pid, mount);
+        close(fd);
+        return -1;
+    }
+
+    char info[PATH_MAX + 64];
+    int len = snprintf(info, sizeof(info), "pid %ld mount %s\n", (long)getpid(), cfg->mount_point);
+    if (len > 0 && ftruncate(fd, 0) == 0) {
+        ssize_t w = pwrite(fd, info, (size_t)len, 0);
+        (void)w;
+    }
+    return fd;
+}
+
 int main(int argc, char **argv) {
     int startup_verbose = 0;
+    double attr_ttl = -1, entry_ttl = -1;
     int arg_start = 1;
-    if (argc > 1 && (strcmp(argv[1], "-v") == 0 || strcmp(argv[1], "--verbose") == 0)) {
-        startup_verbose = 1;
+    while (arg_start < argc && argv[arg_start][0] == '-') {
+        const char *arg = argv[arg_start];
+        const char *value = NULL;
+        double *ttl = NULL;
+        if (strcmp(arg, "-v") == 0 || strcmp(arg, "--verbose") == 0) {
+            startup_verbose = 1;
+        } else if ((value = option_value(argc, argv, &arg_start, "--attr-ttl")) != NULL) {
+            ttl = &attr_ttl;
+        } else if ((value = option_value(argc, argv, &arg_start, "--entry-ttl")) != NULL) {
+            ttl = &entry_ttl;
+        } else {
+            fprintf(stderr, "Unknown option '%s'.\n", arg);
+            print_usage(argv[0]);
+            return 2;
+        }
+        if (ttl && parse_ttl(value, ttl) != 0) {
+            fprintf(stderr, "Invalid %s '%s'. Use seconds >= 0.\n", arg, value);
+            return 2;
+        }
         arg_start++;
     }
 
@@ -250,6 +394,9 @@ int main(int argc, char **argv) {
     if (startup_verbose) {
         cfg.verbose = 1;
     }
+    /* Command-line TTLs win over JUNKNAS_ATTR_TTL / JUNKNAS_ENTRY_TTL. */
+    cfg.attr_ttl = attr_ttl;
+    cfg.entry_ttl = entry_ttl;
 
     log_verbose(cfg.verbose, "startup: config loaded from %s", config_path);
 
@@ -264,6 +411,11 @@ int main(int argc, char **argv) {
         return 1;
     }
 
+    if (check_mount_point(&cfg) != 0) return 1;
+
+    int lock_fd = acquire_instance_lock(&cfg);
+    if (lock_fd < 0) return 1;
+
     log_verbose(cfg.verbose, "startup: establishing mesh");
     junknas_mesh_t *mesh = junknas_mesh_start(&cfg);
     if (!mesh) {
@@ -289,6 +441,7 @@ int main(int argc, char **argv) {
 
     if (web) junknas_web_server_stop(web);
     if (mesh) junknas_mesh_stop(mesh);
+    close(lock_fd);
 
     return rc;
 }
@@ -3,6 +3,10 @@
  */
 
 #include "mesh.h"
+#include "events.h"
+#include "net.h"
+#include "sha256.h"
+#include "stats.h"
 
 #include <arpa/inet.h>
 #include <errno.h>
@@ -26,7 +30,14 @@
 
 #define MESH_MAX_PEERS   MAX_WG_PEERS
 #define MESH_CONNECT_TIMEOUT_SEC 1
+/* Per-address connect and per-send/recv limits for peer HTTP. A soft read
+ * tries each peer up to 3 times with about 2 s of backoff in between, so
+ * one unresponsive peer costs at most a few seconds, not the TCP default.
+ */
+#define MESH_DIAL_TIMEOUT_MS 500
+#define MESH_IO_TIMEOUT_MS 1000
 #define MESH_SYNC_INTERVAL_SEC 5
+#define MESH_REQUEST_ID_LEN 17
 
 static void mesh_log_verbose(const junknas_config_t *config, const char *fmt, ...) {
     if (!config || !config->verbose) return;
@@ -133,9 +144,16 @@ static int parse_endpoint(const char *endpoint, char *host, size_t host_len, uin
     const char *colon = strrchr(endpoint, ':');
     if (!colon || colon == endpoint || *(colon + 1) == '\0') return -1;
 
+    /* "[v6addr]:port" */
+    const char *hstart = endpoint;
     size_t hlen = (size_t)(colon - endpoint);
+    if (endpoint[0] == '[') {
+        if (hlen < 3 || *(colon - 1) != ']') return -1;
+        hstart = endpoint + 1;
+        hlen -= 2;
+    }
     if (hlen >= host_len) return -1;
-    memcpy(host, endpoint, hlen);
+    memcpy(host, hstart, hlen);
     host[hlen] = '\0';
 
     char *end = NULL;
@@ -145,48 +163,63 @@ static int parse_endpoint(const char *endpoint, char *host, size_t host_len, uin
     return 0;
 }
 
-static int mesh_peer_hostport(const junknas_wg_peer_t *peer,
-                              uint16_t default_web_port,
-                              char *host,
-                              size_t host_len,
-                              uint16_t *port) {
-    if (!peer || !host || !port) return -1;
-    if (peer->endpoint[0] != '\0') {
-        return parse_endpoint(peer->endpoint, host, host_len, port);
+int junknas_mesh_peer_candidates(const junknas_wg_peer_t *peer, uint16_t default_web_port,
+                                 junknas_mesh_candidate_t out[JUNKNAS_MESH_MAX_CANDIDATES]) {
+    if (!peer || !out) return 0;
+    int n = 0;
+    if (peer->wg_ip[0] != '\0') {
+        snprintf(out[n].host, sizeof(out[n].host), "%s", peer->wg_ip);
+        out[n].port = peer->web_port ? peer->web_port : default_web_port;
+        n++;
     }
-    if (peer->wg_ip[0] == '\0') return -1;
+    if (peer->endpoint[0] != '\0' &&
+        parse_endpoint(peer->endpoint, out[n].host, sizeof(out[n].host), &out[n].port) == 0) {
+        if (n == 0 || strcmp(out[0].host, out[n].host) != 0 || out[0].port != out[n].port) n++;
+    }
+    return n;
+}
+
+/* Where to send chunk traffic for a peer: its wg_ip, since nodes only serve
+ * /chunks on their overlay address. A peer with just a LAN endpoint, or
+ * with require_encrypted whose wg_ip isn't routed through WireGuard,
+ * returns 1 (blocked).
+ */
+static int mesh_chunk_peer_hostport(const junknas_wg_peer_t *peer,
+                                    uint16_t default_web_port,
+                                    int require_encrypted,
+                                    char *host,
+                                    size_t host_len,
+                                    uint16_t *port) {
+    if (!peer || !host || !port) return -1;
+    if (peer->wg_ip[0] == '\0') return 1;
+    if (require_encrypted && junknas_net_route_is_wireguard(peer->wg_ip) != 1) return 1;
     snprintf(host, host_len, "%s", peer->wg_ip);
     *port = peer->web_port ? peer->web_port : default_web_port;
     return 0;
 }
 
+junknas_mesh_link_security_t junknas_mesh_peer_security(const junknas_wg_peer_t *peer,
+                                                        int have_secret,
+                                                        int wg_routed) {
+    if (!peer) return JUNKNAS_MESH_LINK_PLAINTEXT;
+    if (peer->wg_ip[0] != '\0' && wg_routed) return JUNKNAS_MESH_LINK_ENCRYPTED;
+    return have_secret ? JUNKNAS_MESH_LINK_AUTHENTICATED : JUNKNAS_MESH_LINK_PLAINTEXT;
+}
+
+const char *junknas_mesh_link_security_name(junknas_mesh_link_security_t security) {
+    switch (security) {
+        case JUNKNAS_MESH_LINK_ENCRYPTED: return "encrypted";
+        case JUNKNAS_MESH_LINK_AUTHENTICATED: return "authenticated";
+        default: return "plaintext";
+    }
+}
+
 static int is_ipv4_address(const char *text) {
     if (!text || text[0] == '\0') return 0;
     struct in_addr addr;
     return inet_pton(AF_INET, text, &addr) == 1;
 }
 
-static int resolve_addr(const char *host, uint16_t port, int socktype,
-                        struct sockaddr_storage *out, socklen_t *out_len) {
-    if (!host || !out || !out_len) return -1;
-    char port_str[8];
-    snprintf(port_str, sizeof(port_str), "%u", port);
-
-    struct addrinfo hints;
-    memset(&hints, 0, sizeof(hints));
-    hints.ai_socktype = socktype;
-    hints.ai_family = AF_UNSPEC;
-
-    struct addrinfo *res = NULL;
-    if (getaddrinfo(host, port_str, &hints, &res) != 0) return -1;
-
-    if (!res) return -1;
-    memcpy(out, res->ai_addr, res->ai_addrlen);
-    *out_len = (socklen_t)res->ai_addrlen;
-    freeaddrinfo(res);
-    return 0;
-}
-
 static int fetch_public_ip(const junknas_config_t *config, char *out, size_t out_len) {
     if (!out || out_len == 0) return -1;
     out[0] = '\0';
@@ -275,43 +308,70 @@ static __attribute__((unused)) int mesh_refresh_public_endpoint(struct junknas_m
     return changed;
 }
 
-static char *http_request_body(const junknas_config_t *config, const char *host, uint16_t port,
-                               const char *request, const char *body, size_t body_len, int *out_status) {
-    struct sockaddr_storage addr;
-    socklen_t addr_len = 0;
-    if (resolve_addr(host, port, SOCK_STREAM, &addr, &addr_len) != 0) {
-        mesh_log_verbose(config, "mesh: http resolve failed for %s:%u", host, port);
-        return NULL;
-    }
+/* Stats key for traffic with host:port, as dialed. */
+static void mesh_traffic_key(const char *host, uint16_t port, char out[MAX_ENDPOINT_LEN]) {
+    snprintf(out, MAX_ENDPOINT_LEN, "%s:%u", host, port);
+}
 
-    int fd = socket(addr.ss_family, SOCK_STREAM, 0);
-    if (fd < 0) {
-        mesh_log_verbose(config, "mesh: http socket create failed for %s:%u", host, port);
-        return NULL;
+static void mesh_record_traffic(const char *host, uint16_t port, size_t sent, size_t received, int ok) {
+    char endpoint[MAX_ENDPOINT_LEN];
+    mesh_traffic_key(host, port, endpoint);
+    junknas_stats_record_peer(endpoint, sent, received, ok);
+}
+
+int junknas_mesh_peer_traffic(const junknas_wg_peer_t *peer, uint16_t default_web_port,
+                              junknas_peer_traffic_t *out) {
+    if (!peer || !out) return -1;
+    memset(out, 0, sizeof(*out));
+    junknas_mesh_candidate_t candidates[JUNKNAS_MESH_MAX_CANDIDATES];
+    int n = junknas_mesh_peer_candidates(peer, default_web_port, candidates);
+    int found = 0;
+    for (int i = 0; i < n; i++) {
+        char key[MAX_ENDPOINT_LEN];
+        junknas_peer_traffic_t t;
+        mesh_traffic_key(candidates[i].host, candidates[i].port, key);
+        if (junknas_stats_get_peer(key, &t) != 0) continue;
+        if (!found) snprintf(out->endpoint, sizeof(out->endpoint), "%s", key);
+        out->bytes_sent += t.bytes_sent;
+        out->bytes_received += t.bytes_received;
+        out->requests += t.requests;
+        out->failures += t.failures;
+        found = 1;
     }
+    return found ? 0 : -1;
+}
 
-    if (connect(fd, (struct sockaddr *)&addr, addr_len) != 0) {
-        mesh_log_verbose(config, "mesh: http connect failed for %s:%u", host, port);
-        close(fd);
+static char *http_request_body(const junknas_config_t *config, const char *host, uint16_t port,
+                               const char *request, const char *body, size_t body_len, int *out_status) {
+    int fd = junknas_net_connect(host, port, config ? config->endpoint_preference : NULL,
+                                 MESH_DIAL_TIMEOUT_MS, MESH_IO_TIMEOUT_MS);
+    if (fd < 0) {
+        mesh_log_verbose(config, "mesh: http %s failed for %s:%u",
+                         (fd == -1) ? "resolve" : "connect", host, port);
+        mesh_record_traffic(host, port, 0, 0, 0);
         return NULL;
     }
 
     size_t request_len = strlen(request);
     mesh_log_verbose(config, "mesh: http send request %s:%u (%zu bytes)", host, port, request_len);
-    if (send(fd, request, request_len, 0) < 0) {
+    if (junknas_net_send_all(fd, request, request_len) != 0) {
         mesh_log_verbose(config, "mesh: http send request failed for %s:%u", host, port);
         close(fd);
+        mesh_record_traffic(host, port, 0, 0, 0);
         return NULL;
     }
     if (body && body_len > 0) {
         mesh_log_verbose(config, "mesh: http send body %s:%u (%zu bytes)", host, port, body_len);
-        if (send(fd, body, body_len, 0) < 0) {
+        if (junknas_net_send_all(fd, body, body_len) != 0) {
             mesh_log_verbose(config, "mesh: http send body failed for %s:%u", host, port);
             close(fd);
+            mesh_record_traffic(host, port, request_len, 0, 0);
             return NULL;
         }
     }
 
+    size_t sent = request_len + ((body && body_len > 0) ? body_len : 0);
+    size_t received = 0;
     char buf[4096];
     char header_buf[8192 + 1];
     size_t header_used = 0;
@@ -323,6 +383,7 @@ static char *http_request_body(const junknas_config_t *config, const char *host,
     while (1) {
         ssize_t n = recv(fd, buf, sizeof(buf), 0);
         if (n <= 0) break;
+        received += (size_t)n;
         mesh_log_verbose(config, "mesh: http recv chunk %s:%u (%zd bytes)", host, port, n);
 
         if (!header_done) {
@@ -339,6 +400,16 @@ static char *http_request_body(const junknas_config_t *config, const char *host,
                     header_buf[i + 2] == '\r' && header_buf[i + 3] == '\n') {
                     header_end = header_buf + i + 4;
                     size_t header_len = i + 4;
+                    /* Take the body bytes before terminating the headers in place. */
+                    size_t body_part = header_used - header_len;
+                    if (body_part > 0) {
+                        char *new_out = realloc(out, out_len + body_part + 1);
+                        if (!new_out) break;
+                        out = new_out;
+                        memcpy(out + out_len, header_end, body_part);
+                        out_len += body_part;
+                        out[out_len] = '\0';
+                    }
                     if (header_len < sizeof(header_buf)) {
                         header_buf[header_len] = '\0';
                     } else {
@@ -350,15 +421,6 @@ static char *http_request_body(const junknas_config_t *config, const char *host,
                         (void)sscanf(header_buf, "HTTP/%*s %d", &status);
                     }
                     header_done = 1;
-                    size_t body_part = header_used - header_len;
-                    if (body_part > 0) {
-                        char *new_out = realloc(out, out_len + body_part + 1);
-                        if (!new_out) break;
-                        out = new_out;
-                        memcpy(out + out_len, header_end, body_part);
-                        out_len += body_part;
-                        out[out_len] = '\0';
-                    }
                     break;
                 }
             }
@@ -373,6 +435,7 @@ static char *http_request_body(const junknas_config_t *config, const char *host,
     }
 
     close(fd);
+    mesh_record_traffic(host, port, sent, received, status >= 200 && status < 300);
     if (out_status) *out_status = status;
     if (!out) {
         out = calloc(1, 1);
@@ -390,16 +453,13 @@ static int mesh_mount_points_contains(const junknas_config_t *config, const char
     return 0;
 }
 
-static int mesh_mount_points_equal(const junknas_config_t *config, cJSON *mounts) {
-    if (!config || !cJSON_IsArray(mounts)) return 0;
-
-    int n = cJSON_GetArraySize(mounts);
-    if (n != config->data_mount_point_count) return 0;
+static int mesh_mount_points_equal(const junknas_config_t *config,
+                                   char mounts[][MAX_PATH_LEN], int count) {
+    if (!config) return 0;
+    if (count != config->data_mount_point_count) return 0;
 
-    for (int i = 0; i < n; i++) {
-        cJSON *entry = cJSON_GetArrayItem(mounts, i);
-        if (!cJSON_IsString(entry) || !entry->valuestring) return 0;
-        if (strcmp(config->data_mount_points[i], entry->valuestring) != 0) return 0;
+    for (int i = 0; i < count; i++) {
+        if (strcmp(config->data_mount_points[i], mounts[i]) != 0) return 0;
     }
 
     return 1;
@@ -429,15 +489,18 @@ static int mesh_peer_from_json(cJSON *obj, junknas_wg_peer_t *peer) {
     junknas_wg_peer_t out = {0};
 
     cJSON *pub = cJSON_GetObjectItemCaseSensitive(obj, "public_key");
-    if (cJSON_IsString(pub) && pub->valuestring) {
+    if (cJSON_IsString(pub) && pub->valuestring && pub->valuestring[0] != '\0') {
+        if (!junknas_is_clean_string(pub->valuestring, sizeof(out.public_key))) return -1;
         snprintf(out.public_key, sizeof(out.public_key), "%s", pub->valuestring);
     }
     cJSON *endpoint = cJSON_GetObjectItemCaseSensitive(obj, "endpoint");
-    if (cJSON_IsString(endpoint) && endpoint->valuestring) {
+    if (cJSON_IsString(endpoint) && endpoint->valuestring && endpoint->valuestring[0] != '\0') {
+        if (!junknas_is_clean_string(endpoint->valuestring, sizeof(out.endpoint))) return -1;
         snprintf(out.endpoint, sizeof(out.endpoint), "%s", endpoint->valuestring);
     }
     cJSON *wg_ip = cJSON_GetObjectItemCaseSensitive(obj, "wg_ip");
-    if (cJSON_IsString(wg_ip) && wg_ip->valuestring) {
+    if (cJSON_IsString(wg_ip) && wg_ip->valuestring && wg_ip->valuestring[0] != '\0') {
+        if (!junknas_is_clean_string(wg_ip->valuestring, sizeof(out.wg_ip))) return -1;
         snprintf(out.wg_ip, sizeof(out.wg_ip), "%s", wg_ip->valuestring);
     }
     cJSON *keepalive = cJSON_GetObjectItemCaseSensitive(obj, "persistent_keepalive");
@@ -480,6 +543,8 @@ static int mesh_update_from_json(struct junknas_mesh *mesh, const char *payload)
     if (!root) return -1;
 
     int changed = 0;
+    int peers_rejected = 0;
+    int mounts_rejected = 0;
     junknas_config_t *config = mesh->config;
 
     junknas_wg_peer_t incoming[MESH_MAX_PEERS];
@@ -493,6 +558,8 @@ static int mesh_update_from_json(struct junknas_mesh *mesh, const char *payload)
             junknas_wg_peer_t peer = {0};
             if (mesh_peer_from_json(entry, &peer) == 0) {
                 incoming[incoming_count++] = peer;
+            } else {
+                peers_rejected++;
             }
         }
     }
@@ -502,9 +569,13 @@ static int mesh_update_from_json(struct junknas_mesh *mesh, const char *payload)
         junknas_wg_peer_t peer = {0};
         if (mesh_peer_from_json(self, &peer) == 0) {
             incoming[incoming_count++] = peer;
+        } else {
+            peers_rejected++;
         }
     }
 
+    peers_rejected += junknas_collapse_duplicate_peers(incoming, &incoming_count);
+
     uint64_t remote_updated = 0;
     cJSON *updated = cJSON_GetObjectItemCaseSensitive(root, "updated_at");
     if (cJSON_IsNumber(updated) && updated->valuedouble >= 0) {
@@ -558,15 +629,12 @@ static int mesh_update_from_json(struct junknas_mesh *mesh, const char *payload)
     if (remote_mounts_updated >= config->data_mount_points_updated_at) {
         cJSON *mounts = cJSON_GetObjectItemCaseSensitive(root, "mount_points");
         if (cJSON_IsArray(mounts)) {
-            int same = mesh_mount_points_equal(config, mounts);
-            if (!same) {
+            char clean[MAX_DATA_MOUNT_POINTS][MAX_PATH_LEN];
+            int clean_count = junknas_sanitize_mount_points(mounts, clean, MAX_DATA_MOUNT_POINTS, &mounts_rejected);
+            if (!mesh_mount_points_equal(config, clean, clean_count)) {
                 config->data_mount_point_count = 0;
-                int n = cJSON_GetArraySize(mounts);
-                for (int i = 0; i < n && config->data_mount_point_count < MAX_DATA_MOUNT_POINTS; i++) {
-                    cJSON *entry = cJSON_GetArrayItem(mounts, i);
-                    if (cJSON_IsString(entry) && entry->valuestring) {
-                        (void)junknas_config_add_data_mount_point(config, entry->valuestring);
-                    }
+                for (int i = 0; i < clean_count; i++) {
+                    (void)junknas_config_add_data_mount_point(config, clean[i]);
                 }
                 changed = 1;
             }
@@ -579,6 +647,12 @@ static int mesh_update_from_json(struct junknas_mesh *mesh, const char *payload)
 
     junknas_config_unlock(config);
     cJSON_Delete(root);
+    if (peers_rejected || mounts_rejected) {
+        junknas_stats_add(JUNKNAS_STAT_MESH_PEER_REJECTED, (uint64_t)peers_rejected);
+        junknas_stats_add(JUNKNAS_STAT_MESH_MOUNT_REJECTED, (uint64_t)mounts_rejected);
+        mesh_log_verbose(config, "mesh: dropped %d malformed peer(s) and %d mount point(s) from sync reply",
+                         peers_rejected, mounts_rejected);
+    }
     return changed;
 }
 
@@ -705,6 +779,19 @@ static void mesh_refresh_active(struct junknas_mesh *mesh) {
     pthread_mutex_unlock(&mesh->lock);
 }
 
+static void mesh_publish_peer_status(const char *kind, const char *endpoint, int status) {
+    cJSON *obj = cJSON_CreateObject();
+    if (!obj) return;
+    cJSON_AddStringToObject(obj, "kind", kind);
+    cJSON_AddStringToObject(obj, "endpoint", endpoint);
+    cJSON_AddNumberToObject(obj, "status", status);
+    char *text = cJSON_PrintUnformatted(obj);
+    cJSON_Delete(obj);
+    if (!text) return;
+    junknas_events_publish("peer_status", text);
+    free(text);
+}
+
 static void *mesh_listener_thread(void *arg) {
     struct junknas_mesh *mesh = (struct junknas_mesh *)arg;
 
@@ -737,27 +824,40 @@ static void *mesh_listener_thread(void *arg) {
         for (int i = 0; i < peer_count; i++) {
             mesh_log_verbose(mesh->config, "mesh: syncing bootstrap peer %s", peers[i]);
             int rc = mesh_sync_with_peer(mesh, peers[i]);
+            junknas_stats_inc(rc == 0 ? JUNKNAS_STAT_MESH_SYNC_OK : JUNKNAS_STAT_MESH_SYNC_FAILED);
+            int status = (rc == 0) ? 1 : 0;
             junknas_config_lock(mesh->config);
-            mesh->config->bootstrap_peer_status[i] = (rc == 0) ? 1 : 0;
+            int prev = junknas_config_set_bootstrap_peer_status(mesh->config, i, status);
             junknas_config_unlock(mesh->config);
             mesh_log_verbose(mesh->config, "mesh: bootstrap peer %s sync %s", peers[i], rc == 0 ? "ok" : "failed");
+            if (prev != status && prev != -2) mesh_publish_peer_status("bootstrap", peers[i], status);
             if (rc == 0) did_sync = 1;
         }
 
         for (int i = 0; i < wg_peer_count; i++) {
-            char host[MAX_ENDPOINT_LEN];
-            uint16_t port = 0;
-            if (mesh_peer_hostport(&wg_peers[i], default_web_port, host, sizeof(host), &port) != 0) {
-                continue;
-            }
+            junknas_mesh_candidate_t candidates[JUNKNAS_MESH_MAX_CANDIDATES];
+            int candidate_count = junknas_mesh_peer_candidates(&wg_peers[i], default_web_port, candidates);
+            if (candidate_count == 0) continue;
+
+            /* Overlay first; the advertised endpoint covers a tunnel that isn't up yet. */
             char endpoint[MAX_ENDPOINT_LEN];
-            snprintf(endpoint, sizeof(endpoint), "%s:%u", host, port);
-            mesh_log_verbose(mesh->config, "mesh: syncing LAN peer %s", endpoint);
-            int rc = mesh_sync_with_peer(mesh, endpoint);
+            int rc = -1;
+            for (int c = 0; c < candidate_count && rc != 0; c++) {
+                if (strchr(candidates[c].host, ':')) {
+                    snprintf(endpoint, sizeof(endpoint), "[%s]:%u", candidates[c].host, candidates[c].port);
+                } else {
+                    snprintf(endpoint, sizeof(endpoint), "%s:%u", candidates[c].host, candidates[c].port);
+                }
+                mesh_log_verbose(mesh->config, "mesh: syncing LAN peer %s", endpoint);
+                rc = mesh_sync_with_peer(mesh, endpoint);
+            }
+            junknas_stats_inc(rc == 0 ? JUNKNAS_STAT_MESH_SYNC_OK : JUNKNAS_STAT_MESH_SYNC_FAILED);
+            int status = (rc == 0) ? 1 : -1;
             junknas_config_lock(mesh->config);
-            mesh->config->wg_peer_status[i] = (rc == 0) ? 1 : -1;
+            int prev = junknas_config_set_wg_peer_status(mesh->config, i, status);
             junknas_config_unlock(mesh->config);
             mesh_log_verbose(mesh->config, "mesh: LAN peer %s sync %s", endpoint, rc == 0 ? "ok" : "failed");
+            if (prev != status && prev != -2) mesh_publish_peer_status("wg", endpoint, status);
             if (rc == 0) did_sync = 1;
         }
 
@@ -777,41 +877,35 @@ static void *mesh_listener_thread(void *arg) {
 static int http_request(const junknas_config_t *config, const char *host, uint16_t port,
                         const char *request, const uint8_t *body, size_t body_len,
                         FILE *out, int *out_status) {
-    struct sockaddr_storage addr;
-    socklen_t addr_len = 0;
-    if (resolve_addr(host, port, SOCK_STREAM, &addr, &addr_len) != 0) {
-        mesh_log_verbose(config, "mesh: http resolve failed for %s:%u", host, port);
-        return -1;
-    }
-
-    int fd = socket(addr.ss_family, SOCK_STREAM, 0);
+    int fd = junknas_net_connect(host, port, config ? config->endpoint_preference : NULL,
+                                 MESH_DIAL_TIMEOUT_MS, MESH_IO_TIMEOUT_MS);
     if (fd < 0) {
-        mesh_log_verbose(config, "mesh: http socket create failed for %s:%u", host, port);
-        return -1;
-    }
-
-    if (connect(fd, (struct sockaddr *)&addr, addr_len) != 0) {
-        mesh_log_verbose(config, "mesh: http connect failed for %s:%u", host, port);
-        close(fd);
+        mesh_log_verbose(config, "mesh: http %s failed for %s:%u",
+                         (fd == -1) ? "resolve" : "connect", host, port);
+        mesh_record_traffic(host, port, 0, 0, 0);
         return -1;
     }
 
     size_t request_len = strlen(request);
     mesh_log_verbose(config, "mesh: http send request %s:%u (%zu bytes)", host, port, request_len);
-    if (send(fd, request, request_len, 0) < 0) {
+    if (junknas_net_send_all(fd, request, request_len) != 0) {
         mesh_log_verbose(config, "mesh: http send request failed for %s:%u", host, port);
         close(fd);
+        mesh_record_traffic(host, port, 0, 0, 0);
         return -1;
     }
     if (body && body_len > 0) {
         mesh_log_verbose(config, "mesh: http send body %s:%u (%zu bytes)", host, port, body_len);
-        if (send(fd, body, body_len, 0) < 0) {
+        if (junknas_net_send_all(fd, body, body_len) != 0) {
             mesh_log_verbose(config, "mesh: http send body failed for %s:%u", host, port);
             close(fd);
+            mesh_record_traffic(host, port, request_len, 0, 0);
             return -1;
         }
     }
 
+    size_t sent = request_len + ((body && body_len > 0) ? body_len : 0);
+    size_t received = 0;
     char buf[4096];
     char header_buf[8192 + 1];
     size_t header_used = 0;
@@ -820,6 +914,7 @@ static int http_request(const junknas_config_t *config, const char *host, uint16
     while (1) {
         ssize_t n = recv(fd, buf, sizeof(buf), 0);
         if (n <= 0) break;
+        received += (size_t)n;
         mesh_log_verbose(config, "mesh: http recv chunk %s:%u (%zd bytes)", host, port, n);
 
         if (!header_done) {
@@ -836,6 +931,11 @@ static int http_request(const junknas_config_t *config, const char *host, uint16
                     header_buf[i + 2] == '\r' && header_buf[i + 3] == '\n') {
                     header_end = header_buf + i + 4;
                     size_t header_len = i + 4;
+                    /* Take the body bytes before terminating the headers in place. */
+                    size_t body_part = header_used - header_len;
+                    if (body_part > 0 && out) {
+                        fwrite(header_end, 1, body_part, out);
+                    }
                     if (header_len < sizeof(header_buf)) {
                         header_buf[header_len] = '\0';
                     } else {
@@ -847,10 +947,6 @@ static int http_request(const junknas_config_t *config, const char *host, uint16
                         (void)sscanf(header_buf, "HTTP/%*s %d", &status);
                     }
                     header_done = 1;
-                    size_t body_part = header_used - header_len;
-                    if (body_part > 0 && out) {
-                        fwrite(header_end, 1, body_part, out);
-                    }
                     break;
                 }
             }
@@ -860,15 +956,235 @@ static int http_request(const junknas_config_t *config, const char *host, uint16
     }
 
     close(fd);
+    mesh_record_traffic(host, port, sent, received, status >= 200 && status < 300);
     if (out_status) *out_status = status;
     mesh_log_verbose(config, "mesh: http response %s:%u status=%d", host, port, status);
     return (status >= 200 && status < 300) ? 0 : -1;
 }
 
+int junknas_mesh_auth_sign(const char *secret, const char *method, const char *path,
+                           uint64_t ts, const char *nonce, char *out, size_t out_len) {
+    if (!secret || secret[0] == '\0' || !method || !path || !nonce || !out) return -1;
+    size_t nonce_len = strlen(nonce);
+    if (nonce_len == 0 || nonce_len > JUNKNAS_MESH_AUTH_NONCE_LEN) return -1;
+    for (size_t i = 0; i < nonce_len; i++) {
+        if (!isxdigit((unsigned char)nonce[i])) return -1;
+    }
+    char msg[640];
+    int n = snprintf(msg, sizeof(msg), "%s %s %llu %s", method, path, (unsigned long long)ts, nonce);
+    if (n < 0 || (size_t)n >= sizeof(msg)) return -1;
+    char mac[65];
+    junknas_hmac_sha256_hex(secret, strlen(secret), msg, (size_t)n, mac);
+    n = snprintf(out, out_len, "%llu:%s:%s", (unsigned long long)ts, nonce, mac);
+    return (n < 0 || (size_t)n >= out_len) ? -1 : 0;
+}
+
+/* (ts, nonce) of signatures already accepted, so a captured request can't be
+ * sent again while its timestamp is still inside the window. Entries sit in
+ * a hash set for lookup and a min-heap on ts, which expires them with the
+ * window. When every slot is live the oldest ts is dropped early and any
+ * signature at or before it is refused from then on: a fresh one only hits
+ * that if the whole cache was filled within its own second.
+ */
+#define MESH_AUTH_SEEN_MAX 16384
+#define MESH_AUTH_SEEN_BUCKETS 4096
+
+typedef struct {
+    uint64_t ts;
+    int      next; /* index + 1 of the next entry in the bucket, 0 ends it */
+    char     nonce[JUNKNAS_MESH_AUTH_NONCE_LEN + 1];
+} mesh_auth_seen_t;
+
+static pthread_mutex_t g_auth_seen_lock = PTHREAD_MUTEX_INITIALIZER;
+static mesh_auth_seen_t g_auth_seen[MESH_AUTH_SEEN_MAX];
+static int g_auth_bucket[MESH_AUTH_SEEN_BUCKETS]; /* index + 1, 0 if empty */
+static int g_auth_heap[MESH_AUTH_SEEN_MAX];
+static int g_auth_heap_len;
+static int g_auth_free[MESH_AUTH_SEEN_MAX];
+static int g_auth_free_len;
+static int g_auth_never_used;
+static uint64_t g_auth_floor; /* newest ts dropped before it expired */
+
+static unsigned mesh_auth_bucket(uint64_t ts, const char *nonce) {
+    uint32_t h = 2166136261u;
+    for (int i = 0; i < 8; i++) {
+        h = (h ^ (uint32_t)((ts >> (i * 8)) & 0xff)) * 16777619u;
+    }
+    for (const char *p = nonce; *p; p++) {
+        h = (h ^ (uint32_t)(unsigned char)*p) * 16777619u;
+    }
+    return h & (MESH_AUTH_SEEN_BUCKETS - 1);
+}
+
+static void mesh_auth_heap_swap(int a, int b) {
+    int tmp = g_auth_heap[a];
+    g_auth_heap[a] = g_auth_heap[b];
+    g_auth_heap[b] = tmp;
+}
+
+static void mesh_auth_heap_push(int idx) {
+    int i = g_auth_heap_len++;
+    g_auth_heap[i] = idx;
+    while (i > 0) {
+        int parent = (i - 1) / 2;
+        if (g_auth_seen[g_auth_heap[parent]].ts <= g_auth_seen[g_auth_heap[i]].ts) break;
+        mesh_auth_heap_swap(parent, i);
+        i = parent;
+    }
+}
+
+static int mesh_auth_heap_pop(void) {
+    int top = g_auth_heap[0];
+    g_auth_heap[0] = g_auth_heap[--g_auth_heap_len];
+    int i = 0;
+    for (;;) {
+        int l = 2 * i + 1, r = l + 1, min = i;
+        if (l < g_auth_heap_len && g_auth_seen[g_auth_heap[l]].ts < g_auth_seen[g_auth_heap[min]].ts) min = l;
+        if (r < g_auth_heap_len && g_auth_seen[g_auth_heap[r]].ts < g_auth_seen[g_auth_heap[min]].ts) min = r;
+        if (min == i) break;
+        mesh_auth_heap_swap(min, i);
+        i = min;
+    }
+    return top;
+}
+
+/* Unlink idx from its bucket and put the slot back on the free list. */
+static void mesh_auth_forget(int idx) {
+    int *link = &g_auth_bucket[mesh_auth_bucket(g_auth_seen[idx].ts, g_auth_seen[idx].nonce)];
+    while (*link && *link != idx + 1) link = &g_auth_seen[*link - 1].next;
+    if (*link) *link = g_auth_seen[idx].next;
+    g_auth_free[g_auth_free_len++] = idx;
+}
+
+static int mesh_auth_remember(uint64_t ts, const char *nonce, uint64_t now) {
+    pthread_mutex_lock(&g_auth_seen_lock);
+    while (g_auth_heap_len > 0 && g_auth_seen[g_auth_heap[0]].ts + JUNKNAS_MESH_AUTH_WINDOW_SEC < now) {
+        mesh_auth_forget(mesh_auth_heap_pop());
+    }
+    if (ts <= g_auth_floor) {
+        pthread_mutex_unlock(&g_auth_seen_lock);
+        return -1;
+    }
+
+    unsigned bucket = mesh_auth_bucket(ts, nonce);
+    for (int i = g_auth_bucket[bucket]; i; i = g_auth_seen[i - 1].next) {
+        if (g_auth_seen[i - 1].ts == ts && strcmp(g_auth_seen[i - 1].nonce, nonce) == 0) {
+            pthread_mutex_unlock(&g_auth_seen_lock);
+            return -1;
+        }
+    }
+
+    int idx;
+    if (g_auth_free_len > 0) {
+        idx = g_auth_free[--g_auth_free_len];
+    } else if (g_auth_never_used < MESH_AUTH_SEEN_MAX) {
+        idx = g_auth_never_used++;
+    } else {
+        int oldest = mesh_auth_heap_pop();
+        if (g_auth_seen[oldest].ts > g_auth_floor) g_auth_floor = g_auth_seen[oldest].ts;
+        mesh_auth_forget(oldest);
+        idx = g_auth_free[--g_auth_free_len];
+    }
+    g_auth_seen[idx].ts = ts;
+    snprintf(g_auth_seen[idx].nonce, sizeof(g_auth_seen[idx].nonce), "%s", nonce);
+    g_auth_seen[idx].next = g_auth_bucket[bucket];
+    g_auth_bucket[bucket] = idx + 1;
+    mesh_auth_heap_push(idx);
+    pthread_mutex_unlock(&g_auth_seen_lock);
+    return 0;
+}
+
+int junknas_mesh_auth_verify(const char *secret, const char *method, const char *path,
+                             const char *value, uint64_t now) {
+    if (!secret || secret[0] == '\0' || !value) return -1;
+    char *end = NULL;
+    unsigned long long ts = strtoull(value, &end, 10);
+    if (end == value || *end != ':') return -1;
+    uint64_t skew = (ts > now) ? ts - now : now - ts;
+    if (skew > JUNKNAS_MESH_AUTH_WINDOW_SEC) return -1;
+
+    const char *nonce_start = end + 1;
+    const char *nonce_end = strchr(nonce_start, ':');
+    if (!nonce_end || nonce_end == nonce_start ||
+        (size_t)(nonce_end - nonce_start) > JUNKNAS_MESH_AUTH_NONCE_LEN) {
+        return -1;
+    }
+    char nonce[JUNKNAS_MESH_AUTH_NONCE_LEN + 1];
+    memcpy(nonce, nonce_start, (size_t)(nonce_end - nonce_start));
+    nonce[nonce_end - nonce_start] = '\0';
+
+    char expected[128];
+    if (junknas_mesh_auth_sign(secret, method, path, ts, nonce, expected, sizeof(expected)) != 0) return -1;
+    size_t len = strlen(expected);
+    size_t i = 0;
+    unsigned char diff = 0;
+    for (; i < len && value[i] != '\0'; i++) {
+        diff |= (unsigned char)(expected[i] ^ value[i]);
+    }
+    if (i != len || value[len] != '\0') return -1;
+    if (diff != 0) return -1;
+    return mesh_auth_remember(ts, nonce, now);
+}
+
+/* Random hex nonce for one signed request. */
+static void mesh_auth_nonce(char out[JUNKNAS_MESH_AUTH_NONCE_LEN + 1]) {
+    uint8_t raw[JUNKNAS_MESH_AUTH_NONCE_LEN / 2];
+    FILE *f = fopen("/dev/urandom", "rb");
+    size_t got = f ? fread(raw, 1, sizeof(raw), f) : 0;
+    if (f) fclose(f);
+    if (got != sizeof(raw)) {
+        static unsigned int seq;
+        struct timespec now;
+        clock_gettime(CLOCK_REALTIME, &now);
+        uint64_t mix = ((uint64_t)now.tv_nsec << 20) ^ ((uint64_t)getpid() << 40) ^
+                       __sync_fetch_and_add(&seq, 1);
+        for (size_t i = 0; i < sizeof(raw); i++) raw[i] = (uint8_t)(mix >> (8 * (i % 8)));
+    }
+    for (size_t i = 0; i < sizeof(raw); i++) {
+        snprintf(out + 2 * i, 3, "%02x", raw[i]);
+    }
+    out[JUNKNAS_MESH_AUTH_NONCE_LEN] = '\0';
+}
+
+/* Format "X-Junknas-Auth: ...\r\n" for a chunk request. Returns -1 (and
+ * leaves out empty) when no mesh_secret is set: peers refuse unsigned
+ * chunk requests, so there is no point sending one.
+ */
+static int mesh_auth_header_line(const junknas_mesh_t *mesh, const char *method, const char *path,
+                                 char *out, size_t out_len) {
+    out[0] = '\0';
+    char secret[MAX_MESH_SECRET_LEN];
+    junknas_config_lock(mesh->config);
+    snprintf(secret, sizeof(secret), "%s", mesh->config->mesh_secret);
+    junknas_config_unlock(mesh->config);
+    if (secret[0] == '\0') return -1;
+
+    char nonce[JUNKNAS_MESH_AUTH_NONCE_LEN + 1];
+    mesh_auth_nonce(nonce);
+    char value[128];
+    if (junknas_mesh_auth_sign(secret, method, path, (uint64_t)time(NULL), nonce, value, sizeof(value)) != 0) {
+        return -1;
+    }
+    snprintf(out, out_len, "X-Junknas-Auth: %s\r\n", value);
+    return 0;
+}
+
+/* Id shared by every peer request made for one fetch/replicate, sent as
+ * X-Junknas-Request-Id so the serving node's log lines can be matched up.
+ */
+static void mesh_new_request_id(char out[MESH_REQUEST_ID_LEN]) {
+    static unsigned int seq;
+    unsigned int n = __sync_fetch_and_add(&seq, 1);
+    snprintf(out, MESH_REQUEST_ID_LEN, "%08x%04x%04x",
+             (unsigned int)time(NULL), (unsigned int)getpid() & 0xffffu, n & 0xffffu);
+}
+
 int junknas_mesh_fetch_chunk(junknas_mesh_t *mesh, const char *hashhex, const char *dest_path) {
     if (!mesh || !hashhex || !dest_path) return -1;
-    if (!junknas_mesh_is_active(mesh)) return -1;
-    mesh_log_verbose(mesh->config, "mesh: fetch chunk %s -> %s", hashhex, dest_path);
+    if (!junknas_mesh_is_active(mesh)) return -ENOENT;
+    char req_id[MESH_REQUEST_ID_LEN];
+    mesh_new_request_id(req_id);
+    mesh_log_verbose(mesh->config, "mesh: [req %s] fetch chunk %s -> %s", req_id, hashhex, dest_path);
 
     junknas_config_lock(mesh->config);
     junknas_wg_peer_t peers[MESH_MAX_PEERS];
@@ -878,38 +1194,62 @@ int junknas_mesh_fetch_chunk(junknas_mesh_t *mesh, const char *hashhex, const ch
         peers[i] = mesh->config->wg_peers[i];
     }
     uint16_t default_web_port = mesh->config->web_port;
+    int require_encrypted = mesh->config->require_encrypted_mesh;
     junknas_config_unlock(mesh->config);
 
+    int unreachable = 0;
+    int blocked = 0;
     for (int i = 0; i < peer_count; i++) {
         char host[MAX_ENDPOINT_LEN];
         uint16_t port = 0;
-        if (mesh_peer_hostport(&peers[i], default_web_port, host, sizeof(host), &port) != 0) {
+        int hp = mesh_chunk_peer_hostport(&peers[i], default_web_port, require_encrypted,
+                                          host, sizeof(host), &port);
+        if (hp == 1) {
+            blocked++;
+            junknas_stats_inc(JUNKNAS_STAT_MESH_FETCH_BLOCKED);
             continue;
         }
-        char request[512];
+        if (hp != 0) continue;
+        char chunk_path[80];
+        snprintf(chunk_path, sizeof(chunk_path), "/chunks/%s", hashhex);
+        char auth[160];
+        if (mesh_auth_header_line(mesh, "GET", chunk_path, auth, sizeof(auth)) != 0) {
+            mesh_log_verbose(mesh->config, "mesh: [req %s] not fetching chunk %s: mesh_secret is not set",
+                             req_id, hashhex);
+            junknas_stats_inc(JUNKNAS_STAT_MESH_FETCH_FAILED);
+            return -EACCES;
+        }
+        char request[640];
         snprintf(request, sizeof(request),
-                 "GET /chunks/%s HTTP/1.1\r\nHost: %s\r\nConnection: close\r\n\r\n",
-                 hashhex, host);
+                 "GET %s HTTP/1.1\r\nHost: %s\r\n%sX-Junknas-Request-Id: %s\r\nConnection: close\r\n\r\n",
+                 chunk_path, host, auth, req_id);
 
         FILE *out = fopen(dest_path, "wb");
         if (!out) continue;
         int status = 0;
-        mesh_log_verbose(mesh->config, "mesh: fetching chunk %s from %s:%u",
-                         hashhex, host, port);
+        mesh_log_verbose(mesh->config, "mesh: [req %s] fetching chunk %s from %s:%u",
+                         req_id, hashhex, host, port);
         int rc = http_request(mesh->config, host, port,
                               request, NULL, 0, out, &status);
         fclose(out);
 
         if (rc == 0) {
-            mesh_log_verbose(mesh->config, "mesh: fetch chunk %s succeeded via %s:%u",
-                             hashhex, host, port);
+            mesh_log_verbose(mesh->config, "mesh: [req %s] fetch chunk %s succeeded via %s:%u",
+                             req_id, hashhex, host, port);
+            junknas_stats_inc(JUNKNAS_STAT_MESH_FETCH_OK);
             return 0;
         }
         (void)unlink(dest_path);
+        /* No HTTP status at all (or a 5xx) means the peer may still have it. */
+        if (status == 0 || status >= 500) unreachable++;
     }
 
-    mesh_log_verbose(mesh->config, "mesh: fetch chunk %s failed on all peers", hashhex);
-    return -1;
+    mesh_log_verbose(mesh->config, "mesh: [req %s] fetch chunk %s failed on all peers (%d unreachable, %d blocked)",
+                     req_id, hashhex, unreachable, blocked);
+    junknas_stats_inc(JUNKNAS_STAT_MESH_FETCH_FAILED);
+    if (unreachable > 0) return -EAGAIN;
+    if (blocked > 0) return -EACCES;
+    return -ENOENT;
 }
 
 int junknas_mesh_replicate_chunk(junknas_mesh_t *mesh,
@@ -918,7 +1258,9 @@ int junknas_mesh_replicate_chunk(junknas_mesh_t *mesh,
                                 size_t len) {
     if (!mesh || !hashhex || !data || len == 0) return -1;
     if (!junknas_mesh_is_active(mesh)) return -1;
-    mesh_log_verbose(mesh->config, "mesh: replicate chunk %s (%zu bytes)", hashhex, len);
+    char req_id[MESH_REQUEST_ID_LEN];
+    mesh_new_request_id(req_id);
+    mesh_log_verbose(mesh->config, "mesh: [req %s] replicate chunk %s (%zu bytes)", req_id, hashhex, len);
 
     junknas_config_lock(mesh->config);
     junknas_wg_peer_t peers[MESH_MAX_PEERS];
@@ -928,25 +1270,44 @@ int junknas_mesh_replicate_chunk(junknas_mesh_t *mesh,
         peers[i] = mesh->config->wg_peers[i];
     }
     uint16_t default_web_port = mesh->config->web_port;
+    int require_encrypted = mesh->config->require_encrypted_mesh;
     junknas_config_unlock(mesh->config);
 
     for (int i = 0; i < peer_count; i++) {
         char host[MAX_ENDPOINT_LEN];
         uint16_t port = 0;
-        if (mesh_peer_hostport(&peers[i], default_web_port, host, sizeof(host), &port) != 0) {
+        int hp = mesh_chunk_peer_hostport(&peers[i], default_web_port, require_encrypted,
+                                          host, sizeof(host), &port);
+        if (hp == 1) {
+            mesh_log_verbose(mesh->config, "mesh: [req %s] not replicating chunk %s to %s: %s",
+                             req_id, hashhex, peers[i].endpoint,
+                             peers[i].wg_ip[0] ? "wg_ip is not routed through WireGuard" : "no wg_ip");
+            junknas_stats_inc(JUNKNAS_STAT_MESH_REPLICATE_BLOCKED);
             continue;
         }
-        char request[512];
+        if (hp != 0) continue;
+        char chunk_path[80];
+        snprintf(chunk_path, sizeof(chunk_path), "/chunks/%s", hashhex);
+        char auth[160];
+        if (mesh_auth_header_line(mesh, "POST", chunk_path, auth, sizeof(auth)) != 0) {
+            mesh_log_verbose(mesh->config, "mesh: [req %s] not replicating chunk %s: mesh_secret is not set",
+                             req_id, hashhex);
+            junknas_stats_inc(JUNKNAS_STAT_MESH_REPLICATE_FAILED);
+            return -1;
+        }
+        char request[640];
         snprintf(request, sizeof(request),
-                 "POST /chunks/%s HTTP/1.1\r\nHost: %s\r\nConnection: close\r\nContent-Length: %zu\r\n\r\n",
-                 hashhex, host, len);
-        mesh_log_verbose(mesh->config, "mesh: replicating chunk %s -> %s:%u",
-                         hashhex, host, port);
-        (void)http_request(mesh->config, host, port,
-                           request, data, len, NULL, NULL);
+                 "POST %s HTTP/1.1\r\nHost: %s\r\n%sX-Junknas-Request-Id: %s\r\nConnection: close\r\nContent-Length: %zu\r\n\r\n",
+                 chunk_path, host, auth, req_id, len);
+        mesh_log_verbose(mesh->config, "mesh: [req %s] replicating chunk %s -> %s:%u",
+                         req_id, hashhex, host, port);
+        int rc = http_request(mesh->config, host, port,
+                              request, data, len, NULL, NULL);
+        junknas_stats_inc(rc == 0 ? JUNKNAS_STAT_MESH_REPLICATE_OK : JUNKNAS_STAT_MESH_REPLICATE_FAILED);
+        if (rc == 0) junknas_stats_add(JUNKNAS_STAT_WRITE_BYTES_REPLICATED, (uint64_t)len);
     }
 
-    mesh_log_verbose(mesh->config, "mesh: replicate chunk %s done", hashhex);
+    mesh_log_verbose(mesh->config, "mesh: [req %s] replicate chunk %s done", req_id, hashhex);
     return 0;
 }
 
@@ -0,0 +1,177 @@
+/*
+ * junkNAS - Network helpers
+ *
+ * Peer requests run on FUSE and sync threads, so every dial and every
+ * send/recv is bounded: an unreachable or stalled peer costs its timeout,
+ * not the kernel's TCP defaults.
+ *
+ * WireGuard interfaces are recognised by DEVTYPE=wireguard in their sysfs
+ * uevent; junkNAS doesn't create them, it only checks that one carries
+ * the traffic.
+ */
+
+#include "net.h"
+#include "config.h"
+
+#include <arpa/inet.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <ifaddrs.h>
+#include <net/if.h>
+#include <netdb.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <sys/time.h>
+#include <sys/types.h>
+#include <unistd.h>
+
+static int preferred_family(const char *preference) {
+    if (preference && strcmp(preference, ENDPOINT_PREF_V4) == 0) return AF_INET;
+    if (preference && strcmp(preference, ENDPOINT_PREF_V6) == 0) return AF_INET6;
+    return AF_UNSPEC;
+}
+
+/* connect() with a deadline: non-blocking connect, then poll for it to finish. */
+static int connect_with_timeout(int fd, const struct sockaddr *addr, socklen_t addr_len, int timeout_ms) {
+    int flags = fcntl(fd, F_GETFL, 0);
+    if (flags < 0 || fcntl(fd, F_SETFL, flags | O_NONBLOCK) != 0) return -1;
+    int rc = connect(fd, addr, addr_len);
+    if (rc != 0 && errno == EINPROGRESS) {
+        struct pollfd pfd = { .fd = fd, .events = POLLOUT };
+        int ready;
+        do {
+            ready = poll(&pfd, 1, timeout_ms);
+        } while (ready < 0 && errno == EINTR);
+        int err = 0;
+        socklen_t err_len = sizeof(err);
+        if (ready == 1 && getsockopt(fd, SOL_SOCKET, SO_ERROR, &err, &err_len) == 0 && err == 0) rc = 0;
+    }
+    if (fcntl(fd, F_SETFL, flags) != 0) return -1;
+    return rc;
+}
+
+int junknas_net_connect(const char *host, uint16_t port, const char *preference,
+                        int dial_timeout_ms, int io_timeout_ms) {
+    if (!host) return -1;
+    char port_str[8];
+    snprintf(port_str, sizeof(port_str), "%u", port);
+
+    struct addrinfo hints;
+    memset(&hints, 0, sizeof(hints));
+    hints.ai_socktype = SOCK_STREAM;
+    hints.ai_family = AF_UNSPEC;
+
+    struct addrinfo *res = NULL;
+    if (getaddrinfo(host, port_str, &hints, &res) != 0) return -1;
+    if (!res) return -1;
+
+    struct timeval io_timeout = { io_timeout_ms / 1000, (io_timeout_ms % 1000) * 1000 };
+    int family = preferred_family(preference);
+    int fd = -2;
+    for (int pass = 0; pass < 2 && fd < 0; pass++) {
+        for (struct addrinfo *ai = res; ai && fd < 0; ai = ai->ai_next) {
+            int match = (family == AF_UNSPEC || ai->ai_family == family);
+            if ((pass == 0) != match) continue;
+            int s = socket(ai->ai_family, SOCK_STREAM, 0);
+            if (s < 0) continue;
+            if (connect_with_timeout(s, ai->ai_addr, ai->ai_addrlen, dial_timeout_ms) == 0) {
+                setsockopt(s, SOL_SOCKET, SO_RCVTIMEO, &io_timeout, sizeof(io_timeout));
+                setsockopt(s, SOL_SOCKET, SO_SNDTIMEO, &io_timeout, sizeof(io_timeout));
+                fd = s;
+            } else {
+                close(s);
+            }
+        }
+    }
+    freeaddrinfo(res);
+    return fd;
+}
+
+int junknas_net_send_all(int fd, const void *data, size_t len) {
+    const char *p = (const char *)data;
+    while (len > 0) {
+        ssize_t n = send(fd, p, len, MSG_NOSIGNAL);
+        if (n < 0 && errno == EINTR) continue;
+        if (n <= 0) return -1;
+        p += n;
+        len -= (size_t)n;
+    }
+    return 0;
+}
+
+static int ifname_is_wireguard(const char *ifname) {
+    char path[64 + IF_NAMESIZE];
+    snprintf(path, sizeof(path), "/sys/class/net/%s/uevent", ifname);
+    FILE *f = fopen(path, "r");
+    if (!f) return 0;
+    char line[128];
+    int found = 0;
+    while (!found && fgets(line, sizeof(line), f)) {
+        found = (strcmp(line, "DEVTYPE=wireguard\n") == 0);
+    }
+    fclose(f);
+    return found;
+}
+
+static int same_address(const struct sockaddr *a, const struct sockaddr *b) {
+    if (a->sa_family != b->sa_family) return 0;
+    if (a->sa_family == AF_INET) {
+        return ((const struct sockaddr_in *)a)->sin_addr.s_addr == ((const struct sockaddr_in *)b)->sin_addr.s_addr;
+    }
+    if (a->sa_family == AF_INET6) {
+        return memcmp(&((const struct sockaddr_in6 *)a)->sin6_addr, &((const struct sockaddr_in6 *)b)->sin6_addr,
+                      sizeof(struct in6_addr)) == 0;
+    }
+    return 0;
+}
+
+int junknas_net_addr_on_wireguard(const struct sockaddr *addr) {
+    if (!addr || (addr->sa_family != AF_INET && addr->sa_family != AF_INET6)) return 0;
+    struct ifaddrs *ifs = NULL;
+    if (getifaddrs(&ifs) != 0) return 0;
+    int rc = 0;
+    for (struct ifaddrs *ifa = ifs; ifa && !rc; ifa = ifa->ifa_next) {
+        if (!ifa->ifa_addr || !ifa->ifa_name || !same_address(ifa->ifa_addr, addr)) continue;
+        rc = ifname_is_wireguard(ifa->ifa_name);
+    }
+    freeifaddrs(ifs);
+    return rc;
+}
+
+int junknas_net_route_is_wireguard(const char *ip) {
+    if (!ip) return -1;
+    struct sockaddr_storage dst;
+    socklen_t dst_len;
+    memset(&dst, 0, sizeof(dst));
+    struct sockaddr_in *v4 = (struct sockaddr_in *)&dst;
+    struct sockaddr_in6 *v6 = (struct sockaddr_in6 *)&dst;
+    if (inet_pton(AF_INET, ip, &v4->sin_addr) == 1) {
+        v4->sin_family = AF_INET;
+        v4->sin_port = htons(9);
+        dst_len = sizeof(*v4);
+    } else if (inet_pton(AF_INET6, ip, &v6->sin6_addr) == 1) {
+        v6->sin6_family = AF_INET6;
+        v6->sin6_port = htons(9);
+        dst_len = sizeof(*v6);
+    } else {
+        return -1;
+    }
+
+    /* A connected UDP socket makes the kernel pick a route and source
+     * address without sending anything.
+     */
+    int fd = socket(dst.ss_family, SOCK_DGRAM, 0);
+    if (fd < 0) return -1;
+    struct sockaddr_storage src;
+    socklen_t src_len = sizeof(src);
+    int rc = -1;
+    if (connect(fd, (struct sockaddr *)&dst, dst_len) == 0 &&
+        getsockname(fd, (struct sockaddr *)&src, &src_len) == 0) {
+        rc = junknas_net_addr_on_wireguard((struct sockaddr *)&src);
+    }
+    close(fd);
+    return rc;
+}
@@ -0,0 +1,163 @@
+/*
+ * junkNAS - SHA-256 / HMAC-SHA-256
+ *
+ * Minimal SHA-256 implementation (public-domain style).
+ * Good enough for integrity & content addressing.
+ */
+
+#include "sha256.h"
+
+#include <string.h>
+
+static uint32_t rotr32(uint32_t x, uint32_t n) { return (x >> n) | (x << (32 - n)); }
+
+void junknas_sha256_init(junknas_sha256_ctx *c) {
+    c->h[0] = 0x6a09e667u; c->h[1] = 0xbb67ae85u; c->h[2] = 0x3c6ef372u; c->h[3] = 0xa54ff53au;
+    c->h[4] = 0x510e527fu; c->h[5] = 0x9b05688cu; c->h[6] = 0x1f83d9abu; c->h[7] = 0x5be0cd19u;
+    c->len_bits = 0;
+    c->buf_len = 0;
+}
+
+static void sha256_compress(junknas_sha256_ctx *c, const uint8_t block[64]) {
+    static const uint32_t K[64] = {
+        0x428a2f98u,0x71374491u,0xb5c0fbcfu,0xe9b5dba5u,0x3956c25bu,0x59f111f1u,0x923f82a4u,0xab1c5ed5u,
+        0xd807aa98u,0x12835b01u,0x243185beu,0x550c7dc3u,0x72be5d74u,0x80deb1feu,0x9bdc06a7u,0xc19bf174u,
+        0xe49b69c1u,0xefbe4786u,0x0fc19dc6u,0x240ca1ccu,0x2de92c6fu,0x4a7484aau,0x5cb0a9dcu,0x76f988dau,
+        0x983e5152u,0xa831c66du,0xb00327c8u,0xbf597fc7u,0xc6e00bf3u,0xd5a79147u,0x06ca6351u,0x14292967u,
+        0x27b70a85u,0x2e1b2138u,0x4d2c6dfcu,0x53380d13u,0x650a7354u,0x766a0abbu,0x81c2c92eu,0x92722c85u,
+        0xa2bfe8a1u,0xa81a664bu,0xc24b8b70u,0xc76c51a3u,0xd192e819u,0xd6990624u,0xf40e3585u,0x106aa070u,
+        0x19a4c116u,0x1e376c08u,0x2748774cu,0x34b0bcb5u,0x391c0cb3u,0x4ed8aa4au,0x5b9cca4fu,0x682e6ff3u,
+        0x748f82eeu,0x78a5636fu,0x84c87814u,0x8cc70208u,0x90befffau,0xa4506cebu,0xbef9a3f7u,0xc67178f2u
+    };
+
+    uint32_t w[64];
+    for (int i = 0; i < 16; i++) {
+        w[i] = ((uint32_t)block[i*4+0] << 24) | ((uint32_t)block[i*4+1] << 16) |
+               ((uint32_t)block[i*4+2] << 8)  | ((uint32_t)block[i*4+3]);
+    }
+    for (int i = 16; i < 64; i++) {
+        uint32_t s0 = rotr32(w[i-15], 7) ^ rotr32(w[i-15], 18) ^ (w[i-15] >> 3);
+        uint32_t s1 = rotr32(w[i-2], 17) ^ rotr32(w[i-2], 19) ^ (w[i-2] >> 10);
+        w[i] = w[i-16] + s0 + w[i-7] + s1;
+    }
+
+    uint32_t a=c->h[0], b=c->h[1], d=c->h[3], e=c->h[4], f=c->h[5], g=c->h[6], h=c->h[7], cc=c->h[2];
+
+    for (int i = 0; i < 64; i++) {
+        uint32_t S1 = rotr32(e,6) ^ rotr32(e,11) ^ rotr32(e,25);
+        uint32_t ch = (e & f) ^ ((~e) & g);
+        uint32_t temp1 = h + S1 + ch + K[i] + w[i];
+        uint32_t S0 = rotr32(a,2) ^ rotr32(a,13) ^ rotr32(a,22);
+        uint32_t maj = (a & b) ^ (a & cc) ^ (b & cc);
+        uint32_t temp2 = S0 + maj;
+
+        h = g;
+        g = f;
+        f = e;
+        e = d + temp1;
+        d = cc;
+        cc = b;
+        b = a;
+        a = temp1 + temp2;
+    }
+
+    c->h[0]+=a; c->h[1]+=b; c->h[2]+=cc; c->h[3]+=d; c->h[4]+=e; c->h[5]+=f; c->h[6]+=g; c->h[7]+=h;
+}
+
+void junknas_sha256_update(junknas_sha256_ctx *c, const void *data, size_t n) {
+    const uint8_t *p = (const uint8_t *)data;
+    c->len_bits += (uint64_t)n * 8u;
+
+    while (n > 0) {
+        size_t room = 64 - c->buf_len;
+        size_t take = (n < room) ? n : room;
+        memcpy(c->buf + c->buf_len, p, take);
+        c->buf_len += take;
+        p += take;
+        n -= take;
+
+        if (c->buf_len == 64) {
+            sha256_compress(c, c->buf);
+            c->buf_len = 0;
+        }
+    }
+}
+
+void junknas_sha256_final(junknas_sha256_ctx *c, uint8_t out[32]) {
+    /* pad */
+    c->buf[c->buf_len++] = 0x80;
+    if (c->buf_len > 56) {
+        while (c->buf_len < 64) c->buf[c->buf_len++] = 0x00;
+        sha256_compress(c, c->buf);
+        c->buf_len = 0;
+    }
+    while (c->buf_len < 56) c->buf[c->buf_len++] = 0x00;
+
+    /* length big-endian */
+    uint64_t L = c->len_bits;
+    for (int i = 7; i >= 0; i--) {
+        c->buf[c->buf_len++] = (uint8_t)((L >> (i*8)) & 0xffu);
+    }
+    sha256_compress(c, c->buf);
+
+    for (int i = 0; i < 8; i++) {
+        out[i*4+0] = (uint8_t)((c->h[i] >> 24) & 0xffu);
+        out[i*4+1] = (uint8_t)((c->h[i] >> 16) & 0xffu);
+        out[i*4+2] = (uint8_t)((c->h[i] >> 8) & 0xffu);
+        out[i*4+3] = (uint8_t)((c->h[i]) & 0xffu);
+    }
+}
+
+void junknas_sha256_hex(const uint8_t digest[32], char hex[65]) {
+    static const char *H = "0123456789abcdef";
+    for (int i = 0; i < 32; i++) {
+        hex[i*2+0] = H[(digest[i] >> 4) & 0xF];
+        hex[i*2+1] = H[digest[i] & 0xF];
+    }
+    hex[64] = '\0';
+}
+
+void junknas_sha256_buf_hex(const void *data, size_t n, char hex[65]) {
+    junknas_sha256_ctx c;
+    uint8_t d[32];
+    junknas_sha256_init(&c);
+    junknas_sha256_update(&c, data, n);
+    junknas_sha256_final(&c, d);
+    junknas_sha256_hex(d, hex);
+}
+
+void junknas_hmac_sha256_hex(const void *key, size_t key_len,
+                             const void *msg, size_t msg_len,
+                             char hex[65]) {
+    uint8_t k[64];
+    memset(k, 0, sizeof(k));
+    if (key_len > sizeof(k)) {
+        junknas_sha256_ctx kc;
+        junknas_sha256_init(&kc);
+        junknas_sha256_update(&kc, key, key_len);
+        junknas_sha256_final(&kc, k);
+    } else if (key_len > 0) {
+        memcpy(k, key, key_len);
+    }
+
+    uint8_t ipad[64];
+    uint8_t opad[64];
+    for (int i = 0; i < 64; i++) {
+        ipad[i] = (uint8_t)(k[i] ^ 0x36u);
+        opad[i] = (uint8_t)(k[i] ^ 0x5cu);
+    }
+
+    uint8_t inner[32];
+    junknas_sha256_ctx c;
+    junknas_sha256_init(&c);
+    junknas_sha256_update(&c, ipad, sizeof(ipad));
+    junknas_sha256_update(&c, msg, msg_len);
+    junknas_sha256_final(&c, inner);
+
+    uint8_t outer[32];
+    junknas_sha256_init(&c);
+    junknas_sha256_update(&c, opad, sizeof(opad));
+    junknas_sha256_update(&c, inner, sizeof(inner));
+    junknas_sha256_final(&c, outer);
+    junknas_sha256_hex(outer, hex);
+}
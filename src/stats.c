@@ -0,0 +1,289 @@
+/*
+ * junkNAS - Process-wide counters for FUSE, chunk store and mesh activity
+ *
+ * Counters are plain uint64_t slots updated with atomic builtins so the
+ * FUSE worker threads, mesh thread and web handlers never contend on a lock.
+ */
+
+#include "stats.h"
+
+#include <pthread.h>
+#include <stdarg.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+/* Upper bounds (microseconds) of the FUSE latency histogram buckets; +Inf is implicit. */
+static const uint64_t g_fuse_bucket_usec[] = {
+    100, 500, 1000, 5000, 25000, 100000, 500000, 2500000
+};
+#define FUSE_BUCKET_COUNT (sizeof(g_fuse_bucket_usec) / sizeof(g_fuse_bucket_usec[0]))
+
+typedef struct {
+    uint64_t buckets[FUSE_BUCKET_COUNT + 1];
+    uint64_t sum_usec;
+} fuse_histogram_t;
+
+static uint64_t g_counters[JUNKNAS_STAT_COUNTER_COUNT];
+static fuse_histogram_t g_fuse_ops[JUNKNAS_FUSE_OP_COUNT];
+
+static const struct {
+    const char *name;
+    const char *label;
+    const char *help;
+} g_counter_info[JUNKNAS_STAT_COUNTER_COUNT] = {
+    [JUNKNAS_STAT_CHUNK_READ_LOCAL]     = {"junknas_chunk_reads_total", "source=\"local\"", "Chunks read by the FUSE layer."},
+    [JUNKNAS_STAT_CHUNK_READ_MESH]      = {"junknas_chunk_reads_total", "source=\"mesh\"", NULL},
+    [JUNKNAS_STAT_CHUNK_READ_FAILED]    = {"junknas_chunk_reads_total", "source=\"failed\"", NULL},
+    [JUNKNAS_STAT_CHUNK_WRITE_NEW]      = {"junknas_chunk_writes_total", "result=\"stored\"", "Chunks written by the FUSE layer."},
+    [JUNKNAS_STAT_CHUNK_WRITE_DEDUP]    = {"junknas_chunk_writes_total", "result=\"dedup\"", NULL},
+    [JUNKNAS_STAT_CHUNK_WRITE_FAILED]   = {"junknas_chunk_writes_total", "result=\"failed\"", NULL},
+    [JUNKNAS_STAT_MESH_FETCH_OK]        = {"junknas_mesh_fetches_total", "result=\"ok\"", "Chunk fetches from mesh peers."},
+    [JUNKNAS_STAT_MESH_FETCH_FAILED]    = {"junknas_mesh_fetches_total", "result=\"failed\"", NULL},
+    [JUNKNAS_STAT_MESH_REPLICATE_OK]    = {"junknas_mesh_replications_total", "result=\"ok\"", "Chunk uploads to mesh peers."},
+    [JUNKNAS_STAT_MESH_REPLICATE_FAILED] = {"junknas_mesh_replications_total", "result=\"failed\"", NULL},
+    [JUNKNAS_STAT_MESH_SYNC_OK]         = {"junknas_mesh_syncs_total", "result=\"ok\"", "Peer list syncs with mesh peers."},
+    [JUNKNAS_STAT_MESH_SYNC_FAILED]     = {"junknas_mesh_syncs_total", "result=\"failed\"", NULL},
+    [JUNKNAS_STAT_MESH_PEER_REJECTED]   = {"junknas_mesh_rejected_entries_total", "kind=\"peer\"", "Malformed entries dropped from peer sync payloads."},
+    [JUNKNAS_STAT_MESH_MOUNT_REJECTED]  = {"junknas_mesh_rejected_entries_total", "kind=\"mount_point\"", NULL},
+    [JUNKNAS_STAT_WRITE_BYTES_LOGICAL]  = {"junknas_write_bytes_total", "kind=\"logical\"", "Bytes written by applications vs new chunk bytes stored locally and sent to peers."},
+    [JUNKNAS_STAT_WRITE_BYTES_STORED]   = {"junknas_write_bytes_total", "kind=\"stored\"", NULL},
+    [JUNKNAS_STAT_WRITE_BYTES_REPLICATED] = {"junknas_write_bytes_total", "kind=\"replicated\"", NULL},
+    [JUNKNAS_STAT_MESH_FETCH_BLOCKED]   = {"junknas_mesh_policy_blocked_total", "op=\"fetch\"", "Chunk transfers skipped for a peer with no encrypted link (require_encrypted_mesh)."},
+    [JUNKNAS_STAT_MESH_REPLICATE_BLOCKED] = {"junknas_mesh_policy_blocked_total", "op=\"replicate\"", NULL},
+    [JUNKNAS_STAT_SCRUB_CHECKED]        = {"junknas_scrub_chunks_total", "result=\"checked\"", "Chunk files rehashed by the background scrubber."},
+    [JUNKNAS_STAT_SCRUB_CORRUPT]        = {"junknas_scrub_chunks_total", "result=\"corrupt\"", NULL},
+    [JUNKNAS_STAT_SCRUB_REPAIRED]       = {"junknas_scrub_chunks_total", "result=\"repaired\"", NULL},
+};
+
+static pthread_mutex_t g_peers_lock = PTHREAD_MUTEX_INITIALIZER;
+static junknas_peer_traffic_t g_peers[JUNKNAS_STATS_MAX_PEERS];
+static int g_peer_count = 0;
+
+static uint64_t g_fuse_slow_ops[JUNKNAS_FUSE_OP_COUNT];
+
+static const char *g_fuse_op_names[JUNKNAS_FUSE_OP_COUNT] = {
+    [JUNKNAS_FUSE_OP_GETATTR] = "getattr",
+    [JUNKNAS_FUSE_OP_READDIR] = "readdir",
+    [JUNKNAS_FUSE_OP_CREATE]  = "create",
+    [JUNKNAS_FUSE_OP_OPEN]    = "open",
+    [JUNKNAS_FUSE_OP_READ]    = "read",
+    [JUNKNAS_FUSE_OP_WRITE]   = "write",
+    [JUNKNAS_FUSE_OP_RELEASE] = "release",
+    [JUNKNAS_FUSE_OP_UNLINK]  = "unlink",
+    [JUNKNAS_FUSE_OP_RENAME]  = "rename",
+};
+
+void junknas_stats_inc(junknas_stat_counter_t counter) {
+    junknas_stats_add(counter, 1);
+}
+
+void junknas_stats_add(junknas_stat_counter_t counter, uint64_t n) {
+    if ((unsigned)counter >= JUNKNAS_STAT_COUNTER_COUNT) return;
+    __atomic_fetch_add(&g_counters[counter], n, __ATOMIC_RELAXED);
+}
+
+uint64_t junknas_stats_get(junknas_stat_counter_t counter) {
+    if ((unsigned)counter >= JUNKNAS_STAT_COUNTER_COUNT) return 0;
+    return __atomic_load_n(&g_counters[counter], __ATOMIC_RELAXED);
+}
+
+uint64_t junknas_stats_now_usec(void) {
+    struct timespec ts;
+    if (clock_gettime(CLOCK_MONOTONIC, &ts) != 0) return 0;
+    return (uint64_t)ts.tv_sec * 1000000ULL + (uint64_t)ts.tv_nsec / 1000ULL;
+}
+
+void junknas_stats_observe_fuse_op(junknas_fuse_op_t op, uint64_t usec) {
+    if ((unsigned)op >= JUNKNAS_FUSE_OP_COUNT) return;
+    fuse_histogram_t *h = &g_fuse_ops[op];
+    size_t b = 0;
+    while (b < FUSE_BUCKET_COUNT && usec > g_fuse_bucket_usec[b]) b++;
+    __atomic_fetch_add(&h->buckets[b], 1, __ATOMIC_RELAXED);
+    __atomic_fetch_add(&h->sum_usec, usec, __ATOMIC_RELAXED);
+}
+
+void junknas_stats_count_slow_fuse_op(junknas_fuse_op_t op) {
+    if ((unsigned)op >= JUNKNAS_FUSE_OP_COUNT) return;
+    __atomic_fetch_add(&g_fuse_slow_ops[op], 1, __ATOMIC_RELAXED);
+}
+
+const char *junknas_stats_fuse_op_name(junknas_fuse_op_t op) {
+    if ((unsigned)op >= JUNKNAS_FUSE_OP_COUNT) return "unknown";
+    return g_fuse_op_names[op];
+}
+
+void junknas_stats_record_peer(const char *endpoint, uint64_t sent, uint64_t received, int ok) {
+    if (!endpoint || endpoint[0] == '\0') return;
+    pthread_mutex_lock(&g_peers_lock);
+    junknas_peer_traffic_t *slot = NULL;
+    for (int i = 0; i < g_peer_count; i++) {
+        if (strcmp(g_peers[i].endpoint, endpoint) == 0) {
+            slot = &g_peers[i];
+            break;
+        }
+    }
+    if (!slot && g_peer_count < JUNKNAS_STATS_MAX_PEERS) {
+        slot = &g_peers[g_peer_count++];
+        memset(slot, 0, sizeof(*slot));
+        snprintf(slot->endpoint, sizeof(slot->endpoint), "%s", endpoint);
+    }
+    if (slot) {
+        slot->bytes_sent += sent;
+        slot->bytes_received += received;
+        slot->requests++;
+        if (!ok) slot->failures++;
+    }
+    pthread_mutex_unlock(&g_peers_lock);
+}
+
+int junknas_stats_get_peer(const char *endpoint, junknas_peer_traffic_t *out) {
+    if (!endpoint || !out) return -1;
+    int rc = -1;
+    pthread_mutex_lock(&g_peers_lock);
+    for (int i = 0; i < g_peer_count; i++) {
+        if (strcmp(g_peers[i].endpoint, endpoint) == 0) {
+            *out = g_peers[i];
+            rc = 0;
+            break;
+        }
+    }
+    pthread_mutex_unlock(&g_peers_lock);
+    return rc;
+}
+
+int junknas_stats_peer_snapshot(junknas_peer_traffic_t *out, int max) {
+    if (!out || max <= 0) return 0;
+    pthread_mutex_lock(&g_peers_lock);
+    int n = (g_peer_count < max) ? g_peer_count : max;
+    memcpy(out, g_peers, (size_t)n * sizeof(*out));
+    pthread_mutex_unlock(&g_peers_lock);
+    return n;
+}
+
+/* ------------------------------ Rendering ------------------------------- */
+
+void junknas_strbuf_printf(junknas_strbuf_t *sb, const char *fmt, ...) {
+    if (sb->failed) return;
+    for (;;) {
+        va_list args;
+        va_start(args, fmt);
+        int n = (sb->cap > 0) ? vsnprintf(sb->buf + sb->len, sb->cap - sb->len, fmt, args)
+                              : vsnprintf(NULL, 0, fmt, args);
+        va_end(args);
+        if (n < 0) {
+            sb->failed = 1;
+            return;
+        }
+        if ((size_t)n < sb->cap - sb->len) {
+            sb->len += (size_t)n;
+            return;
+        }
+        size_t cap = sb->cap * 2 + (size_t)n + 1;
+        char *grown = realloc(sb->buf, cap);
+        if (!grown) {
+            sb->failed = 1;
+            return;
+        }
+        sb->buf = grown;
+        sb->cap = cap;
+    }
+}
+
+void junknas_stats_escape_label(const char *in, char *out, size_t out_len) {
+    if (out_len == 0) return;
+    size_t o = 0;
+    for (const char *c = in ? in : ""; *c; c++) {
+        const char *rep = NULL;
+        if (*c == '\\') rep = "\\\\";
+        else if (*c == '"') rep = "\\\"";
+        else if (*c == '\n') rep = "\\n";
+        size_t need = rep ? 2 : 1;
+        if (o + need >= out_len) break;
+        if (rep) {
+            memcpy(out + o, rep, 2);
+        } else {
+            out[o] = *c;
+        }
+        o += need;
+    }
+    out[o] = '\0';
+}
+
+char *junknas_stats_render_prometheus(void) {
+    junknas_strbuf_t sb = {0};
+    sb.cap = 4096;
+    sb.buf = malloc(sb.cap);
+    if (!sb.buf) return NULL;
+    sb.buf[0] = '\0';
+
+    for (int i = 0; i < JUNKNAS_STAT_COUNTER_COUNT; i++) {
+        if (g_counter_info[i].help) {
+            junknas_strbuf_printf(&sb, "# HELP %s %s\n# TYPE %s counter\n",
+                                  g_counter_info[i].name, g_counter_info[i].help, g_counter_info[i].name);
+        }
+        junknas_strbuf_printf(&sb, "%s{%s} %llu\n", g_counter_info[i].name, g_counter_info[i].label,
+                              (unsigned long long)junknas_stats_get((junknas_stat_counter_t)i));
+    }
+
+    junknas_strbuf_printf(&sb, "# HELP junknas_fuse_op_duration_seconds FUSE operation latency.\n"
+                               "# TYPE junknas_fuse_op_duration_seconds histogram\n");
+    for (int op = 0; op < JUNKNAS_FUSE_OP_COUNT; op++) {
+        const fuse_histogram_t *h = &g_fuse_ops[op];
+        uint64_t cumulative = 0;
+        for (size_t b = 0; b < FUSE_BUCKET_COUNT; b++) {
+            cumulative += __atomic_load_n(&h->buckets[b], __ATOMIC_RELAXED);
+            junknas_strbuf_printf(&sb, "junknas_fuse_op_duration_seconds_bucket{op=\"%s\",le=\"%g\"} %llu\n",
+                                  g_fuse_op_names[op], (double)g_fuse_bucket_usec[b] / 1e6,
+                                  (unsigned long long)cumulative);
+        }
+        cumulative += __atomic_load_n(&h->buckets[FUSE_BUCKET_COUNT], __ATOMIC_RELAXED);
+        junknas_strbuf_printf(&sb, "junknas_fuse_op_duration_seconds_bucket{op=\"%s\",le=\"+Inf\"} %llu\n",
+                              g_fuse_op_names[op], (unsigned long long)cumulative);
+        junknas_strbuf_printf(&sb, "junknas_fuse_op_duration_seconds_sum{op=\"%s\"} %.6f\n",
+                              g_fuse_op_names[op],
+                              (double)__atomic_load_n(&h->sum_usec, __ATOMIC_RELAXED) / 1e6);
+        junknas_strbuf_printf(&sb, "junknas_fuse_op_duration_seconds_count{op=\"%s\"} %llu\n",
+                              g_fuse_op_names[op], (unsigned long long)cumulative);
+    }
+
+    junknas_strbuf_printf(&sb, "# HELP junknas_fuse_slow_ops_total FUSE operations that exceeded their latency budget.\n"
+                               "# TYPE junknas_fuse_slow_ops_total counter\n");
+    for (int op = 0; op < JUNKNAS_FUSE_OP_COUNT; op++) {
+        junknas_strbuf_printf(&sb, "junknas_fuse_slow_ops_total{op=\"%s\"} %llu\n", g_fuse_op_names[op],
+                              (unsigned long long)__atomic_load_n(&g_fuse_slow_ops[op], __ATOMIC_RELAXED));
+    }
+
+    junknas_peer_traffic_t peers[JUNKNAS_STATS_MAX_PEERS];
+    int peer_count = junknas_stats_peer_snapshot(peers, JUNKNAS_STATS_MAX_PEERS);
+    char peer_labels[JUNKNAS_STATS_MAX_PEERS][JUNKNAS_STATS_LABEL_LEN(MAX_ENDPOINT_LEN)];
+    for (int i = 0; i < peer_count; i++) {
+        junknas_stats_escape_label(peers[i].endpoint, peer_labels[i], sizeof(peer_labels[i]));
+    }
+    if (peer_count > 0) {
+        junknas_strbuf_printf(&sb, "# HELP junknas_peer_bytes_total HTTP bytes exchanged with each mesh peer.\n"
+                                   "# TYPE junknas_peer_bytes_total counter\n");
+        for (int i = 0; i < peer_count; i++) {
+            junknas_strbuf_printf(&sb, "junknas_peer_bytes_total{peer=\"%s\",direction=\"sent\"} %llu\n"
+                                       "junknas_peer_bytes_total{peer=\"%s\",direction=\"received\"} %llu\n",
+                                  peer_labels[i], (unsigned long long)peers[i].bytes_sent,
+                                  peer_labels[i], (unsigned long long)peers[i].bytes_received);
+        }
+        junknas_strbuf_printf(&sb, "# HELP junknas_peer_requests_total HTTP requests made to each mesh peer.\n"
+                                   "# TYPE junknas_peer_requests_total counter\n");
+        for (int i = 0; i < peer_count; i++) {
+            junknas_strbuf_printf(&sb, "junknas_peer_requests_total{peer=\"%s\",result=\"ok\"} %llu\n"
+                                       "junknas_peer_requests_total{peer=\"%s\",result=\"failed\"} %llu\n",
+                                  peer_labels[i],
+                                  (unsigned long long)(peers[i].requests - peers[i].failures),
+                                  peer_labels[i], (unsigned long long)peers[i].failures);
+        }
+    }
+
+    if (sb.failed) {
+        free(sb.buf);
+        return NULL;
+    }
+    return sb.buf;
+}
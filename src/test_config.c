@@ -17,17 +17,30 @@ static void dump_config(const junknas_config_t *cfg) {
     printf("  data_dir:        %s\n", cfg->data_dir);
     printf("  data_dirs (%zu):\n", cfg->data_dir_count);
     for (size_t i = 0; i < cfg->data_dir_count; i++) {
-        printf("    - %s\n", cfg->data_dirs[i]);
+        if (cfg->data_dir_caps[i] != 0) {
+            printf("    - %s (cap %zu bytes)\n", cfg->data_dirs[i], cfg->data_dir_caps[i]);
+        } else {
+            printf("    - %s\n", cfg->data_dirs[i]);
+        }
     }
     printf("  mount_point:     %s\n", cfg->mount_point);
     printf("  storage_size:    %s\n", cfg->storage_size);
     printf("  max_storage:     %zu bytes\n", cfg->max_storage_bytes);
+    printf("  quota_warn:      %d%%\n", cfg->quota_warn_percent);
+    printf("  drive_reserve:   %s\n", cfg->drive_reserve[0] ? cfg->drive_reserve : "(none)");
     printf("  web_port:        %u\n", cfg->web_port);
+    printf("  mesh_secret:     %s\n", cfg->mesh_secret[0] ? "(set)" : "(empty)");
+    printf("  require_enc:     %d\n", cfg->require_encrypted_mesh);
     printf("  node_state:      %s\n", cfg->node_state);
+    printf("  read_mode:       %s\n", cfg->read_mode);
+    printf("  endpoint_pref:   %s\n", cfg->endpoint_preference);
+    printf("  max_json_body:   %zu bytes\n", cfg->max_json_body_bytes);
+    printf("  max_chunk_body:  %zu bytes\n", cfg->max_chunk_body_bytes);
 
     printf("  verbose:         %d\n", cfg->verbose);
     printf("  enable_fuse:     %d\n", cfg->enable_fuse);
     printf("  daemon_mode:     %d\n", cfg->daemon_mode);
+    printf("  config_ro:       %d\n", cfg->config_read_only);
 
     printf("  WireGuard:\n");
     printf("    interface:     %s\n", cfg->wg.interface_name);
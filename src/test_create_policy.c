@@ -0,0 +1,155 @@
+/*
+ * junkNAS - create policy test harness
+ *
+ * This is a simple test program to verify:
+ *  - (requested & mask) | force, whatever umask the client used
+ *  - Policy text round-trips and bad values are refused
+ *  - Resolution walks up to the nearest policy, which covers deeper
+ *    directories only when it inherits
+ *  - The mode a create stores is the effective one, not the requested one
+ *
+ * It works on a scratch backing dir under /tmp and needs no mount.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#include "create_policy.h"
+
+static char g_root[64];
+
+static int write_policy(const char *rel, const char *text) {
+    char path[256];
+    snprintf(path, sizeof(path), "%s%s/%s", g_root, rel, JUNKNAS_CREATE_POLICY_FILE);
+    FILE *f = fopen(path, "wb");
+    if (!f) return 1;
+    fputs(text, f);
+    fclose(f);
+    return 0;
+}
+
+static int test_mode_math(void) {
+    junknas_create_policy_t p;
+    junknas_create_policy_default(&p);
+    if (junknas_create_policy_mode(&p, 0640) != 0640 || junknas_create_policy_mode(NULL, 0100755) != 0755) {
+        fprintf(stderr, "Default policy changed the mode.\n");
+        return 1;
+    }
+
+    /* Group-writable share: clients with umask 022 or 077 end up alike. */
+    p.mask = 0775;
+    p.force = 0660;
+    if (junknas_create_policy_mode(&p, 0644) != 0664 || junknas_create_policy_mode(&p, 0600) != 0660 ||
+        junknas_create_policy_mode(&p, 0777) != 0775) {
+        fprintf(stderr, "Mask/force math is wrong.\n");
+        return 1;
+    }
+    return 0;
+}
+
+static int test_parse_format(void) {
+    junknas_create_policy_t p;
+    const char *text = "mask 0750\nforce 0640\ngroup 100\ninherit 1\n";
+    if (junknas_create_policy_parse(text, strlen(text), &p) != 0 ||
+        p.mask != 0750 || p.force != 0640 || !p.has_group || p.gid != 100 || !p.inherit) {
+        fprintf(stderr, "Policy text did not parse.\n");
+        return 1;
+    }
+    char out[JUNKNAS_CREATE_POLICY_TEXT_LEN];
+    if (junknas_create_policy_format(&p, out, sizeof(out)) < 0 || strcmp(out, text) != 0) {
+        fprintf(stderr, "Policy text did not round-trip: %s\n", out);
+        return 1;
+    }
+
+    const char *bad[] = { "mask 01000\n", "force 9\n", "inherit yes\n", "colour blue\n", "mask 0755 0644\n" };
+    for (size_t i = 0; i < sizeof(bad) / sizeof(bad[0]); i++) {
+        if (junknas_create_policy_parse(bad[i], strlen(bad[i]), &p) == 0) {
+            fprintf(stderr, "Accepted bad policy: %s", bad[i]);
+            return 1;
+        }
+    }
+    return 0;
+}
+
+static int expect_resolve(const char *rel, int want, mode_t want_mask) {
+    char path[256];
+    snprintf(path, sizeof(path), "%s%s", g_root, rel);
+    junknas_create_policy_t p;
+    int got = junknas_create_policy_resolve(g_root, path, &p);
+    if (got != want || (got == 1 && p.mask != want_mask)) {
+        fprintf(stderr, "Resolve %s: got %d (mask %04o), want %d (mask %04o)\n",
+                rel, got, got == 1 ? (unsigned int)p.mask : 0U, want, (unsigned int)want_mask);
+        return 1;
+    }
+    return 0;
+}
+
+/* root (inherit, 0770) / a / b (no inherit, 0700) / c */
+static int test_resolve(void) {
+    char dir[256];
+    const char *dirs[] = { "/a", "/a/b", "/a/b/c" };
+    for (size_t i = 0; i < 3; i++) {
+        snprintf(dir, sizeof(dir), "%s%s", g_root, dirs[i]);
+        if (mkdir(dir, 0755) != 0) return 1;
+    }
+
+    if (expect_resolve("/a/new", 0, 0) != 0) return 1;
+    if (write_policy("", "mask 0770\ninherit 1\n") != 0) return 1;
+    if (expect_resolve("/new", 1, 0770) != 0) return 1;
+    if (expect_resolve("/a/b/c/new", 1, 0770) != 0) return 1;
+
+    /* The nearest policy decides, even when it doesn't reach further down. */
+    if (write_policy("/a/b", "mask 0700\n") != 0) return 1;
+    if (expect_resolve("/a/b/new", 1, 0700) != 0) return 1;
+    if (expect_resolve("/a/b/c/new", 0, 0) != 0) return 1;
+    if (expect_resolve("/a/new", 1, 0770) != 0) return 1;
+
+    if (write_policy("/a", "bogus\n") != 0) return 1;
+    if (expect_resolve("/a/new", -1, 0) != 0) return 1;
+    return 0;
+}
+
+/* What create/mkdir store: resolve for the new entry, then apply. */
+static int test_effective_mode(void) {
+    if (write_policy("/a", "mask 0775\nforce 0664\ngroup 100\n") != 0) return 1;
+    char path[256];
+    snprintf(path, sizeof(path), "%s/a/report.txt", g_root);
+    junknas_create_policy_t p;
+    if (junknas_create_policy_resolve(g_root, path, &p) != 1) {
+        fprintf(stderr, "No policy for a new file.\n");
+        return 1;
+    }
+    mode_t requested = 0600;
+    mode_t effective = junknas_create_policy_mode(&p, requested);
+    if (effective != 0664 || !p.has_group || p.gid != 100) {
+        fprintf(stderr, "Created file would get %04o instead of 0664.\n", (unsigned int)effective);
+        return 1;
+    }
+    return 0;
+}
+
+static void cleanup(void) {
+    char cmd[128];
+    snprintf(cmd, sizeof(cmd), "rm -rf '%s'", g_root);
+    if (system(cmd) != 0) fprintf(stderr, "Could not remove %s\n", g_root);
+}
+
+int main(void) {
+    snprintf(g_root, sizeof(g_root), "/tmp/junknas_policy_XXXXXX");
+    if (!mkdtemp(g_root)) {
+        perror("mkdtemp");
+        return 1;
+    }
+
+    int rc = 0;
+    if (test_mode_math() != 0 || test_parse_format() != 0 || test_resolve() != 0 ||
+        test_effective_mode() != 0) {
+        rc = 1;
+    }
+    cleanup();
+    if (rc == 0) printf("Create policy test passed.\n");
+    return rc;
+}
@@ -0,0 +1,481 @@
+/*
+ * junkNAS - mesh chunk auth test harness
+ *
+ * This is a simple test program to verify:
+ *  - A freshly signed /chunks request is accepted
+ *  - Missing, wrong, stale and replayed signatures are refused
+ *  - A full replay cache still accepts fresh signatures
+ *  - An empty mesh_secret refuses everything
+ *  - Chunk fetches from a local fake peer: a served chunk arrives within
+ *    the retry budget, a missing one fails fast, a stalled peer times out
+ *  - A sync payload listing one endpoint twice keeps only the later entry
+ *  - Mesh sync tries a peer's wg_ip before its advertised endpoint
+ *  - A peer's traffic is found under every address it was dialed at
+ *  - A link only counts as encrypted when its wg_ip is routed through
+ *    WireGuard, and require_encrypted_mesh keeps chunks off any other link
+ */
+
+#include <arpa/inet.h>
+#include <errno.h>
+#include <netinet/in.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <time.h>
+#include <unistd.h>
+
+#include "mesh.h"
+#include "net.h"
+
+#define TEST_SECRET "correct horse battery staple"
+#define TEST_PATH   "/chunks/0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef"
+
+static int sign(const char *secret, const char *method, const char *path, uint64_t ts,
+                const char *nonce, char *out, size_t out_len) {
+    if (junknas_mesh_auth_sign(secret, method, path, ts, nonce, out, out_len) != 0) {
+        fprintf(stderr, "Signing failed.\n");
+        return 1;
+    }
+    return 0;
+}
+
+static int expect_verify(const char *what, const char *secret, const char *method, const char *path,
+                         const char *value, uint64_t now, int want) {
+    int got = junknas_mesh_auth_verify(secret, method, path, value, now);
+    if (got != want) {
+        fprintf(stderr, "%s: verify returned %d, expected %d.\n", what, got, want);
+        return 1;
+    }
+    return 0;
+}
+
+static int test_valid_and_replayed(uint64_t now) {
+    char value[128];
+    if (sign(TEST_SECRET, "GET", TEST_PATH, now, "00112233445566778899aabbccddeeff", value, sizeof(value))) return 1;
+    if (expect_verify("valid", TEST_SECRET, "GET", TEST_PATH, value, now, 0)) return 1;
+    if (expect_verify("replayed", TEST_SECRET, "GET", TEST_PATH, value, now + 1, -1)) return 1;
+
+    /* Same second, fresh nonce: a second legitimate request still passes. */
+    if (sign(TEST_SECRET, "GET", TEST_PATH, now, "ffeeddccbbaa99887766554433221100", value, sizeof(value))) return 1;
+    return expect_verify("second nonce", TEST_SECRET, "GET", TEST_PATH, value, now, 0);
+}
+
+/* More signatures inside one window than the replay cache holds, 128 a
+ * second: every fresh one still passes, and the first, dropped to make room,
+ * still can't be replayed.
+ */
+static int test_replay_cache_full(uint64_t now) {
+    char first[128], value[128], nonce[32];
+    for (int i = 0; i < 16384 + 256; i++) {
+        uint64_t ts = now - 200 + (uint64_t)(i / 128);
+        snprintf(nonce, sizeof(nonce), "%08x", (unsigned)i);
+        if (sign(TEST_SECRET, "GET", TEST_PATH, ts, nonce, value, sizeof(value))) return 1;
+        if (i == 0) snprintf(first, sizeof(first), "%s", value);
+        if (junknas_mesh_auth_verify(TEST_SECRET, "GET", TEST_PATH, value, now) != 0) {
+            fprintf(stderr, "full cache: fresh signature %d refused.\n", i);
+            return 1;
+        }
+    }
+    if (expect_verify("full cache, replay of the first", TEST_SECRET, "GET", TEST_PATH, first, now, -1)) return 1;
+    if (expect_verify("full cache, replay of the last", TEST_SECRET, "GET", TEST_PATH, value, now, -1)) return 1;
+
+    if (sign(TEST_SECRET, "GET", TEST_PATH, now, "a1a1a1a1", value, sizeof(value))) return 1;
+    return expect_verify("full cache, newer signature", TEST_SECRET, "GET", TEST_PATH, value, now, 0);
+}
+
+static int test_missing(uint64_t now) {
+    if (expect_verify("missing", TEST_SECRET, "GET", TEST_PATH, NULL, now, -1)) return 1;
+    if (expect_verify("empty", TEST_SECRET, "GET", TEST_PATH, "", now, -1)) return 1;
+
+    char value[128];
+    snprintf(value, sizeof(value), "%llu", (unsigned long long)now);
+    if (expect_verify("timestamp only", TEST_SECRET, "GET", TEST_PATH, value, now, -1)) return 1;
+    snprintf(value, sizeof(value), "%llu:0011", (unsigned long long)now);
+    return expect_verify("no hmac", TEST_SECRET, "GET", TEST_PATH, value, now, -1);
+}
+
+static int test_wrong(uint64_t now) {
+    char value[128];
+    if (sign("another secret", "GET", TEST_PATH, now, "0a0a0a0a", value, sizeof(value))) return 1;
+    if (expect_verify("wrong secret", TEST_SECRET, "GET", TEST_PATH, value, now, -1)) return 1;
+
+    if (sign(TEST_SECRET, "GET", TEST_PATH, now, "0b0b0b0b", value, sizeof(value))) return 1;
+    if (expect_verify("wrong method", TEST_SECRET, "POST", TEST_PATH, value, now, -1)) return 1;
+    if (expect_verify("wrong path", TEST_SECRET, "GET", "/chunks/00", value, now, -1)) return 1;
+
+    /* Flip one hex digit of the HMAC. */
+    size_t len = strlen(value);
+    value[len - 1] = (value[len - 1] == '0') ? '1' : '0';
+    if (expect_verify("tampered hmac", TEST_SECRET, "GET", TEST_PATH, value, now, -1)) return 1;
+
+    /* Swap in another nonce under the same HMAC. */
+    if (sign(TEST_SECRET, "GET", TEST_PATH, now, "0c0c0c0c", value, sizeof(value))) return 1;
+    char *nonce = strchr(value, ':') + 1;
+    nonce[0] = 'd';
+    return expect_verify("tampered nonce", TEST_SECRET, "GET", TEST_PATH, value, now, -1);
+}
+
+static int test_stale(uint64_t now) {
+    char value[128];
+    uint64_t old = now - JUNKNAS_MESH_AUTH_WINDOW_SEC - 1;
+    if (sign(TEST_SECRET, "GET", TEST_PATH, old, "0d0d0d0d", value, sizeof(value))) return 1;
+    if (expect_verify("stale", TEST_SECRET, "GET", TEST_PATH, value, now, -1)) return 1;
+
+    uint64_t future = now + JUNKNAS_MESH_AUTH_WINDOW_SEC + 1;
+    if (sign(TEST_SECRET, "GET", TEST_PATH, future, "0e0e0e0e", value, sizeof(value))) return 1;
+    return expect_verify("future", TEST_SECRET, "GET", TEST_PATH, value, now, -1);
+}
+
+static int test_no_secret(uint64_t now) {
+    char value[128];
+    if (junknas_mesh_auth_sign("", "GET", TEST_PATH, now, "0f0f0f0f", value, sizeof(value)) == 0) {
+        fprintf(stderr, "Signing with an empty secret succeeded.\n");
+        return 1;
+    }
+    if (sign(TEST_SECRET, "GET", TEST_PATH, now, "10101010", value, sizeof(value))) return 1;
+    return expect_verify("empty secret", "", "GET", TEST_PATH, value, now, -1);
+}
+
+/* ------------------------------ Fetch harness ---------------------------- */
+
+#define GOOD_HASH    "1111111111111111111111111111111111111111111111111111111111111111"
+#define MISSING_HASH "2222222222222222222222222222222222222222222222222222222222222222"
+#define GOOD_BODY    "chunk bytes from the fake peer"
+
+/* Wall-clock budget for one fetch: well under a soft read's 3 attempts. */
+#define FETCH_FAST_MS  1000
+#define FETCH_STALL_MS 3000
+
+typedef struct {
+    int fd;
+    uint16_t port;
+    pthread_t thread;
+    volatile int stop;
+    volatile int requests;
+} fake_peer_t;
+
+static int fake_peer_listen(fake_peer_t *peer) {
+    peer->fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (peer->fd < 0) return -1;
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t len = sizeof(addr);
+    if (bind(peer->fd, (struct sockaddr *)&addr, sizeof(addr)) != 0 || listen(peer->fd, 8) != 0 ||
+        getsockname(peer->fd, (struct sockaddr *)&addr, &len) != 0) {
+        close(peer->fd);
+        return -1;
+    }
+    peer->port = ntohs(addr.sin_port);
+    return 0;
+}
+
+/* Answers GET /chunks/GOOD_HASH with GOOD_BODY and anything else with 404. */
+static void *fake_peer_serve(void *arg) {
+    fake_peer_t *peer = (fake_peer_t *)arg;
+    while (!peer->stop) {
+        int c = accept(peer->fd, NULL, NULL);
+        if (c < 0) {
+            if (errno == EINTR) continue;
+            break;
+        }
+        char req[4096];
+        size_t used = 0;
+        while (used + 1 < sizeof(req)) {
+            ssize_t n = recv(c, req + used, sizeof(req) - 1 - used, 0);
+            if (n <= 0) break;
+            used += (size_t)n;
+            req[used] = '\0';
+            if (strstr(req, "\r\n\r\n")) break;
+        }
+        req[used] = '\0';
+        peer->requests++;
+
+        char resp[256];
+        if (strncmp(req, "GET /chunks/" GOOD_HASH " ", 12 + 64 + 1) == 0) {
+            snprintf(resp, sizeof(resp), "HTTP/1.1 200 OK\r\nContent-Length: %zu\r\nConnection: close\r\n\r\n%s",
+                     strlen(GOOD_BODY), GOOD_BODY);
+        } else {
+            snprintf(resp, sizeof(resp), "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        }
+        (void)send(c, resp, strlen(resp), MSG_NOSIGNAL);
+        close(c);
+    }
+    return NULL;
+}
+
+static void fake_peer_stop(fake_peer_t *peer, int serving) {
+    peer->stop = 1;
+    shutdown(peer->fd, SHUT_RDWR);
+    if (serving) pthread_join(peer->thread, NULL);
+    close(peer->fd);
+}
+
+static uint64_t now_ms(void) {
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return (uint64_t)ts.tv_sec * 1000ULL + (uint64_t)ts.tv_nsec / 1000000ULL;
+}
+
+static void point_at_peer(junknas_config_t *cfg, uint16_t port) {
+    junknas_wg_peer_t peer;
+    memset(&peer, 0, sizeof(peer));
+    snprintf(peer.public_key, sizeof(peer.public_key), "fake-peer-key");
+    snprintf(peer.wg_ip, sizeof(peer.wg_ip), "127.0.0.1");
+    peer.web_port = port;
+    junknas_config_lock(cfg);
+    (void)junknas_config_set_wg_peers(cfg, &peer, 1);
+    junknas_config_unlock(cfg);
+}
+
+static int expect_fetch(junknas_mesh_t *mesh, const char *what, const char *hash, const char *dest,
+                        int want_rc, uint64_t max_ms) {
+    uint64_t t0 = now_ms();
+    int rc = junknas_mesh_fetch_chunk(mesh, hash, dest);
+    uint64_t took = now_ms() - t0;
+    if (rc != want_rc) {
+        fprintf(stderr, "%s: fetch returned %d, expected %d.\n", what, rc, want_rc);
+        return 1;
+    }
+    if (took > max_ms) {
+        fprintf(stderr, "%s: fetch took %llu ms, budget %llu ms.\n", what,
+                (unsigned long long)took, (unsigned long long)max_ms);
+        return 1;
+    }
+    return 0;
+}
+
+/* What /mesh/status shows for a peer that also advertises an endpoint:
+ * the fetches made to its wg_ip above.
+ */
+static int expect_peer_traffic(uint16_t port) {
+    junknas_wg_peer_t peer;
+    memset(&peer, 0, sizeof(peer));
+    snprintf(peer.wg_ip, sizeof(peer.wg_ip), "127.0.0.1");
+    snprintf(peer.endpoint, sizeof(peer.endpoint), "192.0.2.1:51820");
+    peer.web_port = port;
+
+    junknas_peer_traffic_t traffic;
+    if (junknas_mesh_peer_traffic(&peer, 9090, &traffic) != 0 || traffic.requests < 2 ||
+        traffic.bytes_received == 0) {
+        fprintf(stderr, "peer traffic: fetches to the wg_ip were not counted for the peer.\n");
+        return 1;
+    }
+
+    snprintf(peer.wg_ip, sizeof(peer.wg_ip), "10.99.0.9");
+    if (junknas_mesh_peer_traffic(&peer, 9090, &traffic) == 0) {
+        fprintf(stderr, "peer traffic: another peer got its counters.\n");
+        return 1;
+    }
+    return 0;
+}
+
+static int test_fetch_harness(void) {
+    /* Just what the mesh reads; junknas_config_init would write WireGuard
+     * keys to the user's config file.
+     */
+    static junknas_config_t cfg;
+    memset(&cfg, 0, sizeof(cfg));
+    pthread_mutex_init(&cfg.lock, NULL);
+    snprintf(cfg.mesh_secret, sizeof(cfg.mesh_secret), "%s", TEST_SECRET);
+    snprintf(cfg.endpoint_preference, sizeof(cfg.endpoint_preference), "auto");
+    snprintf(cfg.mount_point, sizeof(cfg.mount_point), "/mnt/junknas-test");
+
+    fake_peer_t serving = {0}, stalled = {0};
+    if (fake_peer_listen(&serving) != 0 || fake_peer_listen(&stalled) != 0 ||
+        pthread_create(&serving.thread, NULL, fake_peer_serve, &serving) != 0) {
+        fprintf(stderr, "Fake peer setup failed.\n");
+        return 1;
+    }
+
+    char dest[] = "/tmp/junknas_test_chunk_XXXXXX";
+    int dfd = mkstemp(dest);
+    if (dfd < 0) {
+        fprintf(stderr, "mkstemp failed.\n");
+        return 1;
+    }
+    close(dfd);
+
+    point_at_peer(&cfg, serving.port);
+    junknas_mesh_t *mesh = junknas_mesh_start(&cfg);
+    if (!mesh) {
+        fprintf(stderr, "Mesh start failed.\n");
+        return 1;
+    }
+
+    int rc = expect_fetch(mesh, "served chunk", GOOD_HASH, dest, 0, FETCH_FAST_MS);
+    if (rc == 0) {
+        char got[64] = {0};
+        FILE *f = fopen(dest, "rb");
+        size_t n = f ? fread(got, 1, sizeof(got) - 1, f) : 0;
+        if (f) fclose(f);
+        if (n != strlen(GOOD_BODY) || memcmp(got, GOOD_BODY, n) != 0) {
+            fprintf(stderr, "served chunk: wrong body \"%s\".\n", got);
+            rc = 1;
+        }
+    }
+    if (rc == 0) rc = expect_fetch(mesh, "missing chunk", MISSING_HASH, dest, -ENOENT, FETCH_FAST_MS);
+    if (rc == 0) rc = expect_peer_traffic(serving.port);
+
+    /* Loopback is no WireGuard link: with the policy on, the peer isn't asked. */
+    if (rc == 0) {
+        int before = serving.requests;
+        junknas_config_lock(&cfg);
+        cfg.require_encrypted_mesh = 1;
+        junknas_config_unlock(&cfg);
+        rc = expect_fetch(mesh, "require_encrypted_mesh", GOOD_HASH, dest, -EACCES, FETCH_FAST_MS);
+        if (rc == 0 && serving.requests != before) {
+            fprintf(stderr, "require_encrypted_mesh: the peer was still sent a request.\n");
+            rc = 1;
+        }
+        junknas_config_lock(&cfg);
+        cfg.require_encrypted_mesh = 0;
+        junknas_config_unlock(&cfg);
+    }
+
+    /* Accepts connections (the kernel backlog does) but never answers. */
+    if (rc == 0) {
+        point_at_peer(&cfg, stalled.port);
+        rc = expect_fetch(mesh, "stalled peer", GOOD_HASH, dest, -EAGAIN, FETCH_STALL_MS);
+    }
+
+    junknas_mesh_stop(mesh);
+    fake_peer_stop(&serving, 1);
+    fake_peer_stop(&stalled, 0);
+    junknas_config_cleanup(&cfg);
+    (void)unlink(dest);
+    return rc;
+}
+
+static int test_collapse_duplicates(void) {
+    junknas_wg_peer_t peers[4] = {0};
+    snprintf(peers[0].endpoint, sizeof(peers[0].endpoint), "10.0.0.1:51820");
+    peers[0].web_port = 1;
+    snprintf(peers[1].endpoint, sizeof(peers[1].endpoint), "10.0.0.2:51820");
+    snprintf(peers[2].endpoint, sizeof(peers[2].endpoint), "10.0.0.1:51820");
+    peers[2].web_port = 2;
+    snprintf(peers[3].wg_ip, sizeof(peers[3].wg_ip), "10.99.0.4");
+
+    int count = 4;
+    int dropped = junknas_collapse_duplicate_peers(peers, &count);
+    if (dropped != 1 || count != 3) {
+        fprintf(stderr, "collapse: dropped %d, kept %d\n", dropped, count);
+        return 1;
+    }
+    if (strcmp(peers[0].endpoint, "10.0.0.2:51820") != 0 ||
+        strcmp(peers[1].endpoint, "10.0.0.1:51820") != 0 || peers[1].web_port != 2 ||
+        strcmp(peers[2].wg_ip, "10.99.0.4") != 0) {
+        fprintf(stderr, "collapse: the later entry did not win\n");
+        return 1;
+    }
+    return 0;
+}
+
+static int expect_candidate(const junknas_mesh_candidate_t *c, const char *host, uint16_t port) {
+    if (strcmp(c->host, host) != 0 || c->port != port) {
+        fprintf(stderr, "candidates: got %s:%u, want %s:%u\n", c->host, c->port, host, port);
+        return 1;
+    }
+    return 0;
+}
+
+static int test_sync_candidates(void) {
+    junknas_wg_peer_t peer = {0};
+    junknas_mesh_candidate_t c[JUNKNAS_MESH_MAX_CANDIDATES];
+
+    snprintf(peer.endpoint, sizeof(peer.endpoint), "192.168.1.20:8080");
+    if (junknas_mesh_peer_candidates(&peer, 8080, c) != 1 ||
+        expect_candidate(&c[0], "192.168.1.20", 8080) != 0) {
+        fprintf(stderr, "candidates: endpoint-only peer\n");
+        return 1;
+    }
+
+    snprintf(peer.wg_ip, sizeof(peer.wg_ip), "10.99.0.2");
+    peer.web_port = 9090;
+    if (junknas_mesh_peer_candidates(&peer, 8080, c) != 2 ||
+        expect_candidate(&c[0], "10.99.0.2", 9090) != 0 ||
+        expect_candidate(&c[1], "192.168.1.20", 8080) != 0) {
+        fprintf(stderr, "candidates: wg_ip should come first\n");
+        return 1;
+    }
+
+    snprintf(peer.endpoint, sizeof(peer.endpoint), "10.99.0.2:9090");
+    if (junknas_mesh_peer_candidates(&peer, 8080, c) != 1) {
+        fprintf(stderr, "candidates: duplicate address listed twice\n");
+        return 1;
+    }
+
+    peer.endpoint[0] = '\0';
+    peer.web_port = 0;
+    if (junknas_mesh_peer_candidates(&peer, 8080, c) != 1 ||
+        expect_candidate(&c[0], "10.99.0.2", 8080) != 0) {
+        fprintf(stderr, "candidates: wg_ip-only peer\n");
+        return 1;
+    }
+    return 0;
+}
+
+static int expect_security(const char *what, const junknas_wg_peer_t *peer, int have_secret, int wg_routed,
+                           junknas_mesh_link_security_t want) {
+    junknas_mesh_link_security_t got = junknas_mesh_peer_security(peer, have_secret, wg_routed);
+    if (got != want) {
+        fprintf(stderr, "security %s: got %s, want %s\n", what, junknas_mesh_link_security_name(got),
+                junknas_mesh_link_security_name(want));
+        return 1;
+    }
+    return 0;
+}
+
+static int test_link_security(void) {
+    junknas_wg_peer_t peer = {0};
+    snprintf(peer.endpoint, sizeof(peer.endpoint), "192.168.1.20:51820");
+    if (expect_security("LAN only", &peer, 0, 0, JUNKNAS_MESH_LINK_PLAINTEXT) != 0 ||
+        expect_security("LAN only, signed", &peer, 1, 0, JUNKNAS_MESH_LINK_AUTHENTICATED) != 0) {
+        return 1;
+    }
+
+    /* Having a wg_ip isn't enough; the route to it has to be the tunnel. */
+    snprintf(peer.wg_ip, sizeof(peer.wg_ip), "10.99.0.2");
+    if (expect_security("wg_ip, tunnel down", &peer, 1, 0, JUNKNAS_MESH_LINK_AUTHENTICATED) != 0 ||
+        expect_security("wg_ip, tunnel down, unsigned", &peer, 0, 0, JUNKNAS_MESH_LINK_PLAINTEXT) != 0 ||
+        expect_security("wg_ip over WireGuard", &peer, 1, 1, JUNKNAS_MESH_LINK_ENCRYPTED) != 0) {
+        return 1;
+    }
+
+    if (junknas_net_route_is_wireguard("127.0.0.1") != 0) {
+        fprintf(stderr, "security: loopback counted as a WireGuard route\n");
+        return 1;
+    }
+    if (junknas_net_route_is_wireguard("not-an-address") != -1) {
+        fprintf(stderr, "security: a hostname was accepted as a route address\n");
+        return 1;
+    }
+    struct sockaddr_in lo = { .sin_family = AF_INET };
+    lo.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    if (junknas_net_addr_on_wireguard((const struct sockaddr *)&lo) != 0) {
+        fprintf(stderr, "security: 127.0.0.1 counted as a WireGuard address\n");
+        return 1;
+    }
+    return 0;
+}
+
+int main(void) {
+    uint64_t now = 1700000000ULL;
+
+    if (test_valid_and_replayed(now) != 0) return 1;
+    if (test_missing(now) != 0) return 1;
+    if (test_wrong(now) != 0) return 1;
+    if (test_stale(now) != 0) return 1;
+    if (test_no_secret(now) != 0) return 1;
+    if (test_replay_cache_full(now) != 0) return 1;
+    if (test_fetch_harness() != 0) return 1;
+    if (test_collapse_duplicates() != 0) return 1;
+    if (test_sync_candidates() != 0) return 1;
+    if (test_link_security() != 0) return 1;
+
+    printf("Mesh test passed.\n");
+    return 0;
+}
@@ -0,0 +1,232 @@
+/*
+ * junkNAS - rsync acceptance test
+ *
+ * This is a simple test program to verify, against a real mount:
+ *  - A second `rsync -a` of the same tree transfers nothing
+ *  - Times set while the file is open survive its close, and a later
+ *    write through another handle gets a new mtime instead of them
+ *
+ * It mounts bin/junknas_fuse (or the binary given as argv[1]) on a scratch
+ * dir under /tmp with the kernel caches off. Without /dev/fuse, rsync or
+ * fusermount3 it prints why and passes.
+ */
+
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define OLD_MTIME 1000000000 /* 2001-09-09, far from the time of any save */
+
+static char g_root[64];
+static char g_src[128];
+static char g_mnt[128];
+
+static int have_tool(const char *name) {
+    char cmd[128];
+    snprintf(cmd, sizeof(cmd), "command -v %s >/dev/null 2>&1", name);
+    return system(cmd) == 0;
+}
+
+static int write_file(const char *path, size_t size, unsigned char seed) {
+    FILE *f = fopen(path, "wb");
+    if (!f) return 1;
+    for (size_t i = 0; i < size; i++) fputc((int)((seed + i * 31) & 0xff), f);
+    return fclose(f) != 0;
+}
+
+static int write_config(int web_port) {
+    char path[192];
+    snprintf(path, sizeof(path), "%s/config.json", g_root);
+    FILE *f = fopen(path, "wb");
+    if (!f) return 1;
+    fprintf(f,
+            "{\n"
+            "  \"data_dir\": \"%s/data\",\n"
+            "  \"mount_point\": \"%s\",\n"
+            "  \"web_port\": %d,\n"
+            "  \"enable_fuse\": true,\n"
+            "  \"bootstrap_peers\": []\n"
+            "}\n",
+            g_root, g_mnt, web_port);
+    return fclose(f) != 0;
+}
+
+static int is_mounted(void) {
+    struct stat root, mnt;
+    return stat(g_root, &root) == 0 && stat(g_mnt, &mnt) == 0 && root.st_dev != mnt.st_dev;
+}
+
+static pid_t start_daemon(const char *binary) {
+    char config[192], log[192];
+    snprintf(config, sizeof(config), "%s/config.json", g_root);
+    snprintf(log, sizeof(log), "%s/junknas.log", g_root);
+
+    pid_t pid = fork();
+    if (pid < 0) return -1;
+    if (pid == 0) {
+        int fd = open(log, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+        if (fd >= 0) {
+            dup2(fd, STDOUT_FILENO);
+            dup2(fd, STDERR_FILENO);
+            close(fd);
+        }
+        execl(binary, binary, "--attr-ttl", "0", "--entry-ttl", "0", config, (char *)NULL);
+        _exit(127);
+    }
+
+    for (int i = 0; i < 100; i++) {
+        if (is_mounted()) return pid;
+        if (waitpid(pid, NULL, WNOHANG) == pid) break;
+        usleep(100000);
+    }
+    fprintf(stderr, "Mount did not come up; see %s\n", log);
+    kill(pid, SIGTERM);
+    waitpid(pid, NULL, 0);
+    return -1;
+}
+
+static void stop_daemon(pid_t pid) {
+    char cmd[192];
+    snprintf(cmd, sizeof(cmd), "fusermount3 -u '%s'", g_mnt);
+    if (system(cmd) != 0) kill(pid, SIGTERM);
+    waitpid(pid, NULL, 0);
+}
+
+/* Number of regular files an `rsync -a --stats` of src onto the mount sent. */
+static int rsync_transferred(void) {
+    char cmd[320];
+    snprintf(cmd, sizeof(cmd), "rsync -a --stats '%s/' '%s/' 2>&1", g_src, g_mnt);
+    FILE *p = popen(cmd, "r");
+    if (!p) return -1;
+    int n = -1;
+    char line[256];
+    while (fgets(line, sizeof(line), p)) {
+        (void)sscanf(line, "Number of regular files transferred: %d", &n);
+    }
+    if (pclose(p) != 0) return -1;
+    return n;
+}
+
+static int test_rsync_twice(void) {
+    char path[192];
+    snprintf(path, sizeof(path), "%s/small.txt", g_src);
+    if (write_file(path, 100, 1) != 0) return 1;
+    /* Crosses a chunk boundary and ends mid-chunk. */
+    snprintf(path, sizeof(path), "%s/big.bin", g_src);
+    if (write_file(path, 1536 * 1024, 2) != 0) return 1;
+
+    int first = rsync_transferred();
+    if (first != 2) {
+        fprintf(stderr, "First rsync transferred %d files, expected 2.\n", first);
+        return 1;
+    }
+    int second = rsync_transferred();
+    if (second != 0) {
+        fprintf(stderr, "Second rsync transferred %d files, expected 0.\n", second);
+        return 1;
+    }
+    return 0;
+}
+
+/* close(2) doesn't wait for FUSE release, which is what saves the file:
+ * poll until the mtime is (or isn't) OLD_MTIME.
+ */
+static int wait_old_mtime(const char *path, int want_old) {
+    for (int i = 0; i < 50; i++) {
+        struct stat st;
+        if (stat(path, &st) == 0 && (st.st_mtime == OLD_MTIME) == want_old) return 0;
+        usleep(100000);
+    }
+    return 1;
+}
+
+/* cp -p style: write, set the times, close; with a second handle kept open
+ * over all of it and written to afterwards.
+ */
+static int test_times_then_write(void) {
+    char path[192];
+    snprintf(path, sizeof(path), "%s/touched.txt", g_mnt);
+    int keep = open(path, O_WRONLY | O_CREAT, 0644);
+    if (keep < 0) {
+        perror("open");
+        return 1;
+    }
+
+    int fd = open(path, O_WRONLY);
+    struct timespec times[2] = { { OLD_MTIME, 0 }, { OLD_MTIME, 0 } };
+    if (fd < 0 || write(fd, "copied\n", 7) != 7 || futimens(fd, times) != 0 || close(fd) != 0) {
+        perror("copy");
+        close(keep);
+        return 1;
+    }
+    if (wait_old_mtime(path, 1) != 0) {
+        fprintf(stderr, "Times set while open were lost on close.\n");
+        close(keep);
+        return 1;
+    }
+
+    if (pwrite(keep, "changed\n", 8, 0) != 8 || close(keep) != 0) {
+        perror("write");
+        return 1;
+    }
+    if (wait_old_mtime(path, 0) != 0) {
+        fprintf(stderr, "A write after the times were set kept the old mtime.\n");
+        return 1;
+    }
+    return 0;
+}
+
+static void cleanup(void) {
+    char cmd[128];
+    snprintf(cmd, sizeof(cmd), "rm -rf '%s'", g_root);
+    if (system(cmd) != 0) fprintf(stderr, "Could not remove %s\n", g_root);
+}
+
+int main(int argc, char **argv) {
+    const char *binary = (argc > 1) ? argv[1] : "bin/junknas_fuse";
+    if (access("/dev/fuse", R_OK | W_OK) != 0 || !have_tool("rsync") || !have_tool("fusermount3")) {
+        printf("Rsync test skipped (needs /dev/fuse, rsync and fusermount3).\n");
+        return 0;
+    }
+    if (access(binary, X_OK) != 0) {
+        fprintf(stderr, "%s: %s\n", binary, strerror(errno));
+        return 1;
+    }
+
+    snprintf(g_root, sizeof(g_root), "/tmp/junknas_rsync_XXXXXX");
+    if (!mkdtemp(g_root)) {
+        perror("mkdtemp");
+        return 1;
+    }
+    snprintf(g_src, sizeof(g_src), "%s/src", g_root);
+    snprintf(g_mnt, sizeof(g_mnt), "%s/mnt", g_root);
+    if (mkdir(g_src, 0755) != 0 || mkdir(g_mnt, 0755) != 0 ||
+        write_config(20000 + (int)(getpid() % 10000)) != 0) {
+        perror("setup");
+        cleanup();
+        return 1;
+    }
+
+    pid_t pid = start_daemon(binary);
+    if (pid < 0) {
+        cleanup();
+        return 1;
+    }
+    int rc = 0;
+    if (test_rsync_twice() != 0 || test_times_then_write() != 0) rc = 1;
+    stop_daemon(pid);
+    if (is_mounted()) {
+        fprintf(stderr, "Could not unmount %s; leaving %s in place.\n", g_mnt, g_root);
+        return 1;
+    }
+    cleanup();
+    if (rc == 0) printf("Rsync test passed.\n");
+    return rc;
+}
@@ -0,0 +1,163 @@
+/*
+ * junkNAS - /metrics exposition format test
+ *
+ * This is a simple test program to verify:
+ *  - Rendered counters parse as Prometheus text format, line by line
+ *  - Label values with quotes, backslashes and newlines are escaped
+ *  - Output larger than the initial buffer is complete (no cut samples)
+ */
+
+#include <ctype.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "stats.h"
+
+static int is_name_start(char c) {
+    return isalpha((unsigned char)c) || c == '_' || c == ':';
+}
+
+static int is_name_char(char c) {
+    return is_name_start(c) || isdigit((unsigned char)c);
+}
+
+/* One sample: name{label="value",...} number. Returns 1 if well formed. */
+static int sample_line_ok(const char *line) {
+    const char *p = line;
+    if (!is_name_start(*p)) return 0;
+    while (is_name_char(*p)) p++;
+
+    if (*p == '{') {
+        p++;
+        while (*p != '}') {
+            if (!is_name_start(*p)) return 0;
+            while (is_name_char(*p)) p++;
+            if (*p++ != '=' || *p++ != '"') return 0;
+            while (*p != '"') {
+                if (*p == '\0' || *p == '\n') return 0;
+                if (*p == '\\') {
+                    p++;
+                    if (*p != '\\' && *p != '"' && *p != 'n') return 0;
+                }
+                p++;
+            }
+            p++;
+            if (*p == ',') p++;
+            else if (*p != '}') return 0;
+        }
+        p++;
+    }
+
+    if (*p++ != ' ') return 0;
+    if (strcmp(p, "+Inf") == 0 || strcmp(p, "-Inf") == 0 || strcmp(p, "NaN") == 0) return 1;
+    char *end = NULL;
+    (void)strtod(p, &end);
+    return end != p && *end == '\0';
+}
+
+static int comment_line_ok(const char *line) {
+    const char *p = NULL;
+    if (strncmp(line, "# HELP ", 7) == 0) p = line + 7;
+    else if (strncmp(line, "# TYPE ", 7) == 0) p = line + 7;
+    else return 0;
+    if (!is_name_start(*p)) return 0;
+    while (is_name_char(*p)) p++;
+    return *p == ' ' && p[1] != '\0';
+}
+
+static int exposition_ok(const char *text) {
+    size_t len = strlen(text);
+    if (len == 0 || text[len - 1] != '\n') {
+        fprintf(stderr, "Output does not end with a newline.\n");
+        return 0;
+    }
+
+    char *copy = strdup(text);
+    if (!copy) return 0;
+    int ok = 1;
+    int lineno = 0;
+    for (char *line = strtok(copy, "\n"); line; line = strtok(NULL, "\n")) {
+        lineno++;
+        int good = (line[0] == '#') ? comment_line_ok(line) : sample_line_ok(line);
+        if (!good) {
+            fprintf(stderr, "Line %d is not valid exposition format: %s\n", lineno, line);
+            ok = 0;
+        }
+    }
+    free(copy);
+    return ok;
+}
+
+static int test_escape_label(void) {
+    char out[JUNKNAS_STATS_LABEL_LEN(32)];
+    junknas_stats_escape_label("a\"b\\c\nd", out, sizeof(out));
+    if (strcmp(out, "a\\\"b\\\\c\\nd") != 0) {
+        fprintf(stderr, "Escaped label mismatch: %s\n", out);
+        return 1;
+    }
+
+    /* Cut before an escape pair, never in the middle of one. */
+    char small[4];
+    junknas_stats_escape_label("ab\"", small, sizeof(small));
+    if (strcmp(small, "ab") != 0) {
+        fprintf(stderr, "Truncated label mismatch: %s\n", small);
+        return 1;
+    }
+    return 0;
+}
+
+static int test_strbuf_growth(void) {
+    junknas_strbuf_t sb = {0};
+    for (int i = 0; i < 2000; i++) {
+        junknas_strbuf_printf(&sb, "junknas_test_gauge{dir=\"/srv/disk%d\"} %d\n", i, i);
+    }
+    if (sb.failed || !sb.buf) {
+        fprintf(stderr, "Buffer growth failed.\n");
+        free(sb.buf);
+        return 1;
+    }
+    int ok = exposition_ok(sb.buf);
+    const char *last = "junknas_test_gauge{dir=\"/srv/disk1999\"} 1999\n";
+    size_t last_len = strlen(last);
+    if (sb.len != strlen(sb.buf) || sb.len < last_len ||
+        strcmp(sb.buf + sb.len - last_len, last) != 0) {
+        fprintf(stderr, "Grown buffer lost its last sample.\n");
+        ok = 0;
+    }
+    free(sb.buf);
+    return ok ? 0 : 1;
+}
+
+static int test_render(void) {
+    junknas_stats_inc(JUNKNAS_STAT_CHUNK_READ_LOCAL);
+    junknas_stats_observe_fuse_op(JUNKNAS_FUSE_OP_READ, 1234);
+    junknas_stats_record_peer("10.0.0.2:8080", 100, 200, 1);
+    junknas_stats_record_peer("evil\"} 1\nfake_metric{x=\"", 1, 1, 0);
+
+    char *text = junknas_stats_render_prometheus();
+    if (!text) {
+        fprintf(stderr, "Render failed.\n");
+        return 1;
+    }
+    int ok = exposition_ok(text);
+    if (!strstr(text, "junknas_peer_bytes_total{peer=\"evil\\\"} 1\\nfake_metric{x=\\\"\",direction=\"sent\"} 1\n")) {
+        fprintf(stderr, "Peer label was not escaped.\n");
+        ok = 0;
+    }
+    if (!strstr(text, "junknas_chunk_reads_total{source=\"local\"} 1\n")) {
+        fprintf(stderr, "Counter sample missing.\n");
+        ok = 0;
+    }
+    free(text);
+    return ok ? 0 : 1;
+}
+
+int main(void) {
+    if (test_escape_label() != 0) return 1;
+    if (test_strbuf_growth() != 0) return 1;
+    if (test_render() != 0) return 1;
+
+    printf("Metrics exposition test passed.\n");
+    return 0;
+}
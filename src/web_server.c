@@ -3,6 +3,13 @@
  */
 
 #include "web_server.h"
+#include "create_policy.h"
+#include "events.h"
+#include "fuse_fs.h"
+#include "mesh.h"
+#include "net.h"
+#include "sha256.h"
+#include "stats.h"
 
 #include <arpa/inet.h>
 #include <ctype.h>
@@ -17,10 +24,13 @@
 #include <stdlib.h>
 #include <stdarg.h>
 #include <string.h>
+#include <sys/file.h>
 #include <sys/socket.h>
 #include <sys/stat.h>
+#include <sys/statvfs.h>
 #include <sys/time.h>
 #include <sys/types.h>
+#include <sys/xattr.h>
 #include <time.h>
 #include <unistd.h>
 
@@ -29,6 +39,9 @@
 
 #define WEB_BACKLOG 16
 #define WEB_BUF_SIZE 8192
+#define WEB_EVENTS_KEEPALIVE_SEC 15
+#define WEB_EVENTS_MAX_STREAMS 16     /* Each open /events stream holds a connection thread */
+#define WEB_PEER_TIMEOUT_MS 1000      /* Dial and send/recv limit for mesh sync requests */
 
 struct junknas_web_server {
     junknas_config_t *config;
@@ -42,6 +55,10 @@ typedef struct {
     junknas_config_t *config;
 } web_conn_t;
 
+/* Set while the server shuts down so long-lived /events streams exit. */
+static volatile int g_web_stopping = 0;
+static int g_event_streams = 0;
+
 static const char qr_lib_js[] __attribute__((unused)) =
     "var qrcode=function(){var t=function(t,r){var e=t,n=g[r],o=null,i=0,a=null,u=[],f={},c=function(t,r){o=function(t){for(var r=new Array(t),e=0;e<t;e+=1){r[e]=new Array(t);for(var n=0;n<t;n+=1)r[e][n]=null}return r}(i=4*e+17),l(0,0),l(i-7,0),l(0,i-7),s(),h(),d(t,r),e>=7&&v(t),null==a&&(a=p(e,n,u)),w(a,r)},l=function(t,r){for(var e=-1;e<=7;e+=1)if(!(t+e<=-1||i<=t+e))for(var n=-1;n<=7;n+=1)r+n<=-1||i<=r+n||(o[t+e][r+n]=0<=e&&e<=6&&(0==n||6==n)||0<=n&&n<=6&&(0==e||6==e)||2<=e&&e<=4&&2<=n&&n<=4)},h=function(){for(var t=8;t<i-8;t+=1)null==o[t][6]&&(o[t][6]=t%2==0);for(var r=8;r<i-8;r+=1)null==o[6][r]&&(o[6][r]=r%2==0)},s=function(){for(var t=B.getPatternPosition(e),r=0;r<t.length;r+=1)for(var n=0;n<t.length;n+=1){var i=t[r],a=t[n];if(null==o[i][a])for(var u=-2;u<=2;u+=1)for(var f=-2;f<=2;f+=1)o[i+u][a+f]=-2==u||2==u||-2==f||2==f||0==u&&0==f}},v=function(t){for(var r=B.getBCHTypeNumber(e),n=0;n<18;n+=1){var a=!t&&1==(r>>n&1);o[Math.floor(n/3)][n%3+i-8-3]=a}for(n=0;n<18;n+=1){a=!t&&1==(r>>n&1);o[n%3+i-8-3][Math.floor(n/3)]=a}},d=function(t,r){for(var e=n<<3|r,a=B.getBCHTypeInfo(e),u=0;u<15;u+=1){var f=!t&&1==(a>>u&1);u<6?o[u][8]=f:u<8?o[u+1][8]=f:o[i-15+u][8]=f}for(u=0;u<15;u+=1){f=!t&&1==(a>>u&1);u<8?o[8][i-u-1]=f:u<9?o[8][15-u-1+1]=f:o[8][15-u-1]=f}o[i-8][8]=!t},w=function(t,r){for(var e=-1,n=i-1,a=7,u=0,f=B.getMaskFunction(r),c=i-1;c>0;c-=2)for(6==c&&(c-=1);;){for(var g=0;g<2;g+=1)if(null==o[n][c-g]){var l=!1;u<t.length&&(l=1==(t[u]>>>a&1)),f(n,c-g)&&(l=!l),o[n][c-g]=l,-1==(a-=1)&&(u+=1,a=7)}if((n+=e)<0||i<=n){n-=e,e=-e;break}}},p=function(t,r,e){for(var n=A.getRSBlocks(t,r),o=b(),i=0;i<e.length;i+=1){var a=e[i];o.put(a.getMode(),4),o.put(a.getLength(),B.getLengthInBits(a.getMode(),t)),a.write(o)}var u=0;for(i=0;i<n.length;i+=1)u+=n[i].dataCount;if(o.getLengthInBits()>8*u)throw\"code length overflow. (\"+o.getLengthInBits()+\">\"+8*u+\")\";for(o.getLengthInBits()+4<=8*u&&o.put(0,4);o.getLengthInBits()%8!=0;)o.putBit(!1);for(;!(o.getLengthInBits()>=8*u||(o.put(236,8),o.getLengthInBits()>=8*u));)o.put(17,8);return function(t,r){for(var e=0,n=0,o=0,i=new Array(r.length),a=new Array(r.length),u=0;u<r.length;u+=1){var f=r[u].dataCount,c=r[u].totalCount-f;n=Math.max(n,f),o=Math.max(o,c),i[u]=new Array(f);for(var g=0;g<i[u].length;g+=1)i[u][g]=255&t.getBuffer()[g+e];e+=f;var l=B.getErrorCorrectPolynomial(c),h=k(i[u],l.getLength()-1).mod(l);for(a[u]=new Array(l.getLength()-1),g=0;g<a[u].length;g+=1){var s=g+h.getLength()-a[u].length;a[u][g]=s>=0?h.getAt(s):0}}var v=0;for(g=0;g<r.length;g+=1)v+=r[g].totalCount;var d=new Array(v),w=0;for(g=0;g<n;g+=1)for(u=0;u<r.length;u+=1)g<i[u].length&&(d[w]=i[u][g],w+=1);for(g=0;g<o;g+=1)for(u=0;u<r.length;u+=1)g<a[u].length&&(d[w]=a[u][g],w+=1);return d}(o,n)};f.addData=function(t,r){var e=null;switch(r=r||\"Byte\"){case\"Numeric\":e=M(t);break;case\"Alphanumeric\":e=x(t);break;case\"Byte\":e=m(t);break;case\"Kanji\":e=L(t);break;default:throw\"mode:\"+r}u.push(e),a=null},f.isDark=function(t,r){if(t<0||i<=t||r<0||i<=r)throw t+\",\"+r;return o[t][r]},f.getModuleCount=function(){return i},f.make=function(){if(e<1){for(var t=1;t<40;t++){for(var r=A.getRSBlocks(t,n),o=b(),i=0;i<u.length;i++){var a=u[i];o.put(a.getMode(),4),o.put(a.getLength(),B.getLengthInBits(a.getMode(),t)),a.write(o)}var g=0;for(i=0;i<r.length;i++)g+=r[i].dataCount;if(o.getLengthInBits()<=8*g)break}e=t}c(!1,function(){for(var t=0,r=0,e=0;e<8;e+=1){c(!0,e);var n=B.getLostPoint(f);(0==e||t>n)&&(t=n,r=e)}return r}())},f.createTableTag=function(t,r){t=t||2;var e=\"\";e+='<table style=\"',e+=\" border-width: 0px; border-style: none;\",e+=\" border-collapse: collapse;\",e+=\" padding: 0px; margin: \"+(r=void 0===r?4*t:r)+\"px;\",e+='\">',e+=\"<tbody>\";for(var n=0;n<f.getModuleCount();n+=1){e+=\"<tr>\";for(var o=0;o<f.getModuleCount();o+=1)e+='<td style=\"',e+=\" border-width: 0px; border-style: none;\",e+=\" border-collapse: collapse;\",e+=\" padding: 0px; margin: 0px;\",e+=\" width: \"+t+\"px;\",e+=\" height: \"+t+\"px;\",e+=\" background-color: \",e+=f.isDark(n,o)?\"#000000\":\"#ffffff\",e+=\";\",e+='\"/>';e+=\"</tr>\"}return e+=\"</tbody>\",e+=\"</table>\"},f.createSvgTag=function(t,r,e,n){var o={};\"object\"==typeof arguments[0]&&(t=(o=arguments[0]).cellSize,r=o.margin,e=o.alt,n=o.title),t=t||2,r=void 0===r?4*t:r,(e=\"string\"==typeof e?{text:e}:e||{}).text=e.text||null,e.id=e.text?e.id||\"qrcode-description\":null,(n=\"string\"==typeof n?{text:n}:n||{}).text=n.text||null,n.id=n.text?n.id||\"qrcode-title\":null;var i,a,u,c,g=f.getModuleCount()*t+2*r,l=\"\";for(c=\"l\"+t+\",0 0,\"+t+\" -\"+t+\",0 0,-\"+t+\"z \",l+='<svg version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\"',l+=o.scalable?\"\":' width=\"'+g+'px\" height=\"'+g+'px\"',l+=' viewBox=\"0 0 '+g+\" \"+g+'\" ',l+=' preserveAspectRatio=\"xMinYMin meet\"',l+=n.text||e.text?' role=\"img\" aria-labelledby=\"'+y([n.id,e.id].join(\" \").trim())+'\"':\"\",l+=\">\",l+=n.text?'<title id=\"'+y(n.id)+'\">'+y(n.text)+\"</title>\":\"\",l+=e.text?'<description id=\"'+y(e.id)+'\">'+y(e.text)+\"</description>\":\"\",l+='<rect width=\"100%\" height=\"100%\" fill=\"white\" cx=\"0\" cy=\"0\"/>',l+='<path d=\"',a=0;a<f.getModuleCount();a+=1)for(u=a*t+r,i=0;i<f.getModuleCount();i+=1)f.isDark(a,i)&&(l+=\"M\"+(i*t+r)+\",\"+u+c);return l+='\" stroke=\"transparent\" fill=\"black\"/>',l+=\"</svg>\"},f.createDataURL=function(t,r){t=t||2,r=void 0===r?4*t:r;var e=f.getModuleCount()*t+2*r,n=r,o=e-r;return I(e,e,(function(r,e){if(n<=r&&r<o&&n<=e&&e<o){var i=Math.floor((r-n)/t),a=Math.floor((e-n)/t);return f.isDark(a,i)?0:1}return 1}))},f.createImgTag=function(t,r,e){t=t||2,r=void 0===r?4*t:r;var n=f.getModuleCount()*t+2*r,o=\"\";return o+=\"<img\",o+=' src=\"',o+=f.createDataURL(t,r),o+='\"',o+=' width=\"',o+=n,o+='\"',o+=' height=\"',o+=n,o+='\"',e&&(o+=' alt=\"',o+=y(e),o+='\"'),o+=\"/>\"};var y=function(t){for(var r=\"\",e=0;e<t.length;e+=1){var n=t.charAt(e);switch(n){case\"<\":r+=\"&lt;\";break;case\">\":r+=\"&gt;\";break;case\"&\":r+=\"&amp;\";break;case'\"':r+=\"&quot;\";break;default:r+=n}}return r};return f.createASCII=function(t,r){if((t=t||1)<2)return function(t){t=void 0===t?2:t;var r,e,n,o,i,a=1*f.getModuleCount()+2*t,u=t,c=a-t,g={\"██\":\"█\",\"█ \":\"▀\",\" █\":\"▄\",\"  \":\" \"},l={\"██\":\"▀\",\"█ \":\"▀\",\" █\":\" \",\"  \":\" \"},h=\"\";for(r=0;r<a;r+=2){for(n=Math.floor((r-u)/1),o=Math.floor((r+1-u)/1),e=0;e<a;e+=1)i=\"█\",u<=e&&e<c&&u<=r&&r<c&&f.isDark(n,Math.floor((e-u)/1))&&(i=\" \"),u<=e&&e<c&&u<=r+1&&r+1<c&&f.isDark(o,Math.floor((e-u)/1))?i+=\" \":i+=\"█\",h+=t<1&&r+1>=c?l[i]:g[i];h+=\"\\n\"}return a%2&&t>0?h.substring(0,h.length-a-1)+Array(a+1).join(\"▀\"):h.substring(0,h.length-1)}(r);t-=1,r=void 0===r?2*t:r;var e,n,o,i,a=f.getModuleCount()*t+2*r,u=r,c=a-r,g=Array(t+1).join(\"██\"),l=Array(t+1).join(\"  \"),h=\"\",s=\"\";for(e=0;e<a;e+=1){for(o=Math.floor((e-u)/t),s=\"\",n=0;n<a;n+=1)i=1,u<=n&&n<c&&u<=e&&e<c&&f.isDark(o,Math.floor((n-u)/t))&&(i=0),s+=i?g:l;for(o=0;o<t;o+=1)h+=s+\"\\n\"}return h.substring(0,h.length-1)},f.renderTo2dContext=function(t,r){r=r||2;for(var e=f.getModuleCount(),n=0;n<e;n++)for(var o=0;o<e;o++)t.fillStyle=f.isDark(n,o)?\"black\":\"white\",t.fillRect(n*r,o*r,r,r)},f};t.stringToBytes=(t.stringToBytesFuncs={default:function(t){for(var r=[],e=0;e<t.length;e+=1){var n=t.charCodeAt(e);r.push(255&n)}return r}}).default,t.createStringToBytes=function(t,r){var e=function(){for(var e=S(t),n=function(){var t=e.read();if(-1==t)throw\"eof\";return t},o=0,i={};;){var a=e.read();if(-1==a)break;var u=n(),f=n()<<8|n();i[String.fromCharCode(a<<8|u)]=f,o+=1}if(o!=r)throw o+\" != \"+r;return i}(),n=\"?\".charCodeAt(0);return function(t){for(var r=[],o=0;o<t.length;o+=1){var i=t.charCodeAt(o);if(i<128)r.push(i);else{var a=e[t.charAt(o)];\"number\"==typeof a?(255&a)==a?r.push(a):(r.push(a>>>8),r.push(255&a)):r.push(n)}}return r}};var r,e,n,o,i,a=1,u=2,f=4,c=8,g={L:1,M:0,Q:3,H:2},l=0,h=1,s=2,v=3,d=4,w=5,p=6,y=7,B=(r=[[],[6,18],[6,22],[6,26],[6,30],[6,34],[6,22,38],[6,24,42],[6,26,46],[6,28,50],[6,30,54],[6,32,58],[6,34,62],[6,26,46,66],[6,26,48,70],[6,26,50,74],[6,30,54,78],[6,30,56,82],[6,30,58,86],[6,34,62,90],[6,28,50,72,94],[6,26,50,74,98],[6,30,54,78,102],[6,28,54,80,106],[6,32,58,84,110],[6,30,58,86,114],[6,34,62,90,118],[6,26,50,74,98,122],[6,30,54,78,102,126],[6,26,52,78,104,130],[6,30,56,82,108,134],[6,34,60,86,112,138],[6,30,58,86,114,142],[6,34,62,90,118,146],[6,30,54,78,102,126,150],[6,24,50,76,102,128,154],[6,28,54,80,106,132,158],[6,32,58,84,110,136,162],[6,26,54,82,110,138,166],[6,30,58,86,114,142,170]],e=1335,n=7973,i=function(t){for(var r=0;0!=t;)r+=1,t>>>=1;return r},(o={}).getBCHTypeInfo=function(t){for(var r=t<<10;i(r)-i(e)>=0;)r^=e<<i(r)-i(e);return 21522^(t<<10|r)},o.getBCHTypeNumber=function(t){for(var r=t<<12;i(r)-i(n)>=0;)r^=n<<i(r)-i(n);return t<<12|r},o.getPatternPosition=function(t){return r[t-1]},o.getMaskFunction=function(t){switch(t){case l:return function(t,r){return(t+r)%2==0};case h:return function(t,r){return t%2==0};case s:return function(t,r){return r%3==0};case v:return function(t,r){return(t+r)%3==0};case d:return function(t,r){return(Math.floor(t/2)+Math.floor(r/3))%2==0};case w:return function(t,r){return t*r%2+t*r%3==0};case p:return function(t,r){return(t*r%2+t*r%3)%2==0};case y:return function(t,r){return(t*r%3+(t+r)%2)%2==0};default:throw\"bad maskPattern:\"+t}},o.getErrorCorrectPolynomial=function(t){for(var r=k([1],0),e=0;e<t;e+=1)r=r.multiply(k([1,C.gexp(e)],0));return r},o.getLengthInBits=function(t,r){if(1<=r&&r<10)switch(t){case a:return 10;case u:return 9;case f:case c:return 8;default:throw\"mode:\"+t}else if(r<27)switch(t){case a:return 12;case u:return 11;case f:return 16;case c:return 10;default:throw\"mode:\"+t}else{if(!(r<41))throw\"type:\"+r;switch(t){case a:return 14;case u:return 13;case f:return 16;case c:return 12;default:throw\"mode:\"+t}}},o.getLostPoint=function(t){for(var r=t.getModuleCount(),e=0,n=0;n<r;n+=1)for(var o=0;o<r;o+=1){for(var i=0,a=t.isDark(n,o),u=-1;u<=1;u+=1)if(!(n+u<0||r<=n+u))for(var f=-1;f<=1;f+=1)o+f<0||r<=o+f||0==u&&0==f||a==t.isDark(n+u,o+f)&&(i+=1);i>5&&(e+=3+i-5)}for(n=0;n<r-1;n+=1)for(o=0;o<r-1;o+=1){var c=0;t.isDark(n,o)&&(c+=1),t.isDark(n+1,o)&&(c+=1),t.isDark(n,o+1)&&(c+=1),t.isDark(n+1,o+1)&&(c+=1),0!=c&&4!=c||(e+=3)}for(n=0;n<r;n+=1)for(o=0;o<r-6;o+=1)t.isDark(n,o)&&!t.isDark(n,o+1)&&t.isDark(n,o+2)&&t.isDark(n,o+3)&&t.isDark(n,o+4)&&!t.isDark(n,o+5)&&t.isDark(n,o+6)&&(e+=40);for(o=0;o<r;o+=1)for(n=0;n<r-6;n+=1)t.isDark(n,o)&&!t.isDark(n+1,o)&&t.isDark(n+2,o)&&t.isDark(n+3,o)&&t.isDark(n+4,o)&&!t.isDark(n+5,o)&&t.isDark(n+6,o)&&(e+=40);var g=0;for(o=0;o<r;o+=1)for(n=0;n<r;n+=1)t.isDark(n,o)&&(g+=1);return e+=Math.abs(100*g/r/r-50)/5*10},o),C=function(){for(var t=new Array(256),r=new Array(256),e=0;e<8;e+=1)t[e]=1<<e;for(e=8;e<256;e+=1)t[e]=t[e-4]^t[e-5]^t[e-6]^t[e-8];for(e=0;e<255;e+=1)r[t[e]]=e;var n={glog:function(t){if(t<1)throw\"glog(\"+t+\")\";return r[t]},gexp:function(r){for(;r<0;)r+=255;for(;r>=256;)r-=255;return t[r]}};return n}();function k(t,r){if(void 0===t.length)throw t.length+\"/\"+r;var e=function(){for(var e=0;e<t.length&&0==t[e];)e+=1;for(var n=new Array(t.length-e+r),o=0;o<t.length-e;o+=1)n[o]=t[o+e];return n}(),n={getAt:function(t){return e[t]},getLength:function(){return e.length},multiply:function(t){for(var r=new Array(n.getLength()+t.getLength()-1),e=0;e<n.getLength();e+=1)for(var o=0;o<t.getLength();o+=1)r[e+o]^=C.gexp(C.glog(n.getAt(e))+C.glog(t.getAt(o)));return k(r,0)},mod:function(t){if(n.getLength()-t.getLength()<0)return n;for(var r=C.glog(n.getAt(0))-C.glog(t.getAt(0)),e=new Array(n.getLength()),o=0;o<n.getLength();o+=1)e[o]=n.getAt(o);for(o=0;o<t.getLength();o+=1)e[o]^=C.gexp(C.glog(t.getAt(o))+r);return k(e,0).mod(t)}};return n}var A=function(){var t=[[1,26,19],[1,26,16],[1,26,13],[1,26,9],[1,44,34],[1,44,28],[1,44,22],[1,44,16],[1,70,55],[1,70,44],[2,35,17],[2,35,13],[1,100,80],[2,50,32],[2,50,24],[4,25,9],[1,134,108],[2,67,43],[2,33,15,2,34,16],[2,33,11,2,34,12],[2,86,68],[4,43,27],[4,43,19],[4,43,15],[2,98,78],[4,49,31],[2,32,14,4,33,15],[4,39,13,1,40,14],[2,121,97],[2,60,38,2,61,39],[4,40,18,2,41,19],[4,40,14,2,41,15],[2,146,116],[3,58,36,2,59,37],[4,36,16,4,37,17],[4,36,12,4,37,13],[2,86,68,2,87,69],[4,69,43,1,70,44],[6,43,19,2,44,20],[6,43,15,2,44,16],[4,101,81],[1,80,50,4,81,51],[4,50,22,4,51,23],[3,36,12,8,37,13],[2,116,92,2,117,93],[6,58,36,2,59,37],[4,46,20,6,47,21],[7,42,14,4,43,15],[4,133,107],[8,59,37,1,60,38],[8,44,20,4,45,21],[12,33,11,4,34,12],[3,145,115,1,146,116],[4,64,40,5,65,41],[11,36,16,5,37,17],[11,36,12,5,37,13],[5,109,87,1,110,88],[5,65,41,5,66,42],[5,54,24,7,55,25],[11,36,12,7,37,13],[5,122,98,1,123,99],[7,73,45,3,74,46],[15,43,19,2,44,20],[3,45,15,13,46,16],[1,135,107,5,136,108],[10,74,46,1,75,47],[1,50,22,15,51,23],[2,42,14,17,43,15],[5,150,120,1,151,121],[9,69,43,4,70,44],[17,50,22,1,51,23],[2,42,14,19,43,15],[3,141,113,4,142,114],[3,70,44,11,71,45],[17,47,21,4,48,22],[9,39,13,16,40,14],[3,135,107,5,136,108],[3,67,41,13,68,42],[15,54,24,5,55,25],[15,43,15,10,44,16],[4,144,116,4,145,117],[17,68,42],[17,50,22,6,51,23],[19,46,16,6,47,17],[2,139,111,7,140,112],[17,74,46],[7,54,24,16,55,25],[34,37,13],[4,151,121,5,152,122],[4,75,47,14,76,48],[11,54,24,14,55,25],[16,45,15,14,46,16],[6,147,117,4,148,118],[6,73,45,14,74,46],[11,54,24,16,55,25],[30,46,16,2,47,17],[8,132,106,4,133,107],[8,75,47,13,76,48],[7,54,24,22,55,25],[22,45,15,13,46,16],[10,142,114,2,143,115],[19,74,46,4,75,47],[28,50,22,6,51,23],[33,46,16,4,47,17],[8,152,122,4,153,123],[22,73,45,3,74,46],[8,53,23,26,54,24],[12,45,15,28,46,16],[3,147,117,10,148,118],[3,73,45,23,74,46],[4,54,24,31,55,25],[11,45,15,31,46,16],[7,146,116,7,147,117],[21,73,45,7,74,46],[1,53,23,37,54,24],[19,45,15,26,46,16],[5,145,115,10,146,116],[19,75,47,10,76,48],[15,54,24,25,55,25],[23,45,15,25,46,16],[13,145,115,3,146,116],[2,74,46,29,75,47],[42,54,24,1,55,25],[23,45,15,28,46,16],[17,145,115],[10,74,46,23,75,47],[10,54,24,35,55,25],[19,45,15,35,46,16],[17,145,115,1,146,116],[14,74,46,21,75,47],[29,54,24,19,55,25],[11,45,15,46,46,16],[13,145,115,6,146,116],[14,74,46,23,75,47],[44,54,24,7,55,25],[59,46,16,1,47,17],[12,151,121,7,152,122],[12,75,47,26,76,48],[39,54,24,14,55,25],[22,45,15,41,46,16],[6,151,121,14,152,122],[6,75,47,34,76,48],[46,54,24,10,55,25],[2,45,15,64,46,16],[17,152,122,4,153,123],[29,74,46,14,75,47],[49,54,24,10,55,25],[24,45,15,46,46,16],[4,152,122,18,153,123],[13,74,46,32,75,47],[48,54,24,14,55,25],[42,45,15,32,46,16],[20,147,117,4,148,118],[40,75,47,7,76,48],[43,54,24,22,55,25],[10,45,15,67,46,16],[19,148,118,6,149,119],[18,75,47,31,76,48],[34,54,24,34,55,25],[20,45,15,61,46,16]],r=function(t,r){var e={};return e.totalCount=t,e.dataCount=r,e},e={};return e.getRSBlocks=function(e,n){var o=function(r,e){switch(e){case g.L:return t[4*(r-1)+0];case g.M:return t[4*(r-1)+1];case g.Q:return t[4*(r-1)+2];case g.H:return t[4*(r-1)+3];default:return}}(e,n);if(void 0===o)throw\"bad rs block @ typeNumber:\"+e+\"/errorCorrectionLevel:\"+n;for(var i=o.length/3,a=[],u=0;u<i;u+=1)for(var f=o[3*u+0],c=o[3*u+1],l=o[3*u+2],h=0;h<f;h+=1)a.push(r(c,l));return a},e}(),b=function(){var t=[],r=0,e={getBuffer:function(){return t},getAt:function(r){var e=Math.floor(r/8);return 1==(t[e]>>>7-r%8&1)},put:function(t,r){for(var n=0;n<r;n+=1)e.putBit(1==(t>>>r-n-1&1))},getLengthInBits:function(){return r},putBit:function(e){var n=Math.floor(r/8);t.length<=n&&t.push(0),e&&(t[n]|=128>>>r%8),r+=1}};return e},M=function(t){var r=a,e=t,n={getMode:function(){return r},getLength:function(t){return e.length},write:function(t){for(var r=e,n=0;n+2<r.length;)t.put(o(r.substring(n,n+3)),10),n+=3;n<r.length&&(r.length-n==1?t.put(o(r.substring(n,n+1)),4):r.length-n==2&&t.put(o(r.substring(n,n+2)),7))}},o=function(t){for(var r=0,e=0;e<t.length;e+=1)r=10*r+i(t.charAt(e));return r},i=function(t){if(\"0\"<=t&&t<=\"9\")return t.charCodeAt(0)-\"0\".charCodeAt(0);throw\"illegal char :\"+t};return n},x=function(t){var r=u,e=t,n={getMode:function(){return r},getLength:function(t){return e.length},write:function(t){for(var r=e,n=0;n+1<r.length;)t.put(45*o(r.charAt(n))+o(r.charAt(n+1)),11),n+=2;n<r.length&&t.put(o(r.charAt(n)),6)}},o=function(t){if(\"0\"<=t&&t<=\"9\")return t.charCodeAt(0)-\"0\".charCodeAt(0);if(\"A\"<=t&&t<=\"Z\")return t.charCodeAt(0)-\"A\".charCodeAt(0)+10;switch(t){case\" \":return 36;case\"$\":return 37;case\"%\":return 38;case\"*\":return 39;case\"+\":return 40;case\"-\":return 41;case\".\":return 42;case\"/\":return 43;case\":\":return 44;default:throw\"illegal char :\"+t}};return n},m=function(r){var e=f,n=t.stringToBytes(r),o={getMode:function(){return e},getLength:function(t){return n.length},write:function(t){for(var r=0;r<n.length;r+=1)t.put(n[r],8)}};return o},L=function(r){var e=c,n=t.stringToBytesFuncs.SJIS;if(!n)throw\"sjis not supported.\";!function(){var t=n(\"友\");if(2!=t.length||38726!=(t[0]<<8|t[1]))throw\"sjis not supported.\"}();var o=n(r),i={getMode:function(){return e},getLength:function(t){return~~(o.length/2)},write:function(t){for(var r=o,e=0;e+1<r.length;){var n=(255&r[e])<<8|255&r[e+1];if(33088<=n&&n<=40956)n-=33088;else{if(!(57408<=n&&n<=60351))throw\"illegal char at \"+(e+1)+\"/\"+n;n-=49472}n=192*(n>>>8&255)+(255&n),t.put(n,13),e+=2}if(e<r.length)throw\"illegal char at \"+(e+1)}};return i},D=function(){var t=[],r={writeByte:function(r){t.push(255&r)},writeShort:function(t){r.writeByte(t),r.writeByte(t>>>8)},writeBytes:function(t,e,n){e=e||0,n=n||t.length;for(var o=0;o<n;o+=1)r.writeByte(t[o+e])},writeString:function(t){for(var e=0;e<t.length;e+=1)r.writeByte(t.charCodeAt(e))},toByteArray:function(){return t},toString:function(){var r=\"\";r+=\"[\";for(var e=0;e<t.length;e+=1)e>0&&(r+=\",\"),r+=t[e];return r+=\"]\"}};return r},S=function(t){var r=t,e=0,n=0,o=0,i={read:function(){for(;o<8;){if(e>=r.length){if(0==o)return-1;throw\"unexpected end of file./\"+o}var t=r.charAt(e);if(e+=1,\"=\"==t)return o=0,-1;t.match(/^\\s$/)||(n=n<<6|a(t.charCodeAt(0)),o+=6)}var i=n>>>o-8&255;return o-=8,i}},a=function(t){if(65<=t&&t<=90)return t-65;if(97<=t&&t<=122)return t-97+26;if(48<=t&&t<=57)return t-48+52;if(43==t)return 62;if(47==t)return 63;throw\"c:\"+t};return i},I=function(t,r,e){for(var n=function(t,r){var e=t,n=r,o=new Array(t*r),i={setPixel:function(t,r,n){o[r*e+t]=n},write:function(t){t.writeString(\"GIF87a\"),t.writeShort(e),t.writeShort(n),t.writeByte(128),t.writeByte(0),t.writeByte(0),t.writeByte(0),t.writeByte(0),t.writeByte(0),t.writeByte(255),t.writeByte(255),t.writeByte(255),t.writeString(\",\"),t.writeShort(0),t.writeShort(0),t.writeShort(e),t.writeShort(n),t.writeByte(0);var r=a(2);t.writeByte(2);for(var o=0;r.length-o>255;)t.writeByte(255),t.writeBytes(r,o,255),o+=255;t.writeByte(r.length-o),t.writeBytes(r,o,r.length-o),t.writeByte(0),t.writeString(\";\")}},a=function(t){for(var r=1<<t,e=1+(1<<t),n=t+1,i=u(),a=0;a<r;a+=1)i.add(String.fromCharCode(a));i.add(String.fromCharCode(r)),i.add(String.fromCharCode(e));var f,c,g,l=D(),h=(f=l,c=0,g=0,{write:function(t,r){if(t>>>r!=0)throw\"length over\";for(;c+r>=8;)f.writeByte(255&(t<<c|g)),r-=8-c,t>>>=8-c,g=0,c=0;g|=t<<c,c+=r},flush:function(){c>0&&f.writeByte(g)}});h.write(r,n);var s=0,v=String.fromCharCode(o[s]);for(s+=1;s<o.length;){var d=String.fromCharCode(o[s]);s+=1,i.contains(v+d)?v+=d:(h.write(i.indexOf(v),n),i.size()<4095&&(i.size()==1<<n&&(n+=1),i.add(v+d)),v=d)}return h.write(i.indexOf(v),n),h.write(e,n),h.flush(),l.toByteArray()},u=function(){var t={},r=0,e={add:function(n){if(e.contains(n))throw\"dup key:\"+n;t[n]=r,r+=1},size:function(){return r},indexOf:function(r){return t[r]},contains:function(r){return void 0!==t[r]}};return e};return i}(t,r),o=0;o<r;o+=1)for(var i=0;i<t;i+=1)n.setPixel(i,o,e(i,o));var a=D();n.write(a);for(var u=function(){var t=0,r=0,e=0,n=\"\",o={},i=function(t){n+=String.fromCharCode(a(63&t))},a=function(t){if(t<0);else{if(t<26)return 65+t;if(t<52)return t-26+97;if(t<62)return t-52+48;if(62==t)return 43;if(63==t)return 47}throw\"n:\"+t};return o.writeByte=function(n){for(t=t<<8|255&n,r+=8,e+=1;r>=6;)i(t>>>r-6),r-=6},o.flush=function(){if(r>0&&(i(t<<6-r),t=0,r=0),e%3!=0)for(var o=3-e%3,a=0;a<o;a+=1)n+=\"=\"},o.toString=function(){return n},o}(),f=a.toByteArray(),c=0;c<f.length;c+=1)u.writeByte(f[c]);return u.flush(),\"data:image/gif;base64,\"+u};return t}();qrcode.stringToBytesFuncs[\"UTF-8\"]=function(t){return function(t){for(var r=[],e=0;e<t.length;e++){var n=t.charCodeAt(e);n<128?r.push(n):n<2048?r.push(192|n>>6,128|63&n):n<55296||n>=57344?r.push(224|n>>12,128|n>>6&63,128|63&n):(e++,n=65536+((1023&n)<<10|1023&t.charCodeAt(e)),r.push(240|n>>18,128|n>>12&63,128|n>>6&63,128|63&n))}return r}(t)},function(t){\"function\"==typeof define&&define.amd?define([],t):\"object\"==typeof exports&&(module.exports=t())}((function(){return qrcode}));\n//# sourceMappingURL=/sm/26b4b0d0b1e283d6b3ec9857ac597d7a60c76ac17be1ef4c965f03086de426bb.map"
     "";
@@ -55,11 +72,38 @@ static void web_log_verbose(const junknas_config_t *config, const char *fmt, ...
     va_end(args);
 }
 
-static int is_safe_relative(const char *path) {
-    if (!path) return 0;
-    if (path[0] == '/') return 0;
-    if (strstr(path, "..")) return 0;
-    return 1;
+/* Canonicalize a request path relative to the mount: drop empty and "."
+ * segments and resolve ".." against what came before, clamping at the
+ * root so "a/../../b" becomes "b". Returns 0, or -1 if out is too small.
+ */
+static int normalize_relative(const char *path, char *out, size_t out_len) {
+    if (!path || !out || out_len == 0) return -1;
+    size_t used = 0;
+    out[0] = '\0';
+
+    const char *p = path;
+    while (*p) {
+        while (*p == '/') p++;
+        if (*p == '\0') break;
+        const char *seg = p;
+        while (*p && *p != '/') p++;
+        size_t seg_len = (size_t)(p - seg);
+
+        if (seg_len == 1 && seg[0] == '.') continue;
+        if (seg_len == 2 && seg[0] == '.' && seg[1] == '.') {
+            while (used > 0 && out[used - 1] != '/') used--;
+            if (used > 0) used--;
+            out[used] = '\0';
+            continue;
+        }
+        size_t need = seg_len + (used > 0 ? 1 : 0);
+        if (used + need >= out_len) return -1;
+        if (used > 0) out[used++] = '/';
+        memcpy(out + used, seg, seg_len);
+        used += seg_len;
+        out[used] = '\0';
+    }
+    return 0;
 }
 
 static int is_hex64(const char *hash) {
@@ -136,6 +180,7 @@ static int parse_peer_json(cJSON *obj, junknas_wg_peer_t *peer) {
 
     cJSON *endpoint = cJSON_GetObjectItemCaseSensitive(obj, "endpoint");
     if (cJSON_IsString(endpoint) && endpoint->valuestring) {
+        if (!junknas_is_clean_string(endpoint->valuestring, sizeof(out.endpoint))) return -1;
         snprintf(out.endpoint, sizeof(out.endpoint), "%s", endpoint->valuestring);
     }
     cJSON *web_port = cJSON_GetObjectItemCaseSensitive(obj, "web_port");
@@ -219,9 +264,16 @@ static int parse_endpoint(const char *endpoint, char *host, size_t host_len, uin
     const char *colon = strrchr(endpoint, ':');
     if (!colon || colon == endpoint || *(colon + 1) == '\0') return -1;
 
+    /* "[v6addr]:port" */
+    const char *hstart = endpoint;
     size_t hlen = (size_t)(colon - endpoint);
+    if (endpoint[0] == '[') {
+        if (hlen < 3 || *(colon - 1) != ']') return -1;
+        hstart = endpoint + 1;
+        hlen -= 2;
+    }
     if (hlen >= host_len) return -1;
-    memcpy(host, endpoint, hlen);
+    memcpy(host, hstart, hlen);
     host[hlen] = '\0';
 
     char *end = NULL;
@@ -231,26 +283,6 @@ static int parse_endpoint(const char *endpoint, char *host, size_t host_len, uin
     return 0;
 }
 
-static int resolve_addr(const char *host, uint16_t port, int socktype,
-                        struct sockaddr_storage *out, socklen_t *out_len) {
-    if (!host || !out || !out_len) return -1;
-    char port_str[8];
-    snprintf(port_str, sizeof(port_str), "%u", port);
-
-    struct addrinfo hints;
-    memset(&hints, 0, sizeof(hints));
-    hints.ai_socktype = socktype;
-    hints.ai_family = AF_UNSPEC;
-
-    struct addrinfo *res = NULL;
-    if (getaddrinfo(host, port_str, &hints, &res) != 0) return -1;
-    if (!res) return -1;
-    memcpy(out, res->ai_addr, res->ai_addrlen);
-    *out_len = (socklen_t)res->ai_addrlen;
-    freeaddrinfo(res);
-    return 0;
-}
-
 static int update_wg_peer_by_ip(junknas_config_t *config, const junknas_wg_peer_t *peer) {
     if (!config || !peer || peer->endpoint[0] == '\0') return -1;
 
@@ -266,32 +298,17 @@ static int update_wg_peer_by_ip(junknas_config_t *config, const junknas_wg_peer_
     return 1;
 }
 
-static char *http_request_body(const char *host, uint16_t port, const char *request,
-                               const char *body, size_t body_len, int *out_status) {
-    struct sockaddr_storage addr;
-    socklen_t addr_len = 0;
-    if (resolve_addr(host, port, SOCK_STREAM, &addr, &addr_len) != 0) return NULL;
-
-    int fd = socket(addr.ss_family, SOCK_STREAM, 0);
+static char *http_request_body(const char *host, uint16_t port, const char *preference,
+                               const char *request, const char *body, size_t body_len, int *out_status) {
+    int fd = junknas_net_connect(host, port, preference, WEB_PEER_TIMEOUT_MS, WEB_PEER_TIMEOUT_MS);
     if (fd < 0) return NULL;
 
-    struct timeval timeout;
-    timeout.tv_sec = 1;
-    timeout.tv_usec = 0;
-    setsockopt(fd, SOL_SOCKET, SO_RCVTIMEO, &timeout, sizeof(timeout));
-    setsockopt(fd, SOL_SOCKET, SO_SNDTIMEO, &timeout, sizeof(timeout));
-
-    if (connect(fd, (struct sockaddr *)&addr, addr_len) != 0) {
-        close(fd);
-        return NULL;
-    }
-
-    if (send(fd, request, strlen(request), 0) < 0) {
+    if (junknas_net_send_all(fd, request, strlen(request)) != 0) {
         close(fd);
         return NULL;
     }
     if (body && body_len > 0) {
-        if (send(fd, body, body_len, 0) < 0) {
+        if (junknas_net_send_all(fd, body, body_len) != 0) {
             close(fd);
             return NULL;
         }
@@ -418,14 +435,41 @@ static const char *status_label(int status) {
     return "connecting";
 }
 
-static void respond_mesh_status(int fd, junknas_config_t *config) {
+static void add_traffic_json(cJSON *entry, const junknas_peer_traffic_t *traffic) {
+    cJSON *obj = cJSON_CreateObject();
+    if (!obj) return;
+    cJSON_AddNumberToObject(obj, "bytes_sent", (double)traffic->bytes_sent);
+    cJSON_AddNumberToObject(obj, "bytes_received", (double)traffic->bytes_received);
+    cJSON_AddNumberToObject(obj, "requests", (double)traffic->requests);
+    cJSON_AddNumberToObject(obj, "failures", (double)traffic->failures);
+    cJSON_AddItemToObject(entry, "traffic", obj);
+}
+
+static void add_peer_traffic_json(cJSON *entry, const char *endpoint) {
+    junknas_peer_traffic_t traffic;
+    if (junknas_stats_get_peer(endpoint, &traffic) == 0) add_traffic_json(entry, &traffic);
+}
+
+/*
+ * since == 0 returns every peer; otherwise only peers whose status or
+ * details changed after that unix timestamp. Clients pass back the returned
+ * server_time on their next poll.
+ */
+static void respond_mesh_status(int fd, junknas_config_t *config, uint64_t since) {
     cJSON *root = cJSON_CreateObject();
     if (!root) {
         send_status(fd, 500, "Error");
         return;
     }
+    if (since != 0) cJSON_AddNumberToObject(root, "since", (double)since);
 
     junknas_config_lock(config);
+    /* changed_at has one-second resolution, so a change later in this same
+     * second would be <= a cursor of now. Hand out now - 1: the next poll
+     * repeats this second's changes rather than missing any.
+     */
+    cJSON_AddNumberToObject(root, "server_time", (double)(time(NULL) - 1));
+    cJSON_AddBoolToObject(root, "config_persisted", config->config_read_only ? 0 : 1);
     int bootstrap_count = config->bootstrap_peer_count;
     int wg_count = config->wg_peer_count;
     int any_reachable = 0;
@@ -455,18 +499,40 @@ static void respond_mesh_status(int fd, junknas_config_t *config) {
     cJSON *bootstrap = cJSON_CreateArray();
     if (bootstrap) {
         for (int i = 0; i < bootstrap_count; i++) {
+            if (config->bootstrap_peer_changed_at[i] <= since && since != 0) continue;
             cJSON *entry = cJSON_CreateObject();
             if (!entry) continue;
             cJSON_AddStringToObject(entry, "endpoint", config->bootstrap_peers[i]);
             cJSON_AddStringToObject(entry, "status", status_label(config->bootstrap_peer_status[i]));
+            cJSON_AddNumberToObject(entry, "changed_at", (double)config->bootstrap_peer_changed_at[i]);
+            add_peer_traffic_json(entry, config->bootstrap_peers[i]);
             cJSON_AddItemToArray(bootstrap, entry);
         }
         cJSON_AddItemToObject(root, "bootstrap_peers", bootstrap);
     }
 
+    int have_secret = (config->mesh_secret[0] != '\0');
+    int require_encrypted = config->require_encrypted_mesh;
+    junknas_mesh_link_security_t security[MAX_WG_PEERS];
+    int weak_links = 0;
+    for (int i = 0; i < wg_count; i++) {
+        const junknas_wg_peer_t *peer = &config->wg_peers[i];
+        int wg_routed = (peer->wg_ip[0] != '\0' && junknas_net_route_is_wireguard(peer->wg_ip) == 1);
+        security[i] = junknas_mesh_peer_security(peer, have_secret, wg_routed);
+        if (security[i] != JUNKNAS_MESH_LINK_ENCRYPTED) weak_links++;
+    }
+    cJSON_AddBoolToObject(root, "require_encrypted_mesh", require_encrypted ? 1 : 0);
+    cJSON_AddNumberToObject(root, "unencrypted_peers", (double)weak_links);
+    if (require_encrypted && weak_links > 0) {
+        cJSON_AddStringToObject(root, "warning",
+                                "require_encrypted_mesh is set but some peers have no wg_ip routed through "
+                                "WireGuard; no chunks are exchanged with them");
+    }
+
     cJSON *wg = cJSON_CreateArray();
     if (wg) {
         for (int i = 0; i < wg_count; i++) {
+            if (config->wg_peer_changed_at[i] <= since && since != 0) continue;
             cJSON *entry = cJSON_CreateObject();
             if (!entry) continue;
             cJSON_AddStringToObject(entry, "endpoint", config->wg_peers[i].endpoint);
@@ -474,6 +540,12 @@ static void respond_mesh_status(int fd, junknas_config_t *config) {
                                     (double)(config->wg_peers[i].web_port ? config->wg_peers[i].web_port
                                                                           : config->web_port));
             cJSON_AddStringToObject(entry, "status", status_label(config->wg_peer_status[i]));
+            cJSON_AddStringToObject(entry, "security", junknas_mesh_link_security_name(security[i]));
+            cJSON_AddNumberToObject(entry, "changed_at", (double)config->wg_peer_changed_at[i]);
+            junknas_peer_traffic_t traffic;
+            if (junknas_mesh_peer_traffic(&config->wg_peers[i], config->web_port, &traffic) == 0) {
+                add_traffic_json(entry, &traffic);
+            }
             cJSON_AddItemToArray(wg, entry);
         }
         cJSON_AddItemToObject(root, "wg_peers", wg);
@@ -490,6 +562,103 @@ static void respond_mesh_status(int fd, junknas_config_t *config) {
     free(printed);
 }
 
+static void respond_metrics(int fd, junknas_config_t *config) {
+    char *counters = junknas_stats_render_prometheus();
+    if (!counters) {
+        send_status(fd, 500, "Error");
+        return;
+    }
+
+    junknas_strbuf_t gauges = {0};
+    junknas_strbuf_printf(&gauges,
+                          "# HELP junknas_drive_bytes Filesystem size, free space after drive_reserve, the reserve and any allocation cap under each data dir.\n"
+                          "# TYPE junknas_drive_bytes gauge\n");
+
+    junknas_config_lock(config);
+    int dir_count = config->data_dir_count;
+    char dirs[MAX_DATA_DIRS][MAX_PATH_LEN];
+    unsigned long long caps[MAX_DATA_DIRS] = {0};
+    for (int i = 0; i < dir_count; i++) {
+        snprintf(dirs[i], sizeof(dirs[i]), "%s", config->data_dirs[i]);
+        caps[i] = (unsigned long long)config->data_dir_caps[i];
+    }
+    if (dir_count == 0) {
+        snprintf(dirs[0], sizeof(dirs[0]), "%s", config->data_dir);
+        caps[0] = (unsigned long long)config->data_dir_caps[0];
+        dir_count = 1;
+    }
+    int connected = 0;
+    for (int i = 0; i < config->wg_peer_count; i++) {
+        if (config->wg_peer_status[i] == 1) connected++;
+    }
+    int peer_count = config->wg_peer_count;
+    uint64_t quota = config->max_storage_bytes;
+    char reserve_spec[sizeof(config->drive_reserve)];
+    snprintf(reserve_spec, sizeof(reserve_spec), "%s", config->drive_reserve);
+    junknas_config_unlock(config);
+
+    char labels[MAX_DATA_DIRS][JUNKNAS_STATS_LABEL_LEN(MAX_PATH_LEN)];
+    for (int i = 0; i < dir_count; i++) {
+        junknas_stats_escape_label(dirs[i], labels[i], sizeof(labels[i]));
+    }
+
+    for (int i = 0; i < dir_count; i++) {
+        struct statvfs vfs;
+        if (statvfs(dirs[i], &vfs) != 0) continue;
+        unsigned long long total = (unsigned long long)vfs.f_blocks * (unsigned long long)vfs.f_frsize;
+        unsigned long long avail = (unsigned long long)vfs.f_bavail * (unsigned long long)vfs.f_frsize;
+        unsigned long long reserve = junknas_drive_reserve_bytes(reserve_spec, total);
+        if (avail > reserve) {
+            avail -= reserve;
+        } else {
+            avail = 0;
+        }
+        junknas_strbuf_printf(&gauges,
+                              "junknas_drive_bytes{dir=\"%s\",kind=\"total\"} %llu\n"
+                              "junknas_drive_bytes{dir=\"%s\",kind=\"available\"} %llu\n"
+                              "junknas_drive_bytes{dir=\"%s\",kind=\"reserved\"} %llu\n",
+                              labels[i], total, labels[i], avail, labels[i], reserve);
+        if (caps[i] != 0) {
+            junknas_strbuf_printf(&gauges, "junknas_drive_bytes{dir=\"%s\",kind=\"cap\"} %llu\n",
+                                  labels[i], caps[i]);
+        }
+    }
+    junknas_strbuf_printf(&gauges,
+                          "# HELP junknas_drive_read_only 1 if the data dir's filesystem is mounted read-only (no new chunks are placed there).\n"
+                          "# TYPE junknas_drive_read_only gauge\n");
+    for (int i = 0; i < dir_count; i++) {
+        struct statvfs vfs;
+        if (statvfs(dirs[i], &vfs) != 0) continue;
+        junknas_strbuf_printf(&gauges, "junknas_drive_read_only{dir=\"%s\"} %d\n",
+                              labels[i], (vfs.f_flag & ST_RDONLY) ? 1 : 0);
+    }
+    junknas_strbuf_printf(&gauges,
+                          "# HELP junknas_storage_quota_bytes Configured chunk store quota.\n"
+                          "# TYPE junknas_storage_quota_bytes gauge\n"
+                          "junknas_storage_quota_bytes %llu\n"
+                          "# HELP junknas_mesh_peers Known LAN peers by status.\n"
+                          "# TYPE junknas_mesh_peers gauge\n"
+                          "junknas_mesh_peers{status=\"connected\"} %d\n"
+                          "junknas_mesh_peers{status=\"other\"} %d\n",
+                          (unsigned long long)quota, connected, peer_count - connected);
+    if (gauges.failed) {
+        free(gauges.buf);
+        free(counters);
+        send_status(fd, 500, "Error");
+        return;
+    }
+
+    char header[256];
+    snprintf(header, sizeof(header),
+             "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: %zu\r\nConnection: close\r\n\r\n",
+             strlen(counters) + gauges.len);
+    send_all(fd, header);
+    send_all(fd, counters);
+    send(fd, gauges.buf, gauges.len, 0);
+    free(gauges.buf);
+    free(counters);
+}
+
 static void respond_mesh_ui(int fd) {
     send_html_header(fd, "junkNAS mesh");
     send_all(fd,
@@ -677,21 +846,24 @@ static void respond_mesh_ui(int fd) {
              "msg.textContent='Join failed.';"
              "}"
              "});"
+             "if(window.EventSource){"
+             "const events=new EventSource('/events');"
+             "events.addEventListener('peer_status',()=>{loadStatus();});"
+             "['peers_updated','mounts_updated','config_updated'].forEach(name=>"
+             "events.addEventListener(name,()=>{loadConfig();}));"
+             "}"
              "loadConfig();"
              "</script>");
     send_html_footer(fd);
 }
 
-static int mesh_mount_points_equal(const junknas_config_t *config, cJSON *mounts) {
-    if (!config || !cJSON_IsArray(mounts)) return 0;
-
-    int n = cJSON_GetArraySize(mounts);
-    if (n != config->data_mount_point_count) return 0;
+static int mesh_mount_points_equal(const junknas_config_t *config,
+                                   char mounts[][MAX_PATH_LEN], int count) {
+    if (!config) return 0;
+    if (count != config->data_mount_point_count) return 0;
 
-    for (int i = 0; i < n; i++) {
-        cJSON *entry = cJSON_GetArrayItem(mounts, i);
-        if (!cJSON_IsString(entry) || !entry->valuestring) return 0;
-        if (strcmp(config->data_mount_points[i], entry->valuestring) != 0) return 0;
+    for (int i = 0; i < count; i++) {
+        if (strcmp(config->data_mount_points[i], mounts[i]) != 0) return 0;
     }
 
     return 1;
@@ -704,35 +876,48 @@ static int merge_mesh_payload(junknas_config_t *config, const char *payload) {
 
     int peers_changed = 0;
     int mounts_changed = 0;
+    int peers_rejected = 0;
+    int mounts_rejected = 0;
     time_t now = time(NULL);
 
-    junknas_config_lock(config);
-    const char *local_endpoint = config->wg.endpoint;
-
-    cJSON *self = cJSON_GetObjectItemCaseSensitive(root, "self");
-    if (cJSON_IsObject(self)) {
-        junknas_wg_peer_t peer = {0};
-        if (parse_peer_json(self, &peer) == 0) {
-            if (local_endpoint[0] == '\0' || strcmp(local_endpoint, peer.endpoint) != 0) {
-                int rc = junknas_config_upsert_wg_peer(config, &peer);
-                if (rc == 1) peers_changed = 1;
-            }
-        }
-    }
+    junknas_wg_peer_t incoming[MAX_WG_PEERS];
+    int incoming_count = 0;
 
     cJSON *peers = cJSON_GetObjectItemCaseSensitive(root, "peers");
     if (cJSON_IsArray(peers)) {
         int n = cJSON_GetArraySize(peers);
-        for (int i = 0; i < n; i++) {
+        for (int i = 0; i < n && incoming_count < MAX_WG_PEERS; i++) {
             cJSON *entry = cJSON_GetArrayItem(peers, i);
             junknas_wg_peer_t peer = {0};
-            if (parse_peer_json(entry, &peer) != 0) continue;
-            if (local_endpoint[0] != '\0' && strcmp(local_endpoint, peer.endpoint) == 0) continue;
-            int rc = junknas_config_upsert_wg_peer(config, &peer);
-            if (rc == 1) peers_changed = 1;
+            if (parse_peer_json(entry, &peer) == 0) {
+                incoming[incoming_count++] = peer;
+            } else {
+                peers_rejected++;
+            }
         }
     }
 
+    cJSON *self = cJSON_GetObjectItemCaseSensitive(root, "self");
+    if (cJSON_IsObject(self) && incoming_count < MAX_WG_PEERS) {
+        junknas_wg_peer_t peer = {0};
+        if (parse_peer_json(self, &peer) == 0) {
+            incoming[incoming_count++] = peer;
+        } else {
+            peers_rejected++;
+        }
+    }
+
+    peers_rejected += junknas_collapse_duplicate_peers(incoming, &incoming_count);
+
+    junknas_config_lock(config);
+    const char *local_endpoint = config->wg.endpoint;
+
+    for (int i = 0; i < incoming_count; i++) {
+        if (local_endpoint[0] != '\0' && strcmp(local_endpoint, incoming[i].endpoint) == 0) continue;
+        int rc = junknas_config_upsert_wg_peer(config, &incoming[i]);
+        if (rc == 1) peers_changed = 1;
+    }
+
     cJSON *mounts_updated = cJSON_GetObjectItemCaseSensitive(root, "mounts_updated_at");
     uint64_t remote_mounts_updated = 0;
     if (cJSON_IsNumber(mounts_updated) && mounts_updated->valuedouble >= 0) {
@@ -741,15 +926,12 @@ static int merge_mesh_payload(junknas_config_t *config, const char *payload) {
     if (remote_mounts_updated >= config->data_mount_points_updated_at) {
         cJSON *mounts = cJSON_GetObjectItemCaseSensitive(root, "mount_points");
         if (cJSON_IsArray(mounts)) {
-            int same = mesh_mount_points_equal(config, mounts);
-            if (!same) {
+            char clean[MAX_DATA_MOUNT_POINTS][MAX_PATH_LEN];
+            int clean_count = junknas_sanitize_mount_points(mounts, clean, MAX_DATA_MOUNT_POINTS, &mounts_rejected);
+            if (!mesh_mount_points_equal(config, clean, clean_count)) {
                 config->data_mount_point_count = 0;
-                int n = cJSON_GetArraySize(mounts);
-                for (int i = 0; i < n && config->data_mount_point_count < MAX_DATA_MOUNT_POINTS; i++) {
-                    cJSON *entry = cJSON_GetArrayItem(mounts, i);
-                    if (cJSON_IsString(entry) && entry->valuestring) {
-                        (void)junknas_config_add_data_mount_point(config, entry->valuestring);
-                    }
+                for (int i = 0; i < clean_count; i++) {
+                    (void)junknas_config_add_data_mount_point(config, clean[i]);
                 }
                 mounts_changed = 1;
             }
@@ -769,6 +951,14 @@ static int merge_mesh_payload(junknas_config_t *config, const char *payload) {
     junknas_config_unlock(config);
 
     cJSON_Delete(root);
+    if (peers_rejected || mounts_rejected) {
+        junknas_stats_add(JUNKNAS_STAT_MESH_PEER_REJECTED, (uint64_t)peers_rejected);
+        junknas_stats_add(JUNKNAS_STAT_MESH_MOUNT_REJECTED, (uint64_t)mounts_rejected);
+        web_log_verbose(config, "mesh: dropped %d malformed peer(s) and %d mount point(s) from sync payload",
+                        peers_rejected, mounts_rejected);
+    }
+    if (peers_changed) junknas_events_publish("peers_updated", NULL);
+    if (mounts_changed) junknas_events_publish("mounts_updated", NULL);
     return (peers_changed || mounts_changed) ? 1 : 0;
 }
 
@@ -841,6 +1031,7 @@ static int update_mesh_config(junknas_config_t *config, const char *payload) {
                  sizeof(config->bootstrap_peers[config->bootstrap_peer_count]),
                  "%s", bootstrap[i]);
         config->bootstrap_peer_status[config->bootstrap_peer_count] = -1;
+        config->bootstrap_peer_changed_at[config->bootstrap_peer_count] = (uint64_t)now;
         config->bootstrap_peer_count++;
     }
     config->bootstrap_peers_updated_at = (uint64_t)now;
@@ -848,7 +1039,7 @@ static int update_mesh_config(junknas_config_t *config, const char *payload) {
     if (has_wg_peers) {
         (void)junknas_config_set_wg_peers(config, peers, peer_count);
         for (int i = 0; i < config->wg_peer_count; i++) {
-            config->wg_peer_status[i] = -1;
+            (void)junknas_config_set_wg_peer_status(config, i, -1);
         }
         config->wg_peers_updated_at = (uint64_t)now;
     }
@@ -863,6 +1054,7 @@ static int update_mesh_config(junknas_config_t *config, const char *payload) {
     junknas_config_unlock(config);
 
     cJSON_Delete(root);
+    junknas_events_publish("config_updated", NULL);
     return 0;
 }
 
@@ -1025,8 +1217,13 @@ static int sync_mesh_with_peer(junknas_config_t *config, const char *endpoint, c
              "POST /mesh/peers HTTP/1.1\r\nHost: %s\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: %zu\r\n\r\n",
              host, payload_len);
 
+    char preference[sizeof(config->endpoint_preference)];
+    junknas_config_lock(config);
+    snprintf(preference, sizeof(preference), "%s", config->endpoint_preference);
+    junknas_config_unlock(config);
+
     int status = 0;
-    char *body = http_request_body(host, port, request, payload, payload_len, &status);
+    char *body = http_request_body(host, port, preference, request, payload, payload_len, &status);
     if (!body) return -1;
 
     if (status >= 200 && status < 300) {
@@ -1041,7 +1238,11 @@ static int sync_mesh_with_peer(junknas_config_t *config, const char *endpoint, c
     return -1;
 }
 
-static void respond_mount_listing(int fd, junknas_config_t *config, const char *rel_path) {
+/* Listing of a directory under the mount. Only names starting with prefix
+ * (if non-empty) are shown, at most limit of them (0 = all).
+ */
+static void respond_mount_listing(int fd, junknas_config_t *config, const char *rel_path,
+                                  const char *prefix, uint64_t limit) {
     char full_path[MAX_PATH_LEN];
     if (rel_path && rel_path[0] != '\0') {
         snprintf(full_path, sizeof(full_path), "%s/%s", config->mount_point, rel_path);
@@ -1078,9 +1279,18 @@ static void respond_mount_listing(int fd, junknas_config_t *config, const char *
     }
 
     send_all(fd, "<h2>Directory listing</h2><ul>");
+    size_t prefix_len = prefix ? strlen(prefix) : 0;
+    uint64_t shown = 0;
+    int truncated = 0;
     struct dirent *ent;
     while ((ent = readdir(dir)) != NULL) {
         if (strcmp(ent->d_name, ".") == 0 || strcmp(ent->d_name, "..") == 0) continue;
+        if (prefix_len > 0 && strncmp(ent->d_name, prefix, prefix_len) != 0) continue;
+        if (limit != 0 && shown >= limit) {
+            truncated = 1;
+            break;
+        }
+        shown++;
         send_all(fd, "<li>");
         if (ent->d_type == DT_DIR) {
             send_all(fd, "<strong>");
@@ -1108,6 +1318,12 @@ static void respond_mount_listing(int fd, junknas_config_t *config, const char *
     }
     closedir(dir);
     send_all(fd, "</ul>");
+    if (truncated) {
+        char note[96];
+        snprintf(note, sizeof(note), "<p class=\"truncated\">Showing the first %llu entries.</p>",
+                 (unsigned long long)shown);
+        send_all(fd, note);
+    }
     send_html_footer(fd);
 }
 
@@ -1159,25 +1375,394 @@ static void ensure_parent_dir(const char *path) {
     mkdir(tmp, 0755);
 }
 
-static void handle_get(web_conn_t *conn, const char *path) {
+static int hex_nibble(char c) {
+    if (c >= '0' && c <= '9') return c - '0';
+    if (c >= 'a' && c <= 'f') return c - 'a' + 10;
+    if (c >= 'A' && c <= 'F') return c - 'A' + 10;
+    return -1;
+}
+
+/* Copy the value of name=... from a query string ("a=1&b=2") into out,
+ * decoding %XX escapes and '+' as a space. A bad escape is copied as is.
+ */
+static int query_param(const char *query, const char *name, char *out, size_t out_len) {
+    if (!query || !name || !out || out_len == 0) return -1;
+    size_t nlen = strlen(name);
+    const char *p = query;
+    while (*p) {
+        if (strncmp(p, name, nlen) == 0 && p[nlen] == '=') {
+            p += nlen + 1;
+            size_t n = 0;
+            while (*p != '\0' && *p != '&' && n + 1 < out_len) {
+                int hi, lo;
+                if (*p == '%' && (hi = hex_nibble(p[1])) >= 0 && (lo = hex_nibble(p[2])) >= 0) {
+                    out[n++] = (char)((hi << 4) | lo);
+                    p += 3;
+                } else {
+                    out[n++] = (*p == '+') ? ' ' : *p;
+                    p++;
+                }
+            }
+            out[n] = '\0';
+            return 0;
+        }
+        p = strchr(p, '&');
+        if (!p) break;
+        p++;
+    }
+    return -1;
+}
+
+static uint64_t query_param_u64(const char *query, const char *name) {
+    char value[32];
+    if (query_param(query, name, value, sizeof(value)) != 0) return 0;
+    char *end = NULL;
+    unsigned long long v = strtoull(value, &end, 10);
+    if (end == value || *end != '\0') return 0;
+    return (uint64_t)v;
+}
+
+/* ------------------------------ Dedup index ----------------------------- */
+
+/* Shared chunks are refcounted in <data_dir>/.jnk/refs/<ab>/<hash>.ref, one
+ * count per manifest line that names the hash. These helpers read that index
+ * back and rebuild it from the manifests for /dedup/stats and /dedup/verify.
+ */
+
+#define WEB_DEDUP_MAX_REPORTED 64
+
+typedef struct {
+    char hash[65];
+    long long count;
+} web_dedup_ref_t;
+
+typedef struct {
+    web_dedup_ref_t *items;
+    size_t count;
+    size_t cap;
+} web_dedup_refs_t;
+
+static int dedup_refs_push(web_dedup_refs_t *list, const char *hash, long long count) {
+    if (list->count == list->cap) {
+        size_t cap = list->cap ? list->cap * 2 : 256;
+        web_dedup_ref_t *items = realloc(list->items, cap * sizeof(*items));
+        if (!items) return -1;
+        list->items = items;
+        list->cap = cap;
+    }
+    snprintf(list->items[list->count].hash, sizeof(list->items[0].hash), "%s", hash);
+    list->items[list->count].count = count;
+    list->count++;
+    return 0;
+}
+
+static int dedup_ref_cmp(const void *a, const void *b) {
+    return strcmp(((const web_dedup_ref_t *)a)->hash, ((const web_dedup_ref_t *)b)->hash);
+}
+
+static int dedup_refs_path(const char *data_dir, char *out, size_t out_len) {
+    return snprintf(out, out_len, "%s/%s", data_dir, JUNKNAS_REFS_SUBDIR) >= (int)out_len ? -1 : 0;
+}
+
+/* Every <hash>.ref file under the refs dir with its stored count, sorted by hash. */
+static int dedup_load_refs(const char *data_dir, web_dedup_refs_t *out) {
+    char refs_dir[MAX_PATH_LEN];
+    if (dedup_refs_path(data_dir, refs_dir, sizeof(refs_dir)) != 0) return -1;
+
+    DIR *top = opendir(refs_dir);
+    if (!top) return (errno == ENOENT) ? 0 : -1;
+    struct dirent *shard;
+    while ((shard = readdir(top)) != NULL) {
+        if (strlen(shard->d_name) != 2) continue;
+        char shard_dir[MAX_PATH_LEN];
+        if (snprintf(shard_dir, sizeof(shard_dir), "%s/%s", refs_dir, shard->d_name) >= (int)sizeof(shard_dir)) continue;
+        DIR *d = opendir(shard_dir);
+        if (!d) continue;
+        struct dirent *ent;
+        while ((ent = readdir(d)) != NULL) {
+            char hash[65];
+            if (strlen(ent->d_name) != 68 || strcmp(ent->d_name + 64, ".ref") != 0) continue;
+            memcpy(hash, ent->d_name, 64);
+            hash[64] = '\0';
+            if (!is_hex64(hash)) continue;
+
+            char ref_path[MAX_PATH_LEN];
+            if (snprintf(ref_path, sizeof(ref_path), "%s/%s", shard_dir, ent->d_name) >= (int)sizeof(ref_path)) continue;
+            FILE *f = fopen(ref_path, "r");
+            if (!f) continue;
+            long long count = 0;
+            if (fscanf(f, "%lld", &count) != 1 || count < 0) count = 0;
+            fclose(f);
+            if (dedup_refs_push(out, hash, count) != 0) {
+                closedir(d);
+                closedir(top);
+                return -1;
+            }
+        }
+        closedir(d);
+    }
+    closedir(top);
+    qsort(out->items, out->count, sizeof(*out->items), dedup_ref_cmp);
+    return 0;
+}
+
+/* Count each hash named by the manifests under dir (recursively, skipping .jnk). */
+static int dedup_scan_manifests(const char *dir, int top_level, web_dedup_refs_t *out, size_t *manifests) {
+    DIR *d = opendir(dir);
+    if (!d) return -1;
+    size_t suffix_len = strlen(JUNKNAS_META_SUFFIX);
+    struct dirent *ent;
+    int rc = 0;
+    while (rc == 0 && (ent = readdir(d)) != NULL) {
+        if (strcmp(ent->d_name, ".") == 0 || strcmp(ent->d_name, "..") == 0) continue;
+        if (top_level && strcmp(ent->d_name, ".jnk") == 0) continue;
+
+        char path[MAX_PATH_LEN];
+        if (snprintf(path, sizeof(path), "%s/%s", dir, ent->d_name) >= (int)sizeof(path)) continue;
+        struct stat st;
+        if (lstat(path, &st) != 0) continue;
+        if (S_ISDIR(st.st_mode)) {
+            rc = dedup_scan_manifests(path, 0, out, manifests);
+            continue;
+        }
+        size_t name_len = strlen(ent->d_name);
+        if (!S_ISREG(st.st_mode) || name_len <= suffix_len ||
+            strcmp(ent->d_name + name_len - suffix_len, JUNKNAS_META_SUFFIX) != 0) {
+            continue;
+        }
+
+        FILE *f = fopen(path, "r");
+        if (!f) continue;
+        (*manifests)++;
+        char line[256];
+        while (fgets(line, sizeof(line), f)) {
+            size_t idx = 0;
+            char hash[65] = {0};
+            if (sscanf(line, "chunk %zu %64s", &idx, hash) != 2 || !is_hex64(hash)) continue;
+            if (dedup_refs_push(out, hash, 1) != 0) {
+                rc = -1;
+                break;
+            }
+        }
+        fclose(f);
+    }
+    closedir(d);
+    return rc;
+}
+
+/* Overwrite one ref file with count (removing it at 0), under the same flock the FUSE layer takes. */
+static int dedup_write_ref(const char *data_dir, const char *hash, long long count) {
+    char ref_path[MAX_PATH_LEN];
+    if (snprintf(ref_path, sizeof(ref_path), "%s/%s/%c%c/%s.ref",
+                 data_dir, JUNKNAS_REFS_SUBDIR, hash[0], hash[1], hash) >= (int)sizeof(ref_path)) {
+        return -1;
+    }
+    if (count == 0) {
+        return (unlink(ref_path) == 0 || errno == ENOENT) ? 0 : -1;
+    }
+    ensure_parent_dir(ref_path);
+    int fd = open(ref_path, O_RDWR | O_CREAT, 0644);
+    if (fd < 0) return -1;
+    if (flock(fd, LOCK_EX) != 0) {
+        close(fd);
+        return -1;
+    }
+    char buf[32];
+    int n = snprintf(buf, sizeof(buf), "%lld\n", count);
+    int rc = (ftruncate(fd, 0) == 0 && pwrite(fd, buf, (size_t)n, 0) == n && fsync(fd) == 0) ? 0 : -1;
+    flock(fd, LOCK_UN);
+    close(fd);
+    return rc;
+}
+
+/* GET /dedup/stats: size of the refcount index and the space it saves. */
+static void respond_dedup_stats(int fd, junknas_config_t *config) {
+    web_dedup_refs_t refs = {0};
+    if (dedup_load_refs(config->data_dir, &refs) != 0) {
+        free(refs.items);
+        send_status(fd, 500, "Error");
+        return;
+    }
+
+    unsigned long long unique = 0, references = 0, stored = 0, saved = 0;
+    for (size_t i = 0; i < refs.count; i++) {
+        if (refs.items[i].count <= 0) continue;
+        unique++;
+        references += (unsigned long long)refs.items[i].count;
+        char chunk_path[MAX_PATH_LEN];
+        struct stat st;
+        if (find_chunk_path(config, refs.items[i].hash, chunk_path, sizeof(chunk_path)) != 0 ||
+            stat(chunk_path, &st) != 0) {
+            continue;
+        }
+        stored += (unsigned long long)st.st_size;
+        saved += (unsigned long long)st.st_size * (unsigned long long)(refs.items[i].count - 1);
+    }
+    free(refs.items);
+
+    char response[256];
+    snprintf(response, sizeof(response),
+             "{\"unique_chunks\":%llu,\"references\":%llu,\"stored_bytes\":%llu,\"saved_bytes\":%llu}",
+             unique, references, stored, saved);
+    send_json(fd, 200, response);
+}
+
+typedef struct {
+    junknas_config_t *config;
+    int repair;
+    char *printed;      /* JSON report */
+} web_dedup_verify_t;
+
+/* Runs with the filesystem's refcounts held still (junknas_fuse_with_refs_locked). */
+static int dedup_verify_locked(void *ctx, char **open_hashes, size_t open_count) {
+    web_dedup_verify_t *v = (web_dedup_verify_t *)ctx;
+    web_dedup_refs_t stored = {0};
+    web_dedup_refs_t expected = {0};
+    size_t manifests = 0;
+    if (dedup_load_refs(v->config->data_dir, &stored) != 0 ||
+        dedup_scan_manifests(v->config->data_dir, 1, &expected, &manifests) != 0) {
+        free(stored.items);
+        free(expected.items);
+        return -1;
+    }
+    /* Unlinked while open: no manifest, but the refs stay until the last close. */
+    for (size_t k = 0; k < open_count; k++) {
+        if (is_hex64(open_hashes[k]) && dedup_refs_push(&expected, open_hashes[k], 1) != 0) {
+            free(stored.items);
+            free(expected.items);
+            return -1;
+        }
+    }
+    qsort(expected.items, expected.count, sizeof(*expected.items), dedup_ref_cmp);
+
+    cJSON *root = cJSON_CreateObject();
+    cJSON *list = cJSON_CreateArray();
+    size_t checked = 0, mismatched = 0, repaired = 0, failed = 0;
+    size_t i = 0, j = 0;
+    while (i < stored.count || j < expected.count) {
+        const char *hash;
+        if (j >= expected.count) hash = stored.items[i].hash;
+        else if (i >= stored.count) hash = expected.items[j].hash;
+        else hash = (strcmp(stored.items[i].hash, expected.items[j].hash) <= 0) ? stored.items[i].hash
+                                                                                 : expected.items[j].hash;
+        char cur[65];
+        snprintf(cur, sizeof(cur), "%s", hash);
+
+        long long have = 0, want = 0;
+        while (i < stored.count && strcmp(stored.items[i].hash, cur) == 0) have += stored.items[i++].count;
+        while (j < expected.count && strcmp(expected.items[j].hash, cur) == 0) want += expected.items[j++].count;
+        checked++;
+        if (have == want) continue;
+
+        mismatched++;
+        int fixed = 0;
+        if (v->repair) {
+            if (dedup_write_ref(v->config->data_dir, cur, want) == 0) {
+                fixed = 1;
+                repaired++;
+            } else {
+                failed++;
+            }
+        }
+        if (mismatched <= WEB_DEDUP_MAX_REPORTED) {
+            cJSON *entry = cJSON_CreateObject();
+            cJSON_AddStringToObject(entry, "hash", cur);
+            cJSON_AddNumberToObject(entry, "stored", (double)have);
+            cJSON_AddNumberToObject(entry, "expected", (double)want);
+            if (v->repair) cJSON_AddBoolToObject(entry, "repaired", fixed);
+            cJSON_AddItemToArray(list, entry);
+        }
+    }
+    free(stored.items);
+    free(expected.items);
+
+    if (mismatched > 0) {
+        fprintf(stderr, "web: dedup verify found %zu refcount mismatch(es) across %zu manifest(s)%s\n",
+                mismatched, manifests, v->repair ? ", rewrote them" : "");
+    }
+
+    cJSON_AddNumberToObject(root, "manifests", (double)manifests);
+    cJSON_AddNumberToObject(root, "open_unlinked_refs", (double)open_count);
+    cJSON_AddNumberToObject(root, "chunks_checked", (double)checked);
+    cJSON_AddNumberToObject(root, "mismatched", (double)mismatched);
+    cJSON_AddNumberToObject(root, "repaired", (double)repaired);
+    cJSON_AddNumberToObject(root, "repair_failed", (double)failed);
+    cJSON_AddItemToObject(root, "mismatches", list);
+    v->printed = cJSON_PrintUnformatted(root);
+    cJSON_Delete(root);
+    return v->printed ? 0 : -1;
+}
+
+/* POST /dedup/verify {"repair": bool}: recount refs from the manifests and
+ * report (optionally rewrite) every ref file that disagrees. Chunk files are
+ * never deleted here, even when their count drops to zero. The pass runs
+ * under the mounted filesystem's lock, so it needs the mount to be up.
+ */
+static void respond_dedup_verify(int fd, junknas_config_t *config, const char *body) {
+    int repair = 0;
+    if (body && body[0] != '\0') {
+        cJSON *root = cJSON_Parse(body);
+        if (!root) {
+            send_status(fd, 400, "Bad Request");
+            return;
+        }
+        cJSON *flag = cJSON_GetObjectItemCaseSensitive(root, "repair");
+        if (flag && !cJSON_IsBool(flag)) {
+            cJSON_Delete(root);
+            send_status(fd, 400, "Bad Request");
+            return;
+        }
+        repair = cJSON_IsTrue(flag) ? 1 : 0;
+        cJSON_Delete(root);
+    }
+
+    web_dedup_verify_t v = { .config = config, .repair = repair, .printed = NULL };
+    int rc = junknas_fuse_with_refs_locked(dedup_verify_locked, &v);
+    if (rc == -EAGAIN) {
+        send_status(fd, 503, "Service Unavailable");
+        return;
+    }
+    if (rc != 0) {
+        free(v.printed);
+        send_status(fd, 500, "Error");
+        return;
+    }
+    send_json(fd, 200, v.printed);
+    free(v.printed);
+}
+
+static void handle_get(web_conn_t *conn, const char *target) {
+    char path[512];
+    snprintf(path, sizeof(path), "%s", target);
+    const char *query = "";
+    char *qmark = strchr(path, '?');
+    if (qmark) {
+        *qmark = '\0';
+        query = qmark + 1;
+    }
+
     if (strcmp(path, "/") == 0) {
-        respond_mount_listing(conn->fd, conn->config, "");
+        char prefix[NAME_MAX + 1] = "";
+        (void)query_param(query, "prefix", prefix, sizeof(prefix));
+        respond_mount_listing(conn->fd, conn->config, "", prefix, query_param_u64(query, "limit"));
         return;
     }
 
     if (strncmp(path, "/browse/", 8) == 0) {
-        const char *rel = path + 8;
-        if (!is_safe_relative(rel)) {
+        char rel[MAX_PATH_LEN];
+        if (normalize_relative(path + 8, rel, sizeof(rel)) != 0) {
             send_status(conn->fd, 400, "Bad Request");
             return;
         }
-        respond_mount_listing(conn->fd, conn->config, rel);
+        char prefix[NAME_MAX + 1] = "";
+        (void)query_param(query, "prefix", prefix, sizeof(prefix));
+        respond_mount_listing(conn->fd, conn->config, rel, prefix, query_param_u64(query, "limit"));
         return;
     }
 
     if (strncmp(path, "/files/", 7) == 0) {
-        const char *rel = path + 7;
-        if (!is_safe_relative(rel)) {
+        char rel[MAX_PATH_LEN];
+        if (normalize_relative(path + 7, rel, sizeof(rel)) != 0 || rel[0] == '\0') {
             send_status(conn->fd, 400, "Bad Request");
             return;
         }
@@ -1213,7 +1798,17 @@ static void handle_get(web_conn_t *conn, const char *path) {
     }
 
     if (strcmp(path, "/mesh/status") == 0) {
-        respond_mesh_status(conn->fd, conn->config);
+        respond_mesh_status(conn->fd, conn->config, query_param_u64(query, "since"));
+        return;
+    }
+
+    if (strcmp(path, "/metrics") == 0) {
+        respond_metrics(conn->fd, conn->config);
+        return;
+    }
+
+    if (strcmp(path, "/dedup/stats") == 0) {
+        respond_dedup_stats(conn->fd, conn->config);
         return;
     }
 
@@ -1265,6 +1860,142 @@ static long parse_content_length(const char *headers) {
     return val;
 }
 
+/* Whether the connection arrived on this node's overlay (wg_ip) address.
+ * Chunk routes are only served there, never on the LAN or public side.
+ * With require_wireguard that address must also sit on a WireGuard
+ * interface, not just be configured somewhere.
+ */
+static int conn_on_overlay(web_conn_t *conn, int require_wireguard) {
+    char wg_ip[sizeof(conn->config->wg.wg_ip)];
+    junknas_config_lock(conn->config);
+    snprintf(wg_ip, sizeof(wg_ip), "%s", conn->config->wg.wg_ip);
+    junknas_config_unlock(conn->config);
+    if (wg_ip[0] == '\0') return 0;
+
+    struct sockaddr_in local;
+    socklen_t local_len = sizeof(local);
+    if (getsockname(conn->fd, (struct sockaddr *)&local, &local_len) != 0 ||
+        local.sin_family != AF_INET) {
+        return 0;
+    }
+    struct in_addr overlay;
+    if (inet_pton(AF_INET, wg_ip, &overlay) != 1) return 0;
+    if (local.sin_addr.s_addr != overlay.s_addr) return 0;
+    return !require_wireguard || junknas_net_addr_on_wireguard((const struct sockaddr *)&local) == 1;
+}
+
+/* Returns 0 if the request may touch /chunks, else the HTTP status to
+ * reject it with: 403 off the overlay (or, with require_encrypted_mesh,
+ * off WireGuard) or with no mesh_secret configured, 401 for a missing,
+ * wrong, stale or replayed signature.
+ */
+static int chunk_request_check(web_conn_t *conn, const char *method, const char *path,
+                               const char *headers) {
+    char secret[MAX_MESH_SECRET_LEN];
+    junknas_config_lock(conn->config);
+    snprintf(secret, sizeof(secret), "%s", conn->config->mesh_secret);
+    int require_encrypted = conn->config->require_encrypted_mesh;
+    junknas_config_unlock(conn->config);
+
+    if (!conn_on_overlay(conn, require_encrypted)) {
+        web_log_verbose(conn->config, "web: rejected %s %s: not on the overlay address%s", method, path,
+                        require_encrypted ? " over WireGuard" : "");
+        return 403;
+    }
+
+    if (secret[0] == '\0') {
+        web_log_verbose(conn->config, "web: rejected %s %s: mesh_secret is not set", method, path);
+        return 403;
+    }
+
+    const char *hdr = find_header_case_insensitive(headers, "X-Junknas-Auth:");
+    if (!hdr) return 401;
+    hdr += strlen("X-Junknas-Auth:");
+    while (*hdr == ' ' || *hdr == '\t') hdr++;
+    char value[128];
+    size_t n = 0;
+    while (hdr[n] != '\0' && hdr[n] != '\r' && hdr[n] != '\n' && hdr[n] != ' ' && n + 1 < sizeof(value)) {
+        value[n] = hdr[n];
+        n++;
+    }
+    value[n] = '\0';
+
+    if (junknas_mesh_auth_verify(secret, method, path, value, (uint64_t)time(NULL)) != 0) {
+        web_log_verbose(conn->config, "web: rejected unauthenticated %s %s", method, path);
+        return 401;
+    }
+    return 0;
+}
+
+static int send_event_frame(int fd, const char *frame) {
+    size_t len = strlen(frame);
+    size_t off = 0;
+    while (off < len) {
+        ssize_t n = send(fd, frame + off, len - off, MSG_NOSIGNAL);
+        if (n <= 0) {
+            if (n < 0 && errno == EINTR) continue;
+            return -1;
+        }
+        off += (size_t)n;
+    }
+    return 0;
+}
+
+static void respond_events(web_conn_t *conn, const char *headers) {
+    if (__sync_add_and_fetch(&g_event_streams, 1) > WEB_EVENTS_MAX_STREAMS) {
+        __sync_sub_and_fetch(&g_event_streams, 1);
+        web_log_verbose(conn->config, "web: refused event stream, %d already open", WEB_EVENTS_MAX_STREAMS);
+        send_status(conn->fd, 503, "Service Unavailable");
+        return;
+    }
+
+    uint64_t cursor = junknas_events_latest();
+    const char *last_id = find_header_case_insensitive(headers, "Last-Event-ID:");
+    if (last_id) {
+        last_id += strlen("Last-Event-ID:");
+        while (*last_id == ' ' || *last_id == '\t') last_id++;
+        char *end = NULL;
+        unsigned long long val = strtoull(last_id, &end, 10);
+        if (end != last_id && val <= cursor) cursor = (uint64_t)val;
+    }
+
+    if (send_event_frame(conn->fd,
+                         "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n"
+                         "Cache-Control: no-cache\r\nConnection: close\r\n\r\n"
+                         "retry: 3000\n\n") != 0) {
+        __sync_sub_and_fetch(&g_event_streams, 1);
+        return;
+    }
+    web_log_verbose(conn->config, "web: event stream opened (cursor=%llu)", (unsigned long long)cursor);
+
+    int idle_sec = 0;
+    while (!g_web_stopping) {
+        junknas_event_t ev;
+        if (junknas_events_wait(cursor, &ev, 1000) == 0) {
+            if (++idle_sec < WEB_EVENTS_KEEPALIVE_SEC) continue;
+            idle_sec = 0;
+            if (send_event_frame(conn->fd, ": keepalive\n\n") != 0) break;
+            continue;
+        }
+        idle_sec = 0;
+
+        char frame[JUNKNAS_EVENT_DATA_LEN + JUNKNAS_EVENT_TYPE_LEN + 96];
+        if (ev.seq > cursor + 1) {
+            /* Fell out of the backlog: the client must refetch state. */
+            snprintf(frame, sizeof(frame), "event: reset\ndata: {\"skipped\":%llu}\n\n",
+                     (unsigned long long)(ev.seq - cursor - 1));
+            if (send_event_frame(conn->fd, frame) != 0) break;
+        }
+        cursor = ev.seq;
+
+        snprintf(frame, sizeof(frame), "id: %llu\nevent: %s\ndata: %s\n\n",
+                 (unsigned long long)ev.seq, ev.type, ev.data);
+        if (send_event_frame(conn->fd, frame) != 0) break;
+    }
+    __sync_sub_and_fetch(&g_event_streams, 1);
+    web_log_verbose(conn->config, "web: event stream closed");
+}
+
 static void handle_post_chunk(web_conn_t *conn, const char *hash, const char *headers, const char *body, size_t body_len) {
     if (!is_hex64(hash)) {
         send_status(conn->fd, 400, "Bad Request");
@@ -1276,158 +2007,474 @@ static void handle_post_chunk(web_conn_t *conn, const char *hash, const char *he
         send_status(conn->fd, 411, "Length Required");
         return;
     }
+    junknas_config_lock(conn->config);
+    size_t max_body = conn->config->max_chunk_body_bytes;
+    junknas_config_unlock(conn->config);
+    if ((size_t)content_len > max_body) {
+        send_status(conn->fd, 413, "Payload Too Large");
+        return;
+    }
 
+    /* A chunk we already hold is only checked against its hash. Otherwise
+     * it goes where the filesystem would put it, so replication can't pass
+     * the drive reserve or a dir's cap either.
+     */
     char chunk_path[MAX_PATH_LEN];
-    const char *dir = (conn->config->data_dir_count > 0) ? conn->config->data_dirs[0] : conn->config->data_dir;
-    if (chunk_path_for_hash(dir, hash, chunk_path, sizeof(chunk_path)) != 0) {
-        send_status(conn->fd, 400, "Bad Request");
-        return;
+    char dir[MAX_PATH_LEN] = "";
+    if (find_chunk_path(conn->config, hash, chunk_path, sizeof(chunk_path)) != 0) {
+        int prc = junknas_fuse_store_dir_for_chunk((size_t)content_len, dir, sizeof(dir));
+        if (prc == -ENOSPC) {
+            send_status(conn->fd, 507, "Insufficient Storage");
+            return;
+        }
+        if (prc == -EROFS || prc == -EAGAIN) {
+            if (prc == -EROFS) web_log_verbose(conn->config, "web: refused chunk %s: every data dir is read-only", hash);
+            send_status(conn->fd, 503, "Service Unavailable");
+            return;
+        }
+        if (prc != 0 || chunk_path_for_hash(dir, hash, chunk_path, sizeof(chunk_path)) != 0) {
+            send_status(conn->fd, 500, "Error");
+            return;
+        }
     }
     ensure_parent_dir(chunk_path);
 
-    int out = open(chunk_path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    /* Stream the body into a temp file, hashing as it arrives, and only
+     * rename it into place once the content matches the hash in the path.
+     */
+    char tmp_path[MAX_PATH_LEN];
+    if (snprintf(tmp_path, sizeof(tmp_path), "%s.tmp.%lu", chunk_path,
+                 (unsigned long)pthread_self()) >= (int)sizeof(tmp_path)) {
+        send_status(conn->fd, 500, "Error");
+        return;
+    }
+    int out = open(tmp_path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
     if (out < 0) {
         send_status(conn->fd, 500, "Error");
         return;
     }
 
+    junknas_sha256_ctx ctx;
+    junknas_sha256_init(&ctx);
+    int write_failed = 0;
+
+    if (body_len > (size_t)content_len) body_len = (size_t)content_len;
     if (body_len > 0) {
-        write(out, body, body_len);
+        junknas_sha256_update(&ctx, body, body_len);
+        if (write(out, body, body_len) != (ssize_t)body_len) write_failed = 1;
     }
-    size_t remaining = (size_t)content_len > body_len ? (size_t)content_len - body_len : 0;
+    size_t remaining = (size_t)content_len - body_len;
     char buf[4096];
-    while (remaining > 0) {
+    while (remaining > 0 && !write_failed) {
         ssize_t n = recv(conn->fd, buf, remaining > sizeof(buf) ? sizeof(buf) : remaining, 0);
         if (n <= 0) break;
-        write(out, buf, (size_t)n);
+        junknas_sha256_update(&ctx, buf, (size_t)n);
+        if (write(out, buf, (size_t)n) != n) write_failed = 1;
         remaining -= (size_t)n;
     }
-    close(out);
+    if (close(out) != 0) write_failed = 1;
+
+    if (write_failed) {
+        unlink(tmp_path);
+        send_status(conn->fd, 500, "Error");
+        return;
+    }
+    if (remaining > 0) {
+        unlink(tmp_path);
+        send_status(conn->fd, 400, "Bad Request");
+        return;
+    }
+
+    uint8_t digest[32];
+    char got[65];
+    junknas_sha256_final(&ctx, digest);
+    junknas_sha256_hex(digest, got);
+    int mismatch = 0;
+    for (size_t i = 0; i < 64; i++) {
+        if (tolower((unsigned char)hash[i]) != got[i]) mismatch = 1;
+    }
+    if (mismatch) {
+        unlink(tmp_path);
+        web_log_verbose(conn->config, "web: rejected chunk %s (body hashes to %s)", hash, got);
+        send_status(conn->fd, 422, "Unprocessable Entity");
+        return;
+    }
+    if (access(chunk_path, F_OK) == 0) {
+        unlink(tmp_path); /* already held, or stored by another upload meanwhile */
+        send_text(conn->fd, 200, "OK\n");
+        return;
+    }
+    if (rename(tmp_path, chunk_path) != 0) {
+        unlink(tmp_path);
+        send_status(conn->fd, 500, "Error");
+        return;
+    }
+    junknas_fuse_store_chunk_added(dir, (size_t)content_len);
 
     send_text(conn->fd, 200, "OK\n");
 }
 
-static void handle_connection(web_conn_t *conn) {
-    char buf[WEB_BUF_SIZE];
-    size_t header_len = 0;
-    if (read_headers(conn->fd, buf, sizeof(buf), &header_len) != 0) {
-        send_status(conn->fd, 400, "Bad Request");
+/* Collect a JSON request body: the part that arrived with the headers plus
+ * the rest of Content-Length. Returns 0 with *out malloc'd (nul-terminated),
+ * 413 if the body exceeds max_json_body, or -1 on a short/failed read.
+ */
+static int read_json_body(web_conn_t *conn, const char *headers, const char *initial,
+                          size_t initial_len, char **out) {
+    *out = NULL;
+    junknas_config_lock(conn->config);
+    size_t max_body = conn->config->max_json_body_bytes;
+    junknas_config_unlock(conn->config);
+
+    long content_len = parse_content_length(headers);
+    size_t want = (content_len < 0) ? initial_len : (size_t)content_len;
+    if (want > max_body) return 413;
+    if (initial_len > want) initial_len = want;
+
+    char *json = (char *)malloc(want + 1);
+    if (!json) return -1;
+    memcpy(json, initial, initial_len);
+    size_t have = initial_len;
+    while (have < want) {
+        ssize_t n = recv(conn->fd, json + have, want - have, 0);
+        if (n <= 0) {
+            free(json);
+            return -1;
+        }
+        have += (size_t)n;
+    }
+    json[have] = '\0';
+    *out = json;
+    return 0;
+}
+
+/* POST /log-level {"verbose": bool}: takes effect at once and is saved like /mesh/config. */
+static void respond_log_level(int fd, junknas_config_t *config, const char *body) {
+    cJSON *root = body ? cJSON_Parse(body) : NULL;
+    cJSON *verbose = root ? cJSON_GetObjectItemCaseSensitive(root, "verbose") : NULL;
+    if (!cJSON_IsBool(verbose)) {
+        cJSON_Delete(root);
+        send_status(fd, 400, "Bad Request");
         return;
     }
+    int on = cJSON_IsTrue(verbose) ? 1 : 0;
+    cJSON_Delete(root);
 
-    char *header_end = strstr(buf, "\r\n\r\n");
-    if (!header_end) {
-        send_status(conn->fd, 400, "Bad Request");
+    junknas_config_lock(config);
+    int was = config->verbose;
+    config->verbose = on;
+    (void)junknas_config_save(config, config->config_file_path);
+    junknas_config_unlock(config);
+    if (was != on) {
+        fprintf(stderr, "web: verbose logging %s\n", on ? "enabled" : "disabled");
+    }
+
+    send_json(fd, 200, on ? "{\"verbose\":true}" : "{\"verbose\":false}");
+}
+
+static int json_octal_mode(cJSON *item, mode_t *out) {
+    if (!item) return 0;
+    if (!cJSON_IsString(item) || !item->valuestring || item->valuestring[0] == '\0') return -1;
+    char *end = NULL;
+    unsigned long v = strtoul(item->valuestring, &end, 8);
+    if (*end != '\0' || v > 0777) return -1;
+    *out = (mode_t)v;
+    return 0;
+}
+
+/* POST /fs/set-create-policy {"path": "photos", "mask": "0775", "force": "0664",
+ * "group": 100, "inherit": true}, or {"path": ..., "clear": true}. Stored
+ * through the mount's policy xattr, so the FUSE layer checks and applies it.
+ */
+static void respond_set_create_policy(int fd, junknas_config_t *config, const char *body) {
+    cJSON *root = body ? cJSON_Parse(body) : NULL;
+    cJSON *path = root ? cJSON_GetObjectItemCaseSensitive(root, "path") : NULL;
+    char rel[MAX_PATH_LEN];
+    if (!cJSON_IsString(path) || !path->valuestring ||
+        normalize_relative(path->valuestring, rel, sizeof(rel)) != 0) {
+        cJSON_Delete(root);
+        send_status(fd, 400, "Bad Request");
         return;
     }
-    size_t body_len = header_len - (size_t)(header_end + 4 - buf);
-    const char *body = header_end + 4;
 
-    char method[8];
-    char path[512];
-    if (sscanf(buf, "%7s %511s", method, path) != 2) {
-        send_status(conn->fd, 400, "Bad Request");
+    junknas_create_policy_t policy;
+    junknas_create_policy_default(&policy);
+    int clear = cJSON_IsTrue(cJSON_GetObjectItemCaseSensitive(root, "clear"));
+    cJSON *group = cJSON_GetObjectItemCaseSensitive(root, "group");
+    cJSON *inherit = cJSON_GetObjectItemCaseSensitive(root, "inherit");
+    int bad = json_octal_mode(cJSON_GetObjectItemCaseSensitive(root, "mask"), &policy.mask) != 0 ||
+              json_octal_mode(cJSON_GetObjectItemCaseSensitive(root, "force"), &policy.force) != 0;
+    if (group) {
+        if (!cJSON_IsNumber(group) || group->valuedouble < 0 || group->valuedouble >= 4294967295.0) {
+            bad = 1;
+        } else {
+            policy.has_group = 1;
+            policy.gid = (gid_t)group->valuedouble;
+        }
+    }
+    if (inherit) {
+        if (!cJSON_IsBool(inherit)) bad = 1;
+        policy.inherit = cJSON_IsTrue(inherit);
+    }
+    cJSON_Delete(root);
+    if (bad) {
+        send_status(fd, 400, "Bad Request");
         return;
     }
 
-    if (strcmp(method, "GET") == 0) {
-        handle_get(conn, path);
+    char full_path[MAX_PATH_LEN];
+    junknas_config_lock(config);
+    int n = snprintf(full_path, sizeof(full_path), "%s/%s", config->mount_point, rel);
+    junknas_config_unlock(config);
+    struct stat st;
+    if (n >= (int)sizeof(full_path) || stat(full_path, &st) != 0 || !S_ISDIR(st.st_mode)) {
+        send_status(fd, 404, "Not Found");
         return;
     }
 
-    if (strcmp(method, "POST") == 0) {
-        if (strcmp(path, "/mesh/peers") == 0) {
-            int updated = merge_mesh_payload(conn->config, body);
-            if (updated >= 0) {
-                respond_mesh_state(conn->fd, conn->config);
-            } else {
-                send_status(conn->fd, 400, "Bad Request");
-            }
-            return;
+    int rc;
+    if (clear) {
+        rc = removexattr(full_path, JUNKNAS_CREATE_POLICY_XATTR);
+        if (rc != 0 && errno == ENODATA) rc = 0;
+    } else {
+        char text[JUNKNAS_CREATE_POLICY_TEXT_LEN];
+        int len = junknas_create_policy_format(&policy, text, sizeof(text));
+        rc = (len < 0) ? -1 : setxattr(full_path, JUNKNAS_CREATE_POLICY_XATTR, text, (size_t)len, 0);
+    }
+    if (rc != 0) {
+        int err = errno;
+        fprintf(stderr, "web: create policy for /%s: %s\n", rel, strerror(err));
+        if (err == EPERM || err == EACCES) {
+            send_status(fd, 403, "Forbidden");
+        } else {
+            send_status(fd, 500, "Error");
         }
-        if (strcmp(path, "/mesh/bootstrap") == 0) {
-            (void)respond_mesh_bootstrap(conn->fd, conn->config);
+        return;
+    }
+
+    cJSON *out = cJSON_CreateObject();
+    if (!out) {
+        send_status(fd, 500, "Error");
+        return;
+    }
+    char path_out[MAX_PATH_LEN + 1];
+    snprintf(path_out, sizeof(path_out), "/%s", rel);
+    cJSON_AddStringToObject(out, "path", path_out);
+    if (clear) {
+        cJSON_AddNullToObject(out, "policy");
+    } else {
+        char mode_text[8];
+        cJSON *p = cJSON_AddObjectToObject(out, "policy");
+        if (!p) {
+            cJSON_Delete(out);
+            send_status(fd, 500, "Error");
             return;
         }
-        if (strcmp(path, "/mesh/join") == 0) {
-            (void)respond_mesh_join(conn->fd, conn->config, body);
-            return;
+        snprintf(mode_text, sizeof(mode_text), "%04o", (unsigned int)policy.mask);
+        cJSON_AddStringToObject(p, "mask", mode_text);
+        snprintf(mode_text, sizeof(mode_text), "%04o", (unsigned int)policy.force);
+        cJSON_AddStringToObject(p, "force", mode_text);
+        if (policy.has_group) cJSON_AddNumberToObject(p, "group", (double)policy.gid);
+        cJSON_AddBoolToObject(p, "inherit", policy.inherit ? 1 : 0);
+    }
+    char *text = cJSON_PrintUnformatted(out);
+    cJSON_Delete(out);
+    if (!text) {
+        send_status(fd, 500, "Error");
+        return;
+    }
+    send_json(fd, 200, text);
+    free(text);
+}
+
+static void handle_post(web_conn_t *conn, const char *path, const char *body) {
+    if (strcmp(path, "/log-level") == 0) {
+        respond_log_level(conn->fd, conn->config, body);
+        return;
+    }
+    if (strcmp(path, "/fs/set-create-policy") == 0) {
+        respond_set_create_policy(conn->fd, conn->config, body);
+        return;
+    }
+    if (strcmp(path, "/dedup/verify") == 0) {
+        respond_dedup_verify(conn->fd, conn->config, body);
+        return;
+    }
+    if (strcmp(path, "/mesh/peers") == 0) {
+        int updated = merge_mesh_payload(conn->config, body);
+        if (updated >= 0) {
+            respond_mesh_state(conn->fd, conn->config);
+        } else {
+            send_status(conn->fd, 400, "Bad Request");
+        }
+        return;
+    }
+    if (strcmp(path, "/mesh/bootstrap") == 0) {
+        (void)respond_mesh_bootstrap(conn->fd, conn->config);
+        return;
+    }
+    if (strcmp(path, "/mesh/join") == 0) {
+        (void)respond_mesh_join(conn->fd, conn->config, body);
+        return;
+    }
+    if (strcmp(path, "/mesh/alternate") == 0) {
+        (void)respond_mesh_alternate(conn->fd, conn->config, body);
+        return;
+    }
+    if (strcmp(path, "/mesh/config") == 0) {
+        if (update_mesh_config(conn->config, body) == 0) {
+            respond_mesh_config(conn->fd, conn->config);
+        } else {
+            send_status(conn->fd, 400, "Bad Request");
         }
-        if (strcmp(path, "/mesh/alternate") == 0) {
-            (void)respond_mesh_alternate(conn->fd, conn->config, body);
+        return;
+    }
+    if (strcmp(path, "/mesh/sync") == 0) {
+        cJSON *payload_json = build_mesh_state_json(conn->config);
+        if (!payload_json) {
+            send_status(conn->fd, 500, "Error");
             return;
         }
-        if (strcmp(path, "/mesh/config") == 0) {
-            if (update_mesh_config(conn->config, body) == 0) {
-                respond_mesh_config(conn->fd, conn->config);
-            } else {
-                send_status(conn->fd, 400, "Bad Request");
-            }
+        char *payload = cJSON_PrintUnformatted(payload_json);
+        cJSON_Delete(payload_json);
+        if (!payload) {
+            send_status(conn->fd, 500, "Error");
             return;
         }
-        if (strcmp(path, "/mesh/sync") == 0) {
-            cJSON *payload_json = build_mesh_state_json(conn->config);
-            if (!payload_json) {
-                send_status(conn->fd, 500, "Error");
-                return;
-            }
-            char *payload = cJSON_PrintUnformatted(payload_json);
-            cJSON_Delete(payload_json);
-            if (!payload) {
-                send_status(conn->fd, 500, "Error");
-                return;
-            }
 
+        junknas_config_lock(conn->config);
+        int bootstrap_count = conn->config->bootstrap_peer_count;
+        char bootstrap[MAX_BOOTSTRAP_PEERS][MAX_ENDPOINT_LEN];
+        for (int i = 0; i < bootstrap_count; i++) {
+            snprintf(bootstrap[i], sizeof(bootstrap[i]), "%s", conn->config->bootstrap_peers[i]);
+        }
+        int wg_count = conn->config->wg_peer_count;
+        junknas_wg_peer_t wg_peers[MAX_WG_PEERS];
+        if (wg_count > MAX_WG_PEERS) wg_count = MAX_WG_PEERS;
+        for (int i = 0; i < wg_count; i++) {
+            wg_peers[i] = conn->config->wg_peers[i];
+        }
+        uint16_t default_web_port = conn->config->web_port;
+        junknas_config_unlock(conn->config);
+
+        int synced = 0;
+        for (int i = 0; i < bootstrap_count; i++) {
+            int rc = sync_mesh_with_peer(conn->config, bootstrap[i], payload);
             junknas_config_lock(conn->config);
-            int bootstrap_count = conn->config->bootstrap_peer_count;
-            char bootstrap[MAX_BOOTSTRAP_PEERS][MAX_ENDPOINT_LEN];
-            for (int i = 0; i < bootstrap_count; i++) {
-                snprintf(bootstrap[i], sizeof(bootstrap[i]), "%s", conn->config->bootstrap_peers[i]);
-            }
-            int wg_count = conn->config->wg_peer_count;
-            junknas_wg_peer_t wg_peers[MAX_WG_PEERS];
-            if (wg_count > MAX_WG_PEERS) wg_count = MAX_WG_PEERS;
-            for (int i = 0; i < wg_count; i++) {
-                wg_peers[i] = conn->config->wg_peers[i];
-            }
-            uint16_t default_web_port = conn->config->web_port;
+            (void)junknas_config_set_bootstrap_peer_status(conn->config, i, (rc == 0) ? 1 : 0);
             junknas_config_unlock(conn->config);
+            if (rc == 0) synced++;
+        }
 
-            int synced = 0;
-            for (int i = 0; i < bootstrap_count; i++) {
-                int rc = sync_mesh_with_peer(conn->config, bootstrap[i], payload);
-                junknas_config_lock(conn->config);
-                conn->config->bootstrap_peer_status[i] = (rc == 0) ? 1 : 0;
-                junknas_config_unlock(conn->config);
-                if (rc == 0) synced++;
-            }
+        for (int i = 0; i < wg_count; i++) {
+            junknas_mesh_candidate_t candidates[JUNKNAS_MESH_MAX_CANDIDATES];
+            int candidate_count = junknas_mesh_peer_candidates(&wg_peers[i], default_web_port, candidates);
+            if (candidate_count == 0) continue;
 
-            for (int i = 0; i < wg_count; i++) {
+            /* Same order as the periodic sync: overlay first, then the endpoint. */
+            int rc = -1;
+            for (int c = 0; c < candidate_count && rc != 0; c++) {
                 char endpoint[MAX_ENDPOINT_LEN];
-                if (wg_peers[i].endpoint[0] != '\0') {
-                    snprintf(endpoint, sizeof(endpoint), "%s", wg_peers[i].endpoint);
+                if (strchr(candidates[c].host, ':')) {
+                    snprintf(endpoint, sizeof(endpoint), "[%s]:%u", candidates[c].host, candidates[c].port);
                 } else {
-                    uint16_t web_port = wg_peers[i].web_port ? wg_peers[i].web_port : default_web_port;
-                    snprintf(endpoint, sizeof(endpoint), "%s:%u", wg_peers[i].wg_ip, web_port);
+                    snprintf(endpoint, sizeof(endpoint), "%s:%u", candidates[c].host, candidates[c].port);
                 }
-                int rc = sync_mesh_with_peer(conn->config, endpoint, payload);
-                junknas_config_lock(conn->config);
-                conn->config->wg_peer_status[i] = (rc == 0) ? 1 : 0;
-                junknas_config_unlock(conn->config);
-                if (rc == 0) synced++;
+                rc = sync_mesh_with_peer(conn->config, endpoint, payload);
             }
+            junknas_config_lock(conn->config);
+            (void)junknas_config_set_wg_peer_status(conn->config, i, (rc == 0) ? 1 : 0);
+            junknas_config_unlock(conn->config);
+            if (rc == 0) synced++;
+        }
 
-            free(payload);
-            char response[128];
-            snprintf(response, sizeof(response), "{\"synced\":%d}", synced);
-            send_json(conn->fd, 200, response);
+        free(payload);
+        char response[128];
+        snprintf(response, sizeof(response), "{\"synced\":%d}", synced);
+        send_json(conn->fd, 200, response);
+        return;
+    }
+    send_status(conn->fd, 404, "Not Found");
+}
+
+/* X-Junknas-Request-Id from a peer, if present and made of [0-9A-Za-z-]. */
+static int request_id_from_headers(const char *headers, char *out, size_t out_len) {
+    const char *v = find_header_case_insensitive(headers, "X-Junknas-Request-Id:");
+    if (!v) return 0;
+    v += strlen("X-Junknas-Request-Id:");
+    while (*v == ' ' || *v == '\t') v++;
+    size_t n = 0;
+    while ((isalnum((unsigned char)v[n]) || v[n] == '-') && n + 1 < out_len) {
+        out[n] = v[n];
+        n++;
+    }
+    out[n] = '\0';
+    return n > 0;
+}
+
+static void handle_connection(web_conn_t *conn) {
+    char buf[WEB_BUF_SIZE];
+    size_t header_len = 0;
+    if (read_headers(conn->fd, buf, sizeof(buf), &header_len) != 0) {
+        send_status(conn->fd, 400, "Bad Request");
+        return;
+    }
+
+    char *header_end = strstr(buf, "\r\n\r\n");
+    if (!header_end) {
+        send_status(conn->fd, 400, "Bad Request");
+        return;
+    }
+    size_t body_len = header_len - (size_t)(header_end + 4 - buf);
+    const char *body = header_end + 4;
+
+    char method[8];
+    char path[512];
+    if (sscanf(buf, "%7s %511s", method, path) != 2) {
+        send_status(conn->fd, 400, "Bad Request");
+        return;
+    }
+
+    if (strncmp(path, "/chunks/", 8) == 0) {
+        char req_id[33];
+        if (request_id_from_headers(buf, req_id, sizeof(req_id))) {
+            web_log_verbose(conn->config, "web: [req %s] %s %s", req_id, method, path);
+        }
+    }
+
+    if (strncmp(path, "/chunks/", 8) == 0) {
+        int denied = chunk_request_check(conn, method, path, buf);
+        if (denied != 0) {
+            send_status(conn->fd, denied, denied == 403 ? "Forbidden" : "Unauthorized");
+            return;
+        }
+    }
+
+    if (strcmp(method, "GET") == 0) {
+        if (strcmp(path, "/events") == 0) {
+            respond_events(conn, buf);
             return;
         }
+        handle_get(conn, path);
+        return;
+    }
+
+    if (strcmp(method, "POST") == 0) {
         if (strncmp(path, "/chunks/", 8) == 0) {
             handle_post_chunk(conn, path + 8, buf, body, body_len);
             return;
         }
-        send_status(conn->fd, 404, "Not Found");
+        char *json = NULL;
+        int rc = read_json_body(conn, buf, body, body_len, &json);
+        if (rc == 413) {
+            send_status(conn->fd, 413, "Payload Too Large");
+            return;
+        }
+        if (rc != 0) {
+            send_status(conn->fd, 400, "Bad Request");
+            return;
+        }
+        handle_post(conn, path, json);
+        free(json);
         return;
     }
 
@@ -1482,6 +2529,7 @@ junknas_web_server_t *junknas_web_server_start(junknas_config_t *config) {
     }
 
     server->config = config;
+    g_web_stopping = 0;
     server->fd = socket(AF_INET, SOCK_STREAM, 0);
     if (server->fd < 0) {
         web_log_verbose(config, "web: failed to create socket");
@@ -1512,6 +2560,20 @@ junknas_web_server_t *junknas_web_server_start(junknas_config_t *config) {
         return NULL;
     }
 
+    /* Advertise the port we actually hold, not the one we asked for. */
+    struct sockaddr_in bound;
+    socklen_t bound_len = sizeof(bound);
+    if (getsockname(server->fd, (struct sockaddr *)&bound, &bound_len) == 0) {
+        uint16_t actual = ntohs(bound.sin_port);
+        junknas_config_lock(config);
+        if (actual != 0 && actual != config->web_port) {
+            fprintf(stderr, "web: bound port %u differs from configured %u; advertising %u\n",
+                    actual, config->web_port, actual);
+            config->web_port = actual;
+        }
+        junknas_config_unlock(config);
+    }
+
     if (pthread_create(&server->thread, NULL, server_thread, server) != 0) {
         web_log_verbose(config, "web: failed to start web server thread");
         close(server->fd);
@@ -1520,12 +2582,19 @@ junknas_web_server_t *junknas_web_server_start(junknas_config_t *config) {
     }
 
     web_log_verbose(config, "web: server listening on port %u", config->web_port);
+    junknas_config_lock(config);
+    int have_secret = (config->mesh_secret[0] != '\0');
+    junknas_config_unlock(config);
+    if (!have_secret) {
+        fprintf(stderr, "web: mesh_secret is not set; peers' /chunks requests will be refused\n");
+    }
     return server;
 }
 
 void junknas_web_server_stop(junknas_web_server_t *server) {
     if (!server) return;
     server->stop = 1;
+    g_web_stopping = 1;
     if (server->fd >= 0) close(server->fd);
     pthread_join(server->thread, NULL);
     free(server);